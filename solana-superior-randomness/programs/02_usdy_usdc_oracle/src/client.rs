@@ -0,0 +1,1099 @@
+//! Off-chain helpers for talking to the vault: PDA derivation, instruction builders and
+//! account decoders. Account lists come from the Anchor-generated `accounts` structs, so a
+//! builder can't drift from the order the program reads them in.
+use crate::*;
+use anchor_lang::{Discriminator, InstructionData, ToAccountMetas};
+use anchor_lang::solana_program::pubkey::PubkeyError;
+use anchor_spl::associated_token::{get_associated_token_address, get_associated_token_address_with_program_id};
+use solend_sdk::state::Reserve;
+use spl_stake_pool::state::StakePool;
+
+pub fn program_state_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[PROGRAM_SEED], program_id).0
+}
+
+pub fn oracle_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[ORACLE_SEED], program_id).0
+}
+
+/// `[jarezi, thewinnerog]`, the vault itself.
+pub fn marginfi_pda_address(program_id: &Pubkey, thewinnerog: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[SEED_PREFIX, thewinnerog.as_ref()], program_id).0
+}
+
+/// `[jarezi, marginfi_pda]`, the vault's Switchboard function binding.
+pub fn marginfi_pda_switchboard_address(program_id: &Pubkey, marginfi_pda: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[SEED_PREFIX, marginfi_pda.as_ref()], program_id).0
+}
+
+pub fn request_address(program_id: &Pubkey, marginfi_pda: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[REQUEST_SEED, marginfi_pda.as_ref()], program_id).0
+}
+
+pub fn holder_snapshot_address(program_id: &Pubkey, marginfi_pda: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[HOLDER_SNAPSHOT_SEED, marginfi_pda.as_ref()], program_id).0
+}
+
+pub fn leverage_config_address(program_id: &Pubkey, marginfi_pda: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[LEVERAGE_SEED, marginfi_pda.as_ref()], program_id).0
+}
+
+pub fn auto_buy_config_address(program_id: &Pubkey, marginfi_pda: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[AUTO_BUY_SEED, marginfi_pda.as_ref()], program_id).0
+}
+
+pub fn collateral_pool_address(program_id: &Pubkey, marginfi_pda: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[COLLATERAL_POOL_SEED, marginfi_pda.as_ref()], program_id).0
+}
+
+/// Solend obligation created by `init_mrgn_fi_pda` with `seed2` and the vault as base.
+pub fn obligation_address(marginfi_pda: &Pubkey, seed: &str) -> Pubkey {
+    Pubkey::create_with_seed(marginfi_pda, seed, &SolendProgram::id()).unwrap()
+}
+
+/// Every PDA hanging off one vault, derived once.
+#[derive(Clone, Copy, Debug)]
+pub struct VaultAddresses {
+    /// The deployment the vault lives under, every builder below targets it
+    pub program_id: Pubkey,
+    pub thewinnerog: Pubkey,
+    pub program_state: Pubkey,
+    pub oracle: Pubkey,
+    pub marginfi_pda: Pubkey,
+    pub marginfi_pda_switchboard: Pubkey,
+    pub request: Pubkey,
+    pub holder_snapshot: Pubkey,
+    pub leverage_config: Pubkey,
+    pub auto_buy_config: Pubkey,
+    pub collateral_pool: Pubkey,
+}
+
+impl VaultAddresses {
+    pub fn new(program_id: Pubkey, thewinnerog: Pubkey) -> Self {
+        let marginfi_pda = marginfi_pda_address(&program_id, &thewinnerog);
+        Self {
+            program_id,
+            thewinnerog,
+            program_state: program_state_address(&program_id),
+            oracle: oracle_address(&program_id),
+            marginfi_pda,
+            marginfi_pda_switchboard: marginfi_pda_switchboard_address(&program_id, &marginfi_pda),
+            request: request_address(&program_id, &marginfi_pda),
+            holder_snapshot: holder_snapshot_address(&program_id, &marginfi_pda),
+            leverage_config: leverage_config_address(&program_id, &marginfi_pda),
+            auto_buy_config: auto_buy_config_address(&program_id, &marginfi_pda),
+            collateral_pool: collateral_pool_address(&program_id, &marginfi_pda),
+        }
+    }
+
+    /// The vault's associated account for a mint, e.g. its collateral pool tokens.
+    pub fn pool_token_account(&self, pool_mint: &Pubkey) -> Pubkey {
+        get_associated_token_address(&self.marginfi_pda, pool_mint)
+    }
+}
+
+fn build(program_id: &Pubkey, accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// Program state and oracle
+
+pub fn initialize(
+    program_id: &Pubkey,
+    authority: Pubkey,
+    payer: Pubkey,
+    switchboard_function: Option<Pubkey>,
+) -> Instruction {
+    let (program, bump) = Pubkey::find_program_address(&[PROGRAM_SEED], program_id);
+    let (oracle, bump2) = Pubkey::find_program_address(&[ORACLE_SEED], program_id);
+    build(
+        program_id,
+        crate::accounts::Initialize {
+            program,
+            oracle,
+            authority,
+            switchboard_function,
+            payer,
+            system_program: system_program::ID,
+        },
+        crate::instruction::Initialize { bump, bump2 },
+    )
+}
+
+/// Grows the program state and oracle to the current layout, see [`MigrateProgram`].
+pub fn migrate_program(program_id: &Pubkey, authority: Pubkey) -> Instruction {
+    build(
+        program_id,
+        crate::accounts::MigrateProgram {
+            program: program_state_address(program_id),
+            oracle: oracle_address(program_id),
+            authority,
+            system_program: system_program::ID,
+        },
+        crate::instruction::MigrateProgram {},
+    )
+}
+
+pub fn update(program_id: &Pubkey, authority: Pubkey, switchboard_function: Option<Pubkey>) -> Instruction {
+    build(
+        program_id,
+        crate::accounts::UpdateProgram {
+            program: program_state_address(program_id),
+            authority,
+            switchboard_function,
+        },
+        crate::instruction::Update {},
+    )
+}
+
+pub fn set_oracle_paused(program_id: &Pubkey, signer: Pubkey, paused: bool) -> Instruction {
+    build(
+        program_id,
+        crate::accounts::SetOraclePaused {
+            program: program_state_address(program_id),
+            signer,
+        },
+        crate::instruction::SetOraclePaused { paused },
+    )
+}
+
+pub fn update_oracle_guardian(program_id: &Pubkey, authority: Pubkey, guardian: Pubkey) -> Instruction {
+    build(
+        program_id,
+        crate::accounts::UpdateOracleGuardian {
+            program: program_state_address(program_id),
+            authority,
+        },
+        crate::instruction::UpdateOracleGuardian { guardian },
+    )
+}
+
+pub fn propose_program_authority(program_id: &Pubkey, authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    build(
+        program_id,
+        crate::accounts::ProposeProgramAuthority {
+            program: program_state_address(program_id),
+            authority,
+        },
+        crate::instruction::ProposeProgramAuthority { new_authority },
+    )
+}
+
+pub fn accept_program_authority(program_id: &Pubkey, pending_authority: Pubkey) -> Instruction {
+    build(
+        program_id,
+        crate::accounts::AcceptProgramAuthority {
+            program: program_state_address(program_id),
+            pending_authority,
+        },
+        crate::instruction::AcceptProgramAuthority {},
+    )
+}
+
+fn manage_oracle_symbol(program_id: &Pubkey, authority: Pubkey) -> crate::accounts::ManageOracleSymbol {
+    crate::accounts::ManageOracleSymbol {
+        program: program_state_address(program_id),
+        oracle: oracle_address(program_id),
+        authority,
+    }
+}
+
+pub fn register_oracle_symbol(program_id: &Pubkey, authority: Pubkey, symbol: TradingSymbol) -> Instruction {
+    build(
+        program_id,
+        manage_oracle_symbol(program_id, authority),
+        crate::instruction::RegisterOracleSymbol { symbol },
+    )
+}
+
+pub fn retire_oracle_symbol(program_id: &Pubkey, authority: Pubkey, symbol: TradingSymbol) -> Instruction {
+    build(
+        program_id,
+        manage_oracle_symbol(program_id, authority),
+        crate::instruction::RetireOracleSymbol { symbol },
+    )
+}
+
+pub fn configure_oracle_feed(
+    program_id: &Pubkey,
+    authority: Pubkey,
+    params: ConfigureOracleFeedParams,
+) -> Instruction {
+    build(
+        program_id,
+        manage_oracle_symbol(program_id, authority),
+        crate::instruction::ConfigureOracleFeed { params },
+    )
+}
+
+/// `vaults` get their circuit breakers run against the push, each must have its collateral
+/// pool created.
+pub fn refresh_oracles(
+    program_id: &Pubkey,
+    switchboard_function: Pubkey,
+    enclave_signer: Pubkey,
+    params: RefreshOraclesParams,
+    vaults: &[VaultAddresses],
+) -> Instruction {
+    let mut ixn = build(
+        program_id,
+        crate::accounts::RefreshOracles {
+            program: program_state_address(program_id),
+            oracle: oracle_address(program_id),
+            switchboard_function,
+            enclave_signer,
+        },
+        crate::instruction::RefreshOracles { params },
+    );
+    ixn.accounts.extend(vaults.iter().flat_map(|vault| {
+        [
+            AccountMeta::new(vault.marginfi_pda, false),
+            AccountMeta::new_readonly(vault.collateral_pool, false),
+        ]
+    }));
+    ixn
+}
+
+pub fn trigger_function(
+    program_id: &Pubkey,
+    switchboard_function: Pubkey,
+    authority: Pubkey,
+    attestation_queue: Pubkey,
+) -> Instruction {
+    build(
+        program_id,
+        crate::accounts::TriggerFunction {
+            program: program_state_address(program_id),
+            switchboard_function,
+            authority,
+            attestation_queue,
+            attestation_program: SWITCHBOARD_ATTESTATION_PROGRAM_ID,
+        },
+        crate::instruction::TriggerFunction {},
+    )
+}
+
+// Vault setup. These take the generated accounts struct as is, VaultAddresses has builders
+// that resolve the accounts from the vault's on-chain state.
+
+pub fn init_mrgn_fi_pda(
+    program_id: &Pubkey,
+    accounts: crate::accounts::InitMrgnFiPda,
+    kickback: u64,
+    seeded_seed: String,
+    seed2: String,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Instruction {
+    let (_, bump) = Pubkey::find_program_address(
+        &[SEED_PREFIX, accounts.winner_winner_chickum_dinner.as_ref()],
+        program_id,
+    );
+    build(
+        program_id,
+        accounts,
+        crate::instruction::InitMrgnFiPda {
+            bump,
+            kickback,
+            seeded_seed,
+            seed2,
+            name,
+            symbol,
+            uri,
+        },
+    )
+}
+
+pub fn create_seeded_account(
+    program_id: &Pubkey,
+    accounts: crate::accounts::CreateSeededAccount,
+    params: CreateSeededAccountParams,
+) -> Instruction {
+    build(program_id, accounts, crate::instruction::CreateSeededAccount { params })
+}
+
+pub fn deposit(program_id: &Pubkey, mut accounts: crate::accounts::Deposit, amount: u64) -> Instruction {
+    accounts.user_wsol_account = None;
+    build(program_id, accounts, crate::instruction::Deposit { amount })
+}
+
+/// Has to sit between a top-level Solend flash borrow and repay into `user_wsol_account`, a
+/// wSOL account owned by the signer.
+pub fn withdraw(
+    program_id: &Pubkey,
+    mut accounts: crate::accounts::Deposit,
+    user_wsol_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    accounts.user_wsol_account = Some(user_wsol_account);
+    build(program_id, accounts, crate::instruction::Withdraw { amount })
+}
+
+/// `[lending_market]` under Solend, the authority over every reserve of a market.
+pub fn lending_market_authority_address(lending_market: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[lending_market.as_ref()], &SolendProgram::id()).0
+}
+
+/// A Solend reserve's accounts as its CPIs take them.
+#[derive(Clone, Copy, Debug)]
+pub struct ReserveAddresses {
+    pub reserve: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_supply: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub collateral_supply: Pubkey,
+    pub fee_receiver: Pubkey,
+    pub pyth_oracle: Pubkey,
+    pub switchboard_oracle: Pubkey,
+}
+
+impl ReserveAddresses {
+    pub fn new(reserve: Pubkey, state: &Reserve) -> Self {
+        Self {
+            reserve,
+            liquidity_mint: state.liquidity.mint_pubkey,
+            liquidity_supply: state.liquidity.supply_pubkey,
+            collateral_mint: state.collateral.mint_pubkey,
+            collateral_supply: state.collateral.supply_pubkey,
+            fee_receiver: state.config.fee_receiver,
+            pyth_oracle: state.liquidity.pyth_oracle_pubkey,
+            switchboard_oracle: state.liquidity.switchboard_oracle_pubkey,
+        }
+    }
+}
+
+/// The pool's own accounts the stake and unstake legs go through. A Marinade pool fills the
+/// stake pool slots with its State's accounts, see the marinade module.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolAddresses {
+    pub withdraw_authority: Pubkey,
+    pub reserve_stake: Pubkey,
+    pub manager_fee_account: Pubkey,
+    /// liq_pool_sol_leg_pda, liq_pool_msol_leg_authority and treasury_msol_account
+    pub marinade: Option<[Pubkey; 3]>,
+}
+
+impl PoolAddresses {
+    pub fn spl(stake_pool_program: &Pubkey, stake_pool_key: &Pubkey, stake_pool: &StakePool) -> Self {
+        let (withdraw_authority, _) =
+            spl_stake_pool::find_withdraw_authority_program_address(stake_pool_program, stake_pool_key);
+        Self {
+            withdraw_authority,
+            reserve_stake: stake_pool.reserve_stake,
+            manager_fee_account: stake_pool.manager_fee_account,
+            marinade: None,
+        }
+    }
+
+    /// `state` is the Marinade State account's data. None if it is too short to be one.
+    pub fn marinade(marinade_program: &Pubkey, state_key: &Pubkey, state: &[u8]) -> Option<Self> {
+        Some(Self {
+            withdraw_authority: marinade_address(marinade_program, state_key, MARINADE_MSOL_MINT_AUTHORITY_SEED),
+            reserve_stake: marinade_address(marinade_program, state_key, MARINADE_RESERVE_SEED),
+            manager_fee_account: marinade_liq_pool_msol_leg(state)?,
+            marinade: Some([
+                marinade_address(marinade_program, state_key, MARINADE_LIQ_POOL_SOL_LEG_SEED),
+                marinade_address(marinade_program, state_key, MARINADE_LIQ_POOL_MSOL_LEG_AUTHORITY_SEED),
+                marinade_treasury_msol_account(state)?,
+            ]),
+        })
+    }
+}
+
+/// Accounts a vault's deposits and withdrawals go through that aren't derived from the vault:
+/// the current winner, the jarezi mint, the Solend obligation and reserves, the stake pool and
+/// the hydra collecting referral and host fees. Built once from the on-chain state.
+#[derive(Clone, Copy, Debug)]
+pub struct VaultMarket {
+    pub winner: Pubkey,
+    pub jarezi_mint: Pubkey,
+    pub obligation: Pubkey,
+    pub hydra: Pubkey,
+    pub lending_market: Pubkey,
+    /// Reserve the pool tokens are deposited into, the CollateralPool's
+    pub collateral_reserve: ReserveAddresses,
+    /// wSOL reserve the vault borrows from
+    pub sol_reserve: ReserveAddresses,
+    pub stake_pool_program: Pubkey,
+    pub stake_pool: Pubkey,
+    pub pool_mint: Pubkey,
+    pub pool: PoolAddresses,
+}
+
+impl VaultMarket {
+    /// `obligation` is `obligation_address` of the seed the vault was created with.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        marginfi_pda: &MarginFiPda,
+        collateral_pool: &CollateralPool,
+        collateral_reserve: &Reserve,
+        sol_reserve: (Pubkey, &Reserve),
+        pool: PoolAddresses,
+        jarezi_mint: Pubkey,
+        obligation: Pubkey,
+        hydra: Pubkey,
+    ) -> Self {
+        Self {
+            winner: marginfi_pda.winner_winner_chickum_dinner,
+            jarezi_mint,
+            obligation,
+            hydra,
+            lending_market: collateral_reserve.lending_market,
+            collateral_reserve: ReserveAddresses::new(collateral_pool.reserve, collateral_reserve),
+            sol_reserve: ReserveAddresses::new(sol_reserve.0, sol_reserve.1),
+            stake_pool_program: collateral_pool.stake_pool_program,
+            stake_pool: collateral_pool.stake_pool,
+            pool_mint: collateral_pool.pool_mint,
+            pool,
+        }
+    }
+}
+
+impl VaultAddresses {
+    // Vault setup and deposits, with every account resolved
+
+    /// `init_mrgn_fi_pda` for the vault these addresses were derived from. The handler seeds
+    /// the new obligation by depositing into `deposit_reserve` and borrowing from
+    /// `borrow_reserve`, both out of the vault's associated accounts. `jarezi_mint` is a fresh
+    /// keypair that signs the transaction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_mrgn_fi_pda(
+        &self,
+        authority: Pubkey,
+        jarezi_mint: Pubkey,
+        lending_market: Pubkey,
+        deposit_reserve: &ReserveAddresses,
+        borrow_reserve: &ReserveAddresses,
+        kickback: u64,
+        seeded_seed: String,
+        seed2: String,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<Instruction, PubkeyError> {
+        let obligation = Pubkey::create_with_seed(&self.marginfi_pda, &seed2, &SolendProgram::id())?;
+        let accounts = crate::accounts::InitMrgnFiPda {
+            marginfi_pda: self.marginfi_pda,
+            winner_winner_chickum_dinner: self.thewinnerog,
+            authority,
+            system_program: system_program::ID,
+            // read by nothing
+            marginfi_group: system_program::ID,
+            marginfi_program: system_program::ID,
+            jarezi_mint,
+            token_program_2022: anchor_spl::token_interface::Token2022::id(),
+            to: obligation,
+            base: self.marginfi_pda,
+            owner: SolendProgram::id(),
+            lending_market,
+            solend_sdk: SolendProgram::id(),
+            rent: anchor_lang::solana_program::sysvar::rent::ID,
+            token_program: anchor_spl::token::ID,
+            marginfi_bank_wsol: deposit_reserve.reserve,
+            pool_token_receiver_account_wsol: self.pool_token_account(&deposit_reserve.liquidity_mint),
+            liquidity_vault_wsol: deposit_reserve.liquidity_supply,
+            pool_mint_wsol: deposit_reserve.liquidity_mint,
+            stake_pool_withdraw_authority_wsol: deposit_reserve.fee_receiver,
+            stake_pool_withdraw_authority_wsol2: borrow_reserve.fee_receiver,
+            lending_market_pubkey: lending_market,
+            lending_market_authority_pubkey: lending_market_authority_address(&lending_market),
+            marginfi_bank_wsol2: borrow_reserve.reserve,
+            pool_token_receiver_account_wsol2: self.pool_token_account(&borrow_reserve.liquidity_mint),
+            liquidity_vault_wsol2: borrow_reserve.liquidity_supply,
+            pool_mint_wsol2: borrow_reserve.liquidity_mint,
+            destination_deposit_collateral_pubkey2: borrow_reserve.collateral_supply,
+            reserve_collateral_mint_pubkey2: borrow_reserve.collateral_mint,
+            // the handler passes these two the other way round to Solend: the vault's
+            // collateral account receives the cTokens, the reserve's supply the deposit
+            destination_deposit_collateral_pubkey: self.pool_token_account(&deposit_reserve.collateral_mint),
+            reserve_collateral_mint_pubkey: deposit_reserve.collateral_mint,
+            pyth_oracle: deposit_reserve.pyth_oracle,
+            switchboard_oracle: deposit_reserve.switchboard_oracle,
+            pyth_oracle2: borrow_reserve.pyth_oracle,
+            switchboard_oracle2: borrow_reserve.switchboard_oracle,
+            user_collateral_pubkey: deposit_reserve.collateral_supply,
+        };
+        Ok(init_mrgn_fi_pda(&self.program_id, accounts, kickback, seeded_seed, seed2, name, symbol, uri))
+    }
+
+    /// Creates `params.seed`'s account with the vault as base, paid by `from`.
+    pub fn create_seeded_account(
+        &self,
+        from: Pubkey,
+        owner: Pubkey,
+        lending_market: Pubkey,
+        params: CreateSeededAccountParams,
+    ) -> Result<Instruction, PubkeyError> {
+        let to = Pubkey::create_with_seed(&self.marginfi_pda, &params.seed, &owner)?;
+        Ok(create_seeded_account(
+            &self.program_id,
+            crate::accounts::CreateSeededAccount {
+                from,
+                to,
+                base: self.marginfi_pda,
+                owner,
+                system_program: system_program::ID,
+                program: self.marginfi_pda,
+                winner_winner_chickum_dinner: self.thewinnerog,
+                lending_market,
+                solend_sdk: SolendProgram::id(),
+                rent: anchor_lang::solana_program::sysvar::rent::ID,
+                token_program: anchor_spl::token::ID,
+            },
+            params,
+        ))
+    }
+
+    /// Deposit and withdraw accounts for `user`, who signs and holds the jarezi.
+    pub fn deposit_accounts(&self, market: &VaultMarket, user: Pubkey) -> crate::accounts::Deposit {
+        let marinade = market.pool.marinade;
+        crate::accounts::Deposit {
+            signer: user,
+            marginfi_pda: self.marginfi_pda,
+            winner_winner_chickum_dinner: market.winner,
+            pool_token_receiver_account: self.pool_token_account(&market.pool_mint),
+            stake_pool: market.stake_pool,
+            stake_pool_withdraw_authority: market.pool.withdraw_authority,
+            reserve_stake_account: market.pool.reserve_stake,
+            manager_fee_account: market.pool.manager_fee_account,
+            pool_mint: market.pool_mint,
+            stake_pool_program: market.stake_pool_program,
+            liq_pool_sol_leg_pda: marinade.map(|accounts| accounts[0]),
+            liq_pool_msol_leg_authority: marinade.map(|accounts| accounts[1]),
+            treasury_msol_account: marinade.map(|accounts| accounts[2]),
+            system_program: system_program::ID,
+            token_program: anchor_spl::token::ID,
+            marginfi_bank: market.collateral_reserve.reserve,
+            liquidity_vault: market.collateral_reserve.liquidity_supply,
+            marginfi_bank_wsol: market.sol_reserve.reserve,
+            pool_token_receiver_account_wsol: self.pool_token_account(&market.sol_reserve.liquidity_mint),
+            user_wsol_account: None,
+            liquidity_vault_wsol: market.sol_reserve.liquidity_supply,
+            pool_mint_wsol: market.sol_reserve.liquidity_mint,
+            stake_pool_withdraw_authority_wsol: market.sol_reserve.fee_receiver,
+            jarezi_mint: market.jarezi_mint,
+            jarezi_token_account: get_associated_token_address_with_program_id(
+                &user,
+                &market.jarezi_mint,
+                &anchor_spl::token_interface::Token2022::id(),
+            ),
+            token_program_2022: anchor_spl::token_interface::Token2022::id(),
+            // read by nothing, kept for the account order
+            to: market.obligation,
+            obligation_pubkey: market.obligation,
+            lending_market_pubkey: market.lending_market,
+            solend_sdk: SolendProgram::id(),
+            lending_market_authority_pubkey: lending_market_authority_address(&market.lending_market),
+            user_collateral_pubkey: self.pool_token_account(&market.collateral_reserve.collateral_mint),
+            reserve_collateral_mint_pubkey: market.collateral_reserve.collateral_mint,
+            destination_deposit_collateral_pubkey: market.collateral_reserve.collateral_supply,
+            pyth_oracle: market.collateral_reserve.pyth_oracle,
+            switchboard_oracle: market.collateral_reserve.switchboard_oracle,
+            pyth_oracle2: market.sol_reserve.pyth_oracle,
+            switchboard_oracle2: market.sol_reserve.switchboard_oracle,
+            clock: anchor_lang::solana_program::sysvar::clock::ID,
+            stake_history: anchor_lang::solana_program::sysvar::stake_history::ID,
+            stake_program: StakeProgram::id(),
+            rent: anchor_lang::solana_program::sysvar::rent::ID,
+            oracle: self.oracle,
+            hydra: market.hydra,
+            hydra_referrer: get_associated_token_address(&market.hydra, &market.pool_mint),
+            hydra_host_fee_account: get_associated_token_address(&market.hydra, &market.sol_reserve.liquidity_mint),
+            leverage_config: self.leverage_config,
+            associated_token_program: anchor_spl::associated_token::ID,
+            collateral_pool: self.collateral_pool,
+        }
+    }
+
+    pub fn deposit(&self, market: &VaultMarket, user: Pubkey, amount: u64) -> Instruction {
+        deposit(&self.program_id, self.deposit_accounts(market, user), amount)
+    }
+
+    /// Flash borrows and repays through the user's associated wSOL account, see [`withdraw`].
+    pub fn withdraw(&self, market: &VaultMarket, user: Pubkey, amount: u64) -> Instruction {
+        let user_wsol_account = get_associated_token_address(&user, &market.sol_reserve.liquidity_mint);
+        withdraw(&self.program_id, self.deposit_accounts(market, user), user_wsol_account, amount)
+    }
+}
+
+impl VaultAddresses {
+    // Authority configuration
+
+    pub fn set_function(&self, winner: Pubkey, switchboard_function: Pubkey, authority: Pubkey) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::SetFunction {
+                marginfi_pda: self.marginfi_pda,
+                marginfi_pda_switchboard: self.marginfi_pda_switchboard,
+                winner_winner_chickum_dinner: winner,
+                switchboard_function,
+                authority,
+                system_program: system_program::ID,
+            },
+            crate::instruction::SetFunction {},
+        )
+    }
+
+    pub fn set_paused(&self, signer: Pubkey, paused_flags: u8) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::SetPaused {
+                marginfi_pda: self.marginfi_pda,
+                signer,
+            },
+            crate::instruction::SetPaused { paused_flags },
+        )
+    }
+
+    pub fn update_circuit_breaker(&self, authority: Pubkey, params: UpdateCircuitBreakerParams) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::UpdateCircuitBreaker {
+                marginfi_pda: self.marginfi_pda,
+                authority,
+            },
+            crate::instruction::UpdateCircuitBreaker { params },
+        )
+    }
+
+    pub fn propose_vault_authority(&self, authority: Pubkey, new_authority: Pubkey) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::ProposeVaultAuthority {
+                marginfi_pda: self.marginfi_pda,
+                authority,
+            },
+            crate::instruction::ProposeVaultAuthority { new_authority },
+        )
+    }
+
+    pub fn accept_vault_authority(&self, pending_authority: Pubkey) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::AcceptVaultAuthority {
+                marginfi_pda: self.marginfi_pda,
+                pending_authority,
+            },
+            crate::instruction::AcceptVaultAuthority {},
+        )
+    }
+
+    pub fn update_leverage_config(&self, authority: Pubkey, params: UpdateLeverageConfigParams) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::UpdateLeverageConfig {
+                marginfi_pda: self.marginfi_pda,
+                leverage_config: self.leverage_config,
+                authority,
+                system_program: system_program::ID,
+            },
+            crate::instruction::UpdateLeverageConfig { params },
+        )
+    }
+
+    pub fn update_rate_limits(&self, authority: Pubkey, params: UpdateRateLimitsParams) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::UpdateRateLimits {
+                marginfi_pda: self.marginfi_pda,
+                authority,
+            },
+            crate::instruction::UpdateRateLimits { params },
+        )
+    }
+
+    pub fn update_collateral_pool(
+        &self,
+        authority: Pubkey,
+        jarezi_mint: Pubkey,
+        params: UpdateCollateralPoolParams,
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::UpdateCollateralPool {
+                marginfi_pda: self.marginfi_pda,
+                collateral_pool: self.collateral_pool,
+                jarezi_mint,
+                authority,
+                system_program: system_program::ID,
+            },
+            crate::instruction::UpdateCollateralPool { params },
+        )
+    }
+
+    pub fn update_prize_schedule(&self, authority: Pubkey, params: UpdatePrizeScheduleParams) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::UpdatePrizeSchedule {
+                marginfi_pda: self.marginfi_pda,
+                authority,
+            },
+            crate::instruction::UpdatePrizeSchedule { params },
+        )
+    }
+
+    pub fn set_kickback(
+        &self,
+        authority: Pubkey,
+        jarezi_mint: Pubkey,
+        kickback_destination: Pubkey,
+        params: SetKickbackParams,
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::SetKickback {
+                marginfi_pda: self.marginfi_pda,
+                jarezi_mint,
+                kickback_destination,
+                authority,
+            },
+            crate::instruction::SetKickback { params },
+        )
+    }
+
+    pub fn set_jarezi_mint_metadata(
+        &self,
+        authority: Pubkey,
+        jarezi_mint: Pubkey,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::SetMetadata {
+                marginfi_pda: self.marginfi_pda,
+                authority,
+                system_program: system_program::ID,
+                jarezi_mint,
+                token_program_2022: anchor_spl::token_interface::Token2022::id(),
+            },
+            crate::instruction::SetJareziMintMetadata { name, symbol, uri },
+        )
+    }
+
+    /// Grows the vault, and its collateral pool and auto buy config where they exist, to the
+    /// current layout, see [`MigrateVault`].
+    pub fn migrate_vault(&self, authority: Pubkey, collateral_pool: bool, auto_buy_config: bool) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::MigrateVault {
+                marginfi_pda: self.marginfi_pda,
+                collateral_pool: Some(self.collateral_pool).filter(|_| collateral_pool),
+                auto_buy_config: Some(self.auto_buy_config).filter(|_| auto_buy_config),
+                authority,
+                system_program: system_program::ID,
+            },
+            crate::instruction::MigrateVault {},
+        )
+    }
+
+    pub fn update_auto_buy_config(&self, authority: Pubkey, params: UpdateAutoBuyConfigParams) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::UpdateAutoBuyConfig {
+                marginfi_pda: self.marginfi_pda,
+                auto_buy_config: self.auto_buy_config,
+                authority,
+                system_program: system_program::ID,
+            },
+            crate::instruction::UpdateAutoBuyConfig { params },
+        )
+    }
+
+    // Draw lifecycle
+
+    pub fn request_randomness(&self, authority: Pubkey, seed: u32) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::RequestRandomness {
+                marginfi_pda: self.marginfi_pda,
+                marginfi_pda_switchboard: self.marginfi_pda_switchboard,
+                request: self.request,
+                authority,
+                system_program: system_program::ID,
+            },
+            crate::instruction::RequestRandomness { seed },
+        )
+    }
+
+    /// `signer` is the vault authority, or the enclave signer of `switchboard_function`, which
+    /// can only cancel once the seed slot has left SlotHashes. The request's rent goes back to
+    /// the vault `authority` either way.
+    pub fn cancel_randomness(
+        &self,
+        authority: Pubkey,
+        signer: Pubkey,
+        switchboard_function: Option<Pubkey>,
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::CancelRandomness {
+                marginfi_pda: self.marginfi_pda,
+                request: self.request,
+                authority,
+                signer,
+                switchboard_function,
+                slot_hashes: solana_program::sysvar::slot_hashes::ID,
+            },
+            crate::instruction::CancelRandomness {},
+        )
+    }
+
+    /// `holders` are jarezi accounts whose weight moved since they were last synced, including
+    /// closed ones. The enclave signer pays for the snapshot account to be created and grown.
+    pub fn sync_holder_snapshot(
+        &self,
+        jarezi_mint: Pubkey,
+        switchboard_function: Pubkey,
+        enclave_signer: Pubkey,
+        holders: &[Pubkey],
+    ) -> Instruction {
+        let mut ixn = build(
+            &self.program_id,
+            crate::accounts::SyncHolderSnapshot {
+                marginfi_pda: self.marginfi_pda,
+                request: self.request,
+                holder_snapshot: self.holder_snapshot,
+                jarezi_mint,
+                switchboard_function,
+                enclave_signer,
+                system_program: system_program::ID,
+            },
+            crate::instruction::SyncHolderSnapshot {},
+        );
+        ixn.accounts
+            .extend(holders.iter().map(|holder| AccountMeta::new_readonly(*holder, false)));
+        ixn
+    }
+
+    pub fn post_holder_snapshot(
+        &self,
+        jarezi_mint: Pubkey,
+        switchboard_function: Pubkey,
+        enclave_signer: Pubkey,
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::PostHolderSnapshot {
+                marginfi_pda: self.marginfi_pda,
+                request: self.request,
+                jarezi_mint,
+                prize_pool: prize_pool_address(&self.marginfi_pda, &jarezi_mint),
+                holder_snapshot: self.holder_snapshot,
+                switchboard_function,
+                enclave_signer,
+            },
+            crate::instruction::PostHolderSnapshot {},
+        )
+    }
+
+    pub fn seed_randomness(
+        &self,
+        switchboard_function: Pubkey,
+        enclave_signer: Pubkey,
+        pubkey_hash: [u8; 32],
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::SeedRandomness {
+                marginfi_pda: self.marginfi_pda,
+                request: self.request,
+                switchboard_function,
+                enclave_signer,
+            },
+            crate::instruction::SeedRandomness { pubkey_hash },
+        )
+    }
+
+    /// Has to land in a later slot than the seed, while the seed slot is still in SlotHashes.
+    pub fn reveal_randomness(&self, switchboard_function: Pubkey, enclave_signer: Pubkey, key: [u8; 32]) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::RevealRandomness {
+                marginfi_pda: self.marginfi_pda,
+                request: self.request,
+                switchboard_function,
+                enclave_signer,
+                slot_hashes: solana_program::sysvar::slot_hashes::ID,
+            },
+            crate::instruction::RevealRandomness { key },
+        )
+    }
+
+    /// `winner_token_account` is the token account of the snapshot entry at `params.index`,
+    /// `new_winner` its owner.
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_winner_winner_chickum_dinner(
+        &self,
+        winner: Pubkey,
+        new_winner: Pubkey,
+        winner_token_account: Pubkey,
+        jarezi_mint: Pubkey,
+        switchboard_function: Pubkey,
+        enclave_signer: Pubkey,
+        params: SetWinnerParams,
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::SetWinner {
+                marginfi_pda: self.marginfi_pda,
+                marginfi_pda_switchboard: self.marginfi_pda_switchboard,
+                winner_winner_chickum_dinner: winner,
+                new_winner_winner_chickum_dinner: new_winner,
+                switchboard_function,
+                enclave_signer,
+                request: self.request,
+                holder_snapshot: self.holder_snapshot,
+                jarezi_mint,
+                winner_token_account,
+            },
+            crate::instruction::SetWinnerWinnerChickumDinner { params },
+        )
+    }
+
+    /// Pays the prize to `winner`, which has to be the vault's winner by the time this runs,
+    /// e.g. straight after `set_winner_winner_chickum_dinner` in the same transaction.
+    /// `kickback_destination` is None while the vault has none set, `prize_pool` is
+    /// [`prize_pool_address`] or None before harvest_withheld_fees has created it.
+    /// `collateral_reserve` is the vault's [`CollateralPool`] reserve, the prize is priced off
+    /// the whole position like a deposit is.
+    #[allow(clippy::too_many_arguments)]
+    pub fn winner_winner_chickum_dinner_distribute(
+        &self,
+        winner: Pubkey,
+        actual_destination: Pubkey,
+        kickback_destination: Option<Pubkey>,
+        prize_pool: Option<Pubkey>,
+        jarezi_mint: Pubkey,
+        pool_mint: Pubkey,
+        obligation: Pubkey,
+        collateral_reserve: Pubkey,
+        switchboard_function: Pubkey,
+        enclave_signer: Pubkey,
+    ) -> Instruction {
+        build(
+            &self.program_id,
+            crate::accounts::Winner {
+                marginfi_pda: self.marginfi_pda,
+                marginfi_pda_switchboard: self.marginfi_pda_switchboard,
+                winner_winner_chickum_dinner: winner,
+                actual_destination,
+                kickback_destination,
+                prize_pool,
+                system_program: system_program::ID,
+                token_program_2022: anchor_spl::token_interface::Token2022::id(),
+                jarezi_mint,
+                pool_token_receiver_account: self.pool_token_account(&pool_mint),
+                pool_mint,
+                collateral_pool: self.collateral_pool,
+                switchboard_function,
+                enclave_signer,
+                oracle: self.oracle,
+                obligation_pubkey: obligation,
+                marginfi_bank: collateral_reserve,
+            },
+            crate::instruction::WinnerWinnerChickumDinnerDistribute {},
+        )
+    }
+
+    /// `svbonk_program` and `memo_program` have to match the vault's [`AutoBuyConfig`].
+    /// `game_accounts` are svbonk's `buy` accounts after the signer, see [`AutoBuy`].
+    pub fn auto_buy(
+        &self,
+        svbonk_program: Pubkey,
+        memo_program: Pubkey,
+        switchboard_function: Pubkey,
+        enclave_signer: Pubkey,
+        game_accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        let mut ixn = build(
+            &self.program_id,
+            crate::accounts::AutoBuy {
+                program: self.program_state,
+                marginfi_pda: self.marginfi_pda,
+                auto_buy_config: self.auto_buy_config,
+                switchboard_function,
+                enclave_signer,
+                svbonk_program,
+                memo_program,
+            },
+            crate::instruction::AutoBuy {},
+        );
+        ixn.accounts.extend(game_accounts);
+        ixn
+    }
+
+    /// Permissionless. `sources` are jarezi accounts holding withheld transfer fees.
+    pub fn harvest_withheld_fees(&self, payer: Pubkey, jarezi_mint: Pubkey, sources: &[Pubkey]) -> Instruction {
+        let mut ixn = build(
+            &self.program_id,
+            crate::accounts::HarvestWithheldFees {
+                marginfi_pda: self.marginfi_pda,
+                jarezi_mint,
+                prize_pool: prize_pool_address(&self.marginfi_pda, &jarezi_mint),
+                payer,
+                token_program_2022: anchor_spl::token_interface::Token2022::id(),
+                associated_token_program: anchor_spl::associated_token::ID,
+                system_program: system_program::ID,
+            },
+            crate::instruction::HarvestWithheldFees {},
+        );
+        ixn.accounts
+            .extend(sources.iter().map(|source| AccountMeta::new(*source, false)));
+        ixn
+    }
+}
+
+// Account decoders
+
+/// Decodes an `#[account]` after checking its discriminator.
+pub fn decode_account<T: AccountDeserialize>(data: &[u8]) -> anchor_lang::Result<T> {
+    T::try_deserialize(&mut &data[..])
+}
+
+/// Copies a zero-copy account out of its data after checking its discriminator. Works on
+/// unaligned buffers, which is what RPC clients hand back.
+pub fn decode_zero_copy<T: ZeroCopy + Discriminator>(data: &[u8]) -> anchor_lang::Result<T> {
+    if data.len() < 8 || data[..8] != T::discriminator() {
+        return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+    let end = 8 + std::mem::size_of::<T>();
+    if data.len() < end {
+        return Err(ErrorCode::AccountDidNotDeserialize.into());
+    }
+    Ok(bytemuck::pod_read_unaligned(&data[8..end]))
+}
+
+pub fn decode_marginfi_pda(data: &[u8]) -> anchor_lang::Result<MarginFiPda> {
+    decode_account(data)
+}
+
+pub fn decode_auto_buy_config(data: &[u8]) -> anchor_lang::Result<AutoBuyConfig> {
+    decode_account(data)
+}
+
+pub fn decode_leverage_config(data: &[u8]) -> anchor_lang::Result<LeverageConfig> {
+    decode_account(data)
+}
+
+pub fn decode_collateral_pool(data: &[u8]) -> anchor_lang::Result<CollateralPool> {
+    decode_account(data)
+}
+
+pub fn decode_program_state(data: &[u8]) -> anchor_lang::Result<MyProgramState> {
+    decode_zero_copy(data)
+}
+
+pub fn decode_oracle(data: &[u8]) -> anchor_lang::Result<MyOracleState> {
+    decode_zero_copy(data)
+}
+
+pub fn decode_request(data: &[u8]) -> anchor_lang::Result<RequestAccountData> {
+    decode_zero_copy(data)
+}
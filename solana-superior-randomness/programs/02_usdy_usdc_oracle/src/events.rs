@@ -0,0 +1,117 @@
+use crate::*;
+
+// Everything an indexer needs to rebuild the vault's accounting without scraping msg! logs.
+// Amounts are base units, prices and exchange rates lamports scaled by PRICE_SCALE.
+
+#[event]
+pub struct Deposited {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub lamports: u64,
+    pub jarezi_minted: u64,
+    /// Lamports per jarezi after the deposit
+    pub exchange_rate: u64,
+    /// Collateral pool token price the deposit was valued at
+    pub pool_price: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub jarezi_burned: u64,
+    /// SOL unstaked to the user, before the flash loan is wrapped back
+    pub lamports: u64,
+    /// wSOL debt repaid with the flash loan
+    pub debt_repaid: u64,
+    /// Lamports per jarezi before the withdrawal
+    pub exchange_rate: u64,
+    pub pool_price: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct WinnerSet {
+    pub vault: Pubkey,
+    pub winner: Pubkey,
+    pub token_account: Pubkey,
+    pub draw: u64,
+    pub snapshot_total_weight: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PrizeDistributed {
+    pub vault: Pubkey,
+    pub winner_token_account: Pubkey,
+    pub periods: i64,
+    /// Pool tokens the elapsed periods paid out, before interest and kickback
+    pub pool_tokens: u64,
+    /// Jarezi minted to the winner
+    pub prize: u64,
+    /// Jarezi minted to the kickback destination
+    pub kickback: u64,
+    /// Harvested transfer fees paid out of the prize pool
+    pub fees: u64,
+    /// Lamports per jarezi after the payout
+    pub exchange_rate: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OracleRefreshed {
+    pub symbol: TradingSymbol,
+    pub mean: u64,
+    pub median: u64,
+    pub std: u64,
+    pub oracle_timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct UnknownOracleSymbol {
+    pub symbol: TradingSymbol,
+    pub oracle_timestamp: i64,
+    pub slot: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum ConfigKind {
+    SwitchboardFunction,
+    OracleFeed,
+    ProgramAuthority,
+    Paused,
+    CircuitBreaker,
+    VaultSwitchboardFunction,
+    VaultAuthority,
+    LeverageConfig,
+    CollateralPool,
+    PrizeSchedule,
+    Kickback,
+    AutoBuyConfig,
+    RateLimits,
+    JareziMetadata,
+    Migration,
+}
+
+/// The new values are in the instruction data and the account itself, the event marks when
+/// and by whom they changed.
+#[event]
+pub struct ConfigChanged {
+    /// Vault the change applies to, default for program-wide settings
+    pub vault: Pubkey,
+    pub signer: Pubkey,
+    pub config: ConfigKind,
+    pub slot: u64,
+}
+
+pub fn emit_config_changed(vault: Pubkey, signer: Pubkey, config: ConfigKind) -> anchor_lang::Result<()> {
+    emit!(ConfigChanged {
+        vault,
+        signer,
+        config,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
@@ -0,0 +1,103 @@
+pub use crate::*;
+
+// Authority handoff is two-step: the current authority proposes a key and that key has to sign
+// accept_*, so a typo can't lock the program or a vault out. Proposing the default pubkey
+// withdraws a pending proposal.
+
+#[derive(Accounts)]
+pub struct ProposeProgramAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_SEED],
+        bump = program.load()?.bump,
+        has_one = authority @ USDY_USDC_ORACLEError::InvalidAuthority
+    )]
+    pub program: AccountLoader<'info, MyProgramState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptProgramAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_SEED],
+        bump = program.load()?.bump,
+        constraint = program.load()?.pending_authority == pending_authority.key() @ USDY_USDC_ORACLEError::InvalidPendingAuthority
+    )]
+    pub program: AccountLoader<'info, MyProgramState>,
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeVaultAuthority<'info> {
+    #[account(mut,
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptVaultAuthority<'info> {
+    #[account(mut,
+        constraint = marginfi_pda.pending_authority == pending_authority.key() @ USDY_USDC_ORACLEError::InvalidPendingAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    pub pending_authority: Signer<'info>,
+}
+
+impl ProposeProgramAuthority<'_> {
+    pub fn propose_program_authority(
+        ctx: Context<ProposeProgramAuthority>,
+        new_authority: Pubkey,
+    ) -> anchor_lang::Result<()> {
+        let program = &mut ctx.accounts.program.load_mut()?;
+        program.pending_authority = new_authority;
+        msg!("pending_authority: {}", new_authority);
+        emit_config_changed(Pubkey::default(), ctx.accounts.authority.key(), ConfigKind::ProgramAuthority)?;
+
+        Ok(())
+    }
+}
+
+impl AcceptProgramAuthority<'_> {
+    pub fn accept_program_authority(ctx: Context<AcceptProgramAuthority>) -> anchor_lang::Result<()> {
+        let program = &mut ctx.accounts.program.load_mut()?;
+        program.authority = ctx.accounts.pending_authority.key();
+        program.pending_authority = Pubkey::default();
+        msg!("authority: {}", program.authority);
+        emit_config_changed(Pubkey::default(), program.authority, ConfigKind::ProgramAuthority)?;
+
+        Ok(())
+    }
+}
+
+impl ProposeVaultAuthority<'_> {
+    pub fn propose_vault_authority(
+        ctx: Context<ProposeVaultAuthority>,
+        new_authority: Pubkey,
+    ) -> anchor_lang::Result<()> {
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        marginfi_pda.pending_authority = new_authority;
+        msg!("pending_authority: {}", new_authority);
+        emit_config_changed(ctx.accounts.marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::VaultAuthority)?;
+
+        Ok(())
+    }
+}
+
+impl AcceptVaultAuthority<'_> {
+    pub fn accept_vault_authority(ctx: Context<AcceptVaultAuthority>) -> anchor_lang::Result<()> {
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        marginfi_pda.authority = ctx.accounts.pending_authority.key();
+        marginfi_pda.pending_authority = Pubkey::default();
+        msg!("authority: {}", marginfi_pda.authority);
+        emit_config_changed(marginfi_pda.key(), marginfi_pda.authority, ConfigKind::VaultAuthority)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+pub use crate::*;
+use anchor_lang::solana_program::{hash::hashv, program::invoke_signed};
+
+pub const AUTO_BUY_SEED: &[u8] = b"auto_buy";
+
+#[account]
+#[derive(Default)]
+pub struct AutoBuyConfig {
+    pub bump: u8,
+    pub marginfi_pda: Pubkey,
+    pub svbonk_program: Pubkey,
+    pub memo_program: Pubkey,
+    /// Arguments of the svbonk `buy` instruction
+    pub game_index: i32,
+    pub quantity: i32,
+    pub version: i32,
+    /// game_accounts_hash of the accounts `buy` is allowed to run with, see AutoBuy
+    pub game_accounts_hash: [u8; 32],
+}
+
+/// Commits to the ordered keys passed to svbonk's `buy` after the signer.
+pub fn game_accounts_hash(keys: &[Pubkey]) -> [u8; 32] {
+    let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+    hashv(&keys).to_bytes()
+}
+
+#[derive(Accounts)]
+pub struct UpdateAutoBuyConfig<'info> {
+    #[account(
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<AutoBuyConfig>(),
+        payer = authority,
+        seeds = [AUTO_BUY_SEED, marginfi_pda.key().as_ref()],
+        bump
+    )]
+    pub auto_buy_config: Box<Account<'info, AutoBuyConfig>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateAutoBuyConfigParams {
+    pub svbonk_program: Pubkey,
+    pub memo_program: Pubkey,
+    pub game_index: i32,
+    pub quantity: i32,
+    pub version: i32,
+    /// Every account `buy` takes after the signer, in order. They are all fixed for a vault
+    /// and game: the game user and buyer token account are the vault's own.
+    pub game_accounts: Vec<Pubkey>,
+}
+
+/// Buys svbonk game tickets with the vault PDA as the buyer. The game accounts are passed in
+/// `ctx.remaining_accounts` in the order svbonk's `buy` expects after the signer: contract,
+/// game user, contract token account, buyer token account, instructions sysvar, raffle, token
+/// program, system program, then the game's own remaining accounts. The vault signs the CPI,
+/// so the accounts have to be exactly the ones the authority configured.
+#[derive(Accounts)]
+pub struct AutoBuy<'info> {
+    // We need this to validate that the Switchboard Function passed to our program
+    // is the expected one.
+    #[account(
+        seeds = [PROGRAM_SEED],
+        bump = program.load()?.bump,
+        has_one = switchboard_function
+    )]
+    pub program: AccountLoader<'info, MyProgramState>,
+    #[account(mut,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(
+        seeds = [AUTO_BUY_SEED, marginfi_pda.key().as_ref()],
+        bump = auto_buy_config.bump
+    )]
+    pub auto_buy_config: Box<Account<'info, AutoBuyConfig>>,
+    // We use this to verify the functions enclave state was verified successfully
+    #[account(
+        constraint =
+                switchboard_function.load()?.validate(
+                &enclave_signer.to_account_info()
+            )? @ USDY_USDC_ORACLEError::FunctionValidationFailed
+    )]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    pub enclave_signer: Signer<'info>,
+    /// CHECK: address is checked against the config
+    #[account(address = auto_buy_config.svbonk_program)]
+    pub svbonk_program: AccountInfo<'info>,
+    /// CHECK: address is checked against the config
+    #[account(address = auto_buy_config.memo_program)]
+    pub memo_program: AccountInfo<'info>,
+}
+
+impl UpdateAutoBuyConfig<'_> {
+    pub fn update_auto_buy_config(
+        ctx: Context<UpdateAutoBuyConfig>,
+        params: UpdateAutoBuyConfigParams,
+    ) -> anchor_lang::Result<()> {
+        let auto_buy_config = &mut ctx.accounts.auto_buy_config;
+        auto_buy_config.bump = ctx.bumps.auto_buy_config;
+        auto_buy_config.marginfi_pda = ctx.accounts.marginfi_pda.key();
+        auto_buy_config.svbonk_program = params.svbonk_program;
+        auto_buy_config.memo_program = params.memo_program;
+        auto_buy_config.game_index = params.game_index;
+        auto_buy_config.quantity = params.quantity;
+        auto_buy_config.version = params.version;
+        auto_buy_config.game_accounts_hash = game_accounts_hash(&params.game_accounts);
+
+        msg!("game_index: {}", params.game_index);
+        msg!("quantity: {}", params.quantity);
+        emit_config_changed(ctx.accounts.marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::AutoBuyConfig)?;
+
+        Ok(())
+    }
+}
+
+impl<'info> AutoBuy<'info> {
+    pub fn auto_buy(ctx: Context<'_, '_, '_, 'info, AutoBuy<'info>>) -> anchor_lang::Result<()> {
+        let config = &ctx.accounts.auto_buy_config;
+        let buyer = ctx.accounts.marginfi_pda.to_account_info();
+
+        let keys: Vec<Pubkey> = ctx.remaining_accounts.iter().map(|account| account.key()).collect();
+        if game_accounts_hash(&keys) != config.game_accounts_hash {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidGameAccounts));
+        }
+
+        let mut data = get_ixn_discriminator("buy").to_vec();
+        data.extend_from_slice(&config.game_index.to_le_bytes());
+        data.extend_from_slice(&config.quantity.to_le_bytes());
+        data.extend_from_slice(&config.version.to_le_bytes());
+
+        let mut accounts = vec![AccountMeta::new(buyer.key(), true)];
+        accounts.extend(ctx.remaining_accounts.iter().map(|account| AccountMeta {
+            pubkey: account.key(),
+            is_signer: false,
+            is_writable: account.is_writable,
+        }));
+        let ix = Instruction {
+            program_id: config.svbonk_program,
+            accounts,
+            data,
+        };
+
+        let memo_data = format!("{}-0-{}-{}", buyer.key(), config.quantity, config.version);
+        let memo_ix = Instruction {
+            program_id: config.memo_program,
+            accounts: vec![AccountMeta::new(buyer.key(), true)],
+            data: memo_data.as_bytes().to_vec(),
+        };
+
+        let winner = ctx.accounts.marginfi_pda.thewinnerog;
+        let seeds: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(), &[ctx.accounts.marginfi_pda.bump]]];
+
+        let mut account_infos = vec![buyer.clone()];
+        account_infos.extend(ctx.remaining_accounts.iter().cloned());
+        account_infos.push(ctx.accounts.svbonk_program.to_account_info());
+        invoke_signed(&ix, &account_infos, seeds)?;
+
+        invoke_signed(
+            &memo_ix,
+            &[buyer, ctx.accounts.memo_program.to_account_info()],
+            seeds,
+        )?;
+
+        Ok(())
+    }
+}
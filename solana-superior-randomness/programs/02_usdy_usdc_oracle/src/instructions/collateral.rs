@@ -0,0 +1,115 @@
+pub use crate::*;
+
+pub const COLLATERAL_POOL_SEED: &[u8] = b"collateral_pool";
+
+/// How the vault stakes into and unstakes out of its pool.
+#[repr(u8)]
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub enum PoolKind {
+    /// SPL stake pool (or a fork with the same interface), e.g. jitoSOL or bSOL
+    #[default]
+    SplStakePool,
+    /// Marinade's mSOL, see the marinade module for how its accounts are passed
+    Marinade,
+}
+
+/// The liquid staking token backing one vault's jarezi. Each vault (and its jarezi mint) is
+/// bound to exactly one pool, so jitoSOL, bSOL and mSOL run as separate vaults.
+#[account]
+#[derive(Default)]
+pub struct CollateralPool {
+    pub bump: u8,
+    pub marginfi_pda: Pubkey,
+    pub stake_pool_program: Pubkey,
+    pub stake_pool: Pubkey,
+    pub pool_mint: Pubkey,
+    /// Solend reserve taking the pool tokens as collateral
+    pub reserve: Pubkey,
+    /// Oracle feed pricing the pool token in SOL, either its market price (e.g. Bsol_sol) or
+    /// its stake pool redemption value (e.g. Bsol_redemption)
+    pub oracle_symbol: TradingSymbol,
+    pub kind: PoolKind,
+}
+
+impl CollateralPool {
+    /// Pool token price the vault values deposits, withdrawals and NAV at.
+    pub fn price(&self, oracle: &MyOracleState, now: i64) -> anchor_lang::Result<u64> {
+        oracle.feed_twap(self.oracle_symbol, now)
+    }
+}
+
+#[derive(Accounts)]
+pub struct UpdateCollateralPool<'info> {
+    #[account(
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<CollateralPool>(),
+        payer = authority,
+        seeds = [COLLATERAL_POOL_SEED, marginfi_pda.key().as_ref()],
+        bump
+    )]
+    pub collateral_pool: Box<Account<'info, CollateralPool>>,
+    #[account(
+        mint::authority = marginfi_pda,
+    )]
+    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateCollateralPoolParams {
+    pub stake_pool_program: Pubkey,
+    pub stake_pool: Pubkey,
+    pub pool_mint: Pubkey,
+    pub reserve: Pubkey,
+    pub oracle_symbol: TradingSymbol,
+    pub kind: PoolKind,
+}
+
+impl UpdateCollateralPool<'_> {
+    pub fn update_collateral_pool(
+        ctx: Context<UpdateCollateralPool>,
+        params: UpdateCollateralPoolParams,
+    ) -> anchor_lang::Result<()> {
+        if matches!(params.oracle_symbol, TradingSymbol::Unknown | TradingSymbol::Wsol_borrow) {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidCollateralPool));
+        }
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        // outstanding jarezi is backed by, and priced off, the current binding; none of it can
+        // change until the vault is empty
+        let bound = collateral_pool.marginfi_pda != Pubkey::default();
+        let changed = collateral_pool.stake_pool_program != params.stake_pool_program
+            || collateral_pool.stake_pool != params.stake_pool
+            || collateral_pool.pool_mint != params.pool_mint
+            || collateral_pool.reserve != params.reserve
+            || collateral_pool.oracle_symbol != params.oracle_symbol
+            || collateral_pool.kind != params.kind;
+        if bound && changed && ctx.accounts.jarezi_mint.supply > 0 {
+            return Err(error!(USDY_USDC_ORACLEError::CollateralPoolInUse));
+        }
+        collateral_pool.bump = ctx.bumps.collateral_pool;
+        collateral_pool.marginfi_pda = ctx.accounts.marginfi_pda.key();
+        collateral_pool.stake_pool_program = params.stake_pool_program;
+        collateral_pool.stake_pool = params.stake_pool;
+        collateral_pool.pool_mint = params.pool_mint;
+        collateral_pool.reserve = params.reserve;
+        collateral_pool.oracle_symbol = params.oracle_symbol;
+        collateral_pool.kind = params.kind;
+
+        msg!("stake_pool: {}", params.stake_pool);
+        msg!("pool_mint: {}", params.pool_mint);
+        msg!("reserve: {}", params.reserve);
+        msg!("oracle_symbol: {:?}", params.oracle_symbol);
+        msg!("kind: {:?}", params.kind);
+        emit_config_changed(ctx.accounts.marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::CollateralPool)?;
+
+        Ok(())
+    }
+}
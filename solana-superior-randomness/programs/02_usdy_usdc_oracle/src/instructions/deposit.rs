@@ -1,9 +1,11 @@
 pub use crate::SbError;
 pub use crate::*;
 use anchor_lang::solana_program::system_instruction;
-use anchor_spl::{token_interface::{MintTo, Token2022}, token::SyncNative};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{MintTo, Token2022}, token::SyncNative};
 use solana_program::program_pack::Pack;
-use solend_sdk::{math::{Decimal, Rate, TryMul, TryDiv}, state::Obligation};
+use solend_sdk::{math::{Decimal, TryMul, TryDiv, TrySub}, state::{Obligation, Reserve}};
+use spl_token_2022::extension::{metadata_pointer, transfer_fee, BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_metadata_interface::state::{Field, TokenMetadata};
 use std::str::FromStr;
 
 #[derive(Clone)]
@@ -32,7 +34,42 @@ impl anchor_lang::Id for SolendProgram {
     }
 }
 
-const SEED_PREFIX: &[u8] = b"jarezi";
+pub const SEED_PREFIX: &[u8] = b"jarezi";
+
+/// Jarezi transfer fee, 1.38%. Withheld fees are swept into the prize pool.
+pub const JAREZI_TRANSFER_FEE_BPS: u16 = 138;
+
+/// wSOL lamports the vault deposits as Solend collateral when its obligation is opened.
+pub const INIT_COLLATERAL_LAMPORTS: u64 = 666;
+
+/// wSOL lamports borrowed against [`INIT_COLLATERAL_LAMPORTS`] to open the borrow side.
+pub const INIT_BORROW_LAMPORTS: u64 = 100;
+
+/// Tags a CPI result with the vault leg it belongs to, see [`VaultError`].
+pub(crate) trait VaultStage<T> {
+    fn stage(self, stage: VaultError, amount: u64) -> anchor_lang::Result<T>;
+}
+
+impl<T, E: std::fmt::Debug> VaultStage<T> for std::result::Result<T, E> {
+    fn stage(self, stage: VaultError, amount: u64) -> anchor_lang::Result<T> {
+        self.map_err(|err| {
+            msg!("{:?} failed, amount {}: {:?}", stage, amount, err);
+            error!(stage)
+        })
+    }
+}
+
+/// Runs one vault CPI leg. A callee that fails aborts the whole transaction before the result
+/// gets back here, so the stage and amount are logged first: the last one in the logs is the
+/// leg that failed.
+pub(crate) fn staged<T, E: std::fmt::Debug>(
+    stage: VaultError,
+    amount: u64,
+    cpi: impl FnOnce() -> std::result::Result<T, E>,
+) -> anchor_lang::Result<T> {
+    msg!("{:?}, amount {}", stage, amount);
+    cpi().stage(stage, amount)
+}
 
 use anchor_lang::AnchorSerialize;
 #[derive(Accounts)]
@@ -122,6 +159,39 @@ pub struct MarginFiPda {
     pub winner_winner_chickum_dinner: Pubkey,
     pub seeded_seed: String,
     pub thewinnerog: Pubkey,
+    /// Draw derived from the last consumed randomness request, in jarezi base units
+    pub last_draw: u64,
+    /// Hash of the holder snapshot entries committed before the draw
+    pub snapshot_root: [u8; 32],
+    pub snapshot_total_weight: u64,
+    /// Token account proven by the last draw, the only valid prize destination
+    pub winner_token_account: Pubkey,
+    /// End of the last prize period paid out
+    pub last_distribution_ts: i64,
+    /// Cumulative wSOL borrow growth since init, Decimal scaled value (1.0 = 1e18)
+    pub borrow_rate_index: u128,
+    pub prize_period_secs: i64,
+    /// Pool tokens paid out per elapsed period, before interest and kickback
+    pub prize_per_period: u64,
+    /// Jarezi token account (hydra fanout, treasury) minted the kickback share of each prize
+    pub kickback_destination: Pubkey,
+    /// Jarezi minted to kickback_destination so far
+    pub total_kickback: u64,
+    /// Proposed by the authority, takes over once it signs accept_vault_authority
+    pub pending_authority: Pubkey,
+    /// SOL deposited per window, see update_rate_limits
+    pub inflow_limiter: PackedRateLimiter,
+    /// SOL withdrawn per window, valued at NAV
+    pub outflow_limiter: PackedRateLimiter,
+    /// Second key allowed to pause the vault, see set_paused
+    pub guardian: Pubkey,
+    /// Move of the collateral pool's oracle feed between pushes that trips the circuit
+    /// breaker, parts per million
+    pub trip_bpm: u64,
+    /// PAUSE_* bits, PAUSE_VAULT only
+    pub paused_flags: u8,
+    /// Collateral feed mean the circuit breaker last ran against, 0 until it runs again
+    pub breaker_mean: u64,
 }
 
 #[account]
@@ -133,70 +203,84 @@ impl InitMrgnFiPda<'_> {
     pub fn set_jarezi_mint_metadata(
         ctx: Context<SetMetadata>, name: String, symbol: String, uri: String) -> anchor_lang::Result<()>
     {
-        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
-        let jarezi_mint = &mut ctx.accounts.jarezi_mint;
-        let metadata = &mut ctx.accounts.metadata;
-        let metadata_account = metadata.to_account_info();
-        marginfi_pda.thewinnerog = marginfi_pda.winner_winner_chickum_dinner;
+        let mint = ctx.accounts.jarezi_mint.to_account_info();
+        let marginfi_pda = &ctx.accounts.marginfi_pda;
         let winner = marginfi_pda.thewinnerog;
         let seeds: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(), &[marginfi_pda.bump]]];
-/*
-        let metadata_pointer_ix = spl_token_2022::extension::metadata_pointer::instruction::update(
-            &ctx.accounts.token_program_2022.key(),
-            &jarezi_mint.key(),
-            marginfi_pda.key()),
-            &vec![],
-            Some(metadata_account.key()),
-        )?;
-        invoke_signed(
-            &metadata_pointer_ix,
-            &[
-                metadata_account.clone(),
-                jarezi_mint.to_account_info(),
-                marginfi_pda.to_account_info(),
-                ctx.accounts.token_program_2022.to_account_info(),
-            ],
-            seeds,
-        )?; */
+
+        // the token program reallocs the mint in place, top up its rent for the new length first
+        let (len, current) = {
+            let data = mint.try_borrow_data()?;
+            let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+            let current = state.get_variable_len_extension::<TokenMetadata>()?;
+            (data.len(), current)
+        };
+        let updated = TokenMetadata {
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+            ..current.clone()
+        };
+        let len = (len + updated.tlv_size_of()?).saturating_sub(current.tlv_size_of()?);
+        let top_up = Rent::get()?.minimum_balance(len).saturating_sub(mint.lamports());
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: mint.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+
+        for (field, value) in [(Field::Name, name), (Field::Symbol, symbol), (Field::Uri, uri)] {
+            msg!("{:?}: {}", field, value);
+            invoke_signed(
+                &spl_token_metadata_interface::instruction::update_field(
+                    &ctx.accounts.token_program_2022.key(),
+                    &mint.key(),
+                    &marginfi_pda.key(),
+                    field,
+                    value,
+                ),
+                &[mint.clone(), marginfi_pda.to_account_info()],
+                seeds,
+            )?;
+        }
+        emit_config_changed(marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::JareziMetadata)?;
         Ok(())
     }
 
-    pub fn init_mrgn_fi_pda(ctx: Context<InitMrgnFiPda>, bump: u8, kickback: u64, seeded_seed: String, seed2: String) -> anchor_lang::Result<()> {
+    pub fn init_mrgn_fi_pda(ctx: Context<InitMrgnFiPda>, bump: u8, kickback: u64, seeded_seed: String, seed2: String, name: String, symbol: String, uri: String) -> anchor_lang::Result<()> {
         let marginfi_pda = &mut ctx.accounts.marginfi_pda;
         marginfi_pda.authority = ctx.accounts.authority.key();
-        assert!(kickback <= 1_000_000);
+        if kickback > BPM {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidKickback));
+        }
         marginfi_pda.kickback_percent_bpm = kickback;
+        marginfi_pda.borrow_rate_index = Decimal::one().to_scaled_val()?;
         marginfi_pda.winner_winner_chickum_dinner = ctx.accounts.winner_winner_chickum_dinner.key();
         marginfi_pda.bump = bump;
         marginfi_pda.seeded_seed = seeded_seed;
-        let mint = ctx.accounts.jarezi_mint.clone();
         marginfi_pda.thewinnerog = marginfi_pda.winner_winner_chickum_dinner;
-        let token_program_2022 = ctx.accounts.token_program_2022.clone();
         let winner = marginfi_pda.thewinnerog;
         let seeds: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(), &[marginfi_pda.bump]]];
-        {
-        // set fees to 1.38%
-        spl_token_2022::extension::transfer_fee::instruction::set_transfer_fee(
-            &token_program_2022.key(),
-            &mint.key(),
-            &marginfi_pda.key(),
-            &[],
-            138,
-            u64::MAX,
-        )
-        .unwrap();
-}
+        ctx.accounts.create_jarezi_mint(seeds, name, symbol, uri)?;
 {
+    let lamports = Rent::default().minimum_balance(Obligation::LEN);
     let create_seeded_ix = system_instruction::create_account_with_seed(
         &ctx.accounts.authority.key(),
         &ctx.accounts.to.key(),
         &ctx.accounts.marginfi_pda.key(),
         &seed2,
-        Rent::default().minimum_balance(Obligation::LEN),
-        Obligation::LEN.try_into().unwrap(),
+        lamports,
+        Obligation::LEN as u64,
         &ctx.accounts.solend_sdk.key(),
     );
-    invoke_signed(
+    staged(VaultError::CreateObligation, lamports, || invoke_signed(
         &create_seeded_ix,
         &[
             ctx.accounts.to.to_account_info(),
@@ -207,7 +291,7 @@ impl InitMrgnFiPda<'_> {
             ctx.accounts.rent.to_account_info(),
         ],
         seeds,
-    )?;
+    ))?;
 }
 {
     let instruction: Instruction = solend_sdk::instruction::init_obligation(
@@ -216,7 +300,7 @@ impl InitMrgnFiPda<'_> {
         ctx.accounts.lending_market.key(),
         ctx.accounts.marginfi_pda.key(),
     );
-    invoke_signed(
+    staged(VaultError::InitObligation, 0, || invoke_signed(
         &instruction,
         &[
             ctx.accounts.to.to_account_info(),
@@ -228,14 +312,14 @@ impl InitMrgnFiPda<'_> {
             ctx.accounts.token_program.to_account_info(),
         ],
         seeds,
-    )?;
+    ))?;
 
 }
 {
-    invoke_signed(
+    staged(VaultError::SolendDeposit, INIT_COLLATERAL_LAMPORTS, || invoke_signed(
         &solend_sdk::instruction::deposit_reserve_liquidity_and_obligation_collateral(
             ctx.accounts.solend_sdk.key(),
-            666 as u64,
+            INIT_COLLATERAL_LAMPORTS,
             ctx.accounts.pool_token_receiver_account_wsol.key(),
             ctx.accounts.destination_deposit_collateral_pubkey.key(),
             ctx.accounts.marginfi_bank_wsol.key(),
@@ -273,12 +357,11 @@ impl InitMrgnFiPda<'_> {
             ctx.accounts.switchboard_oracle.to_account_info(),
         ],
         &seeds,
-    )
-    .unwrap();
+    ))?;
 
 }
 {
-    invoke_signed(
+    staged(VaultError::Refresh, 0, || invoke_signed(
         &solend_sdk::instruction::refresh_reserve(
             ctx.accounts.solend_sdk.key(),
             ctx.accounts.marginfi_bank_wsol.key(),
@@ -293,11 +376,10 @@ impl InitMrgnFiPda<'_> {
             ctx.accounts.switchboard_oracle.to_account_info(),
         ],
         seeds,
-    )
-    .unwrap();
+    ))?;
 }
 {
-    invoke_signed(
+    staged(VaultError::Refresh, 0, || invoke_signed(
         &solend_sdk::instruction::refresh_reserve(
             ctx.accounts.solend_sdk.key(),
             ctx.accounts.marginfi_bank_wsol2.key(),
@@ -312,11 +394,10 @@ impl InitMrgnFiPda<'_> {
             ctx.accounts.switchboard_oracle2.to_account_info(),
         ],
         seeds,
-    )
-    .unwrap();
+    ))?;
 }
 {
-    invoke_signed(
+    staged(VaultError::Refresh, 0, || invoke_signed(
         &solend_sdk::instruction::refresh_obligation(
             ctx.accounts.solend_sdk.key(),
             ctx.accounts.to.key(),
@@ -331,15 +412,14 @@ impl InitMrgnFiPda<'_> {
             ctx.accounts.marginfi_bank_wsol.to_account_info(),
         ],
         seeds,
-    )
-    .unwrap();
+    ))?;
 
     }
 {
-    invoke_signed(
+    staged(VaultError::Borrow, INIT_BORROW_LAMPORTS, || invoke_signed(
         &solend_sdk::instruction::borrow_obligation_liquidity(
             ctx.accounts.solend_sdk.key(),
-            100 as u64,
+            INIT_BORROW_LAMPORTS,
             ctx.accounts.liquidity_vault_wsol2.key(),
             ctx.accounts.pool_token_receiver_account_wsol2.key(),
             ctx.accounts.marginfi_bank_wsol2.key(),
@@ -373,43 +453,112 @@ impl InitMrgnFiPda<'_> {
             ctx.accounts.liquidity_vault_wsol2.to_account_info(),
         ],
         &seeds,
-    )
-    .unwrap();
+    ))?;
+    }
+        Ok(())
     }
+}
+impl<'info> InitMrgnFiPda<'info> {
+    /// Creates the jarezi mint with the transfer fee and a metadata pointer to itself, then
+    /// writes its TokenMetadata. The jarezi PDA holds every authority.
+    fn create_jarezi_mint(&self, signer: &[&[&[u8]]], name: String, symbol: String, uri: String) -> anchor_lang::Result<()> {
+        let mint = self.jarezi_mint.to_account_info();
+        let marginfi_pda = self.marginfi_pda.key();
+        let token_program_2022 = self.token_program_2022.key();
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+            ExtensionType::MetadataPointer,
+        ])?;
+        // TokenMetadata is variable length and reallocs the mint when written, its rent is
+        // paid up front
+        let metadata = TokenMetadata {
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+            ..Default::default()
+        };
+        let lamports = Rent::get()?.minimum_balance(space + metadata.tlv_size_of()?);
+        anchor_lang::system_program::create_account(
+            CpiContext::new(
+                self.system_program.to_account_info(),
+                anchor_lang::system_program::CreateAccount {
+                    from: self.authority.to_account_info(),
+                    to: mint.clone(),
+                },
+            ),
+            lamports,
+            space as u64,
+            &token_program_2022,
+        )?;
+
+        invoke(
+            &transfer_fee::instruction::initialize_transfer_fee_config(
+                &token_program_2022,
+                &mint.key(),
+                Some(&marginfi_pda),
+                Some(&marginfi_pda),
+                JAREZI_TRANSFER_FEE_BPS,
+                u64::MAX,
+            )?,
+            &[mint.clone()],
+        )?;
+        invoke(
+            &metadata_pointer::instruction::initialize(
+                &token_program_2022,
+                &mint.key(),
+                Some(marginfi_pda),
+                Some(mint.key()),
+            )?,
+            &[mint.clone()],
+        )?;
+        invoke(
+            &spl_token_2022::instruction::initialize_mint2(
+                &token_program_2022,
+                &mint.key(),
+                &marginfi_pda,
+                None,
+                9,
+            )?,
+            &[mint.clone()],
+        )?;
+        invoke_signed(
+            &spl_token_metadata_interface::instruction::initialize(
+                &token_program_2022,
+                &mint.key(),
+                &marginfi_pda,
+                &mint.key(),
+                &marginfi_pda,
+                name,
+                symbol,
+                uri,
+            ),
+            &[mint.clone(), self.marginfi_pda.to_account_info()],
+            signer,
+        )?;
+        msg!("jarezi_mint: {}", mint.key());
+        msg!("transfer_fee_bps: {}", JAREZI_TRANSFER_FEE_BPS);
+
         Ok(())
     }
 }
 #[derive(Accounts)]
 pub struct SetMetadata<'info> {
-                #[account(mut,
-        constraint = marginfi_pda.authority == authority.key(),
-        seeds = [SEED_PREFIX, winner_winner_chickum_dinner.key().as_ref()],
+    #[account(
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
         bump
     )]
     pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    // pays the rent of a longer name, symbol or uri
     #[account(mut)]
     pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
-    /// CHECK:
-    pub winner_winner_chickum_dinner: AccountInfo<'info>,
-    #[account(mut)]
-    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
-    #[account(mut)]
-    /// CHECK:
-    pub metadata: AccountInfo<'info>,
-    pub token_program_2022: Program<'info, Token2022>,
-    /// CHECK:
-    pub mpl_token_metadata_program: AccountInfo<'info>,
-    /// CHECK:
-    pub sysvar_instructions: AccountInfo<'info>,
-    #[account(init, 
-        payer = authority,
+    #[account(mut,
         mint::authority = marginfi_pda,
-        mint::decimals = 9,
+        mint::token_program = token_program_2022,
     )]
-    pub fake_mint: Box<Account<'info, Mint>>,
-    pub token_program: Program<'info, Token>,
-    
+    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    pub token_program_2022: Program<'info, Token2022>,
 }
 #[derive(Accounts)]
 pub struct InitMrgnFiPda<'info> {
@@ -430,13 +579,9 @@ pub struct InitMrgnFiPda<'info> {
     pub marginfi_group: AccountInfo<'info>,
     /// CHECK: no validation, for educational purpose only
     pub marginfi_program: AccountInfo<'info>,
-    #[account(init,
-        payer = authority,
-        mint::authority = marginfi_pda,
-        mint::decimals = 9,
-        mint::token_program = token_program_2022,
-    )]
-    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    // created in the handler, anchor can't declare the transfer fee and metadata extensions
+    #[account(mut)]
+    pub jarezi_mint: Signer<'info>,
     pub token_program_2022: Program<'info, Token2022>,
     #[account(mut)]
     /// CHECK:
@@ -547,8 +692,17 @@ pub struct Deposit<'info> {
     pub manager_fee_account: Box<Account<'info, TokenAccount>>,
     #[account(mut)]
     pub pool_mint: Box<Account<'info, Mint>>,
-    /// CHECK:
-    pub stake_pool_program: Program<'info, StakePoolProgram>,
+    /// CHECK: address is checked against the collateral pool
+    #[account(address = collateral_pool.stake_pool_program)]
+    pub stake_pool_program: AccountInfo<'info>,
+    /// CHECK: Marinade vaults only, checked by CPI to Marinade
+    #[account(mut)]
+    pub liq_pool_sol_leg_pda: Option<AccountInfo<'info>>,
+    /// CHECK: Marinade vaults only, checked by CPI to Marinade
+    pub liq_pool_msol_leg_authority: Option<AccountInfo<'info>>,
+    /// CHECK: Marinade vaults only, checked by CPI to Marinade
+    #[account(mut)]
+    pub treasury_msol_account: Option<AccountInfo<'info>>,
 
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
@@ -568,6 +722,12 @@ pub struct Deposit<'info> {
         token::mint = pool_mint_wsol,
     )]
     pub pool_token_receiver_account_wsol: Box<Account<'info, TokenAccount>>,
+    // only used by withdraw, holds the flash-borrowed wSOL
+    #[account(mut,
+        token::authority = signer,
+        token::mint = pool_mint_wsol,
+    )]
+    pub user_wsol_account: Option<Box<Account<'info, TokenAccount>>>,
     #[account(mut)]
     pub liquidity_vault_wsol: Box<Account<'info, TokenAccount>>,
     #[account(mut)]
@@ -621,7 +781,6 @@ pub struct Deposit<'info> {
     pub stake_program: Program<'info, StakeProgram>,
     pub rent: Sysvar<'info, Rent>,
     #[account(
-        mut,
         seeds = [ORACLE_SEED],
         bump = oracle.load()?.bump
     )]
@@ -639,6 +798,26 @@ pub struct Deposit<'info> {
         token::mint = pool_mint_wsol,
     )]
     pub hydra_host_fee_account: Box<Account<'info, TokenAccount>>,
+    #[account(
+        seeds = [LEVERAGE_SEED, marginfi_pda.key().as_ref()],
+        bump = leverage_config.bump
+    )]
+    pub leverage_config: Box<Account<'info, LeverageConfig>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    #[account(
+        seeds = [COLLATERAL_POOL_SEED, marginfi_pda.key().as_ref()],
+        bump = collateral_pool.bump,
+        has_one = stake_pool @ USDY_USDC_ORACLEError::InvalidCollateralPool,
+        has_one = pool_mint @ USDY_USDC_ORACLEError::InvalidCollateralPool,
+        constraint = collateral_pool.reserve == marginfi_bank.key() @ USDY_USDC_ORACLEError::InvalidCollateralPool,
+    )]
+    pub collateral_pool: Box<Account<'info, CollateralPool>>,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct SetWinnerParams {
+    /// Holder snapshot entry the draw falls in, its range is worked out on-chain
+    pub index: u32,
 }
 
 #[derive(Accounts)]
@@ -669,6 +848,29 @@ pub struct SetWinner<'info> {
         )]
         pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
         pub enclave_signer: Signer<'info>,
+    // the revealed request is consumed by the draw so it can't be replayed
+    #[account(mut,
+        close = marginfi_pda,
+        constraint = request.load()?.is_revealed() @ USDY_USDC_ORACLEError::RequestNotRevealed,
+        seeds = [REQUEST_SEED, marginfi_pda.key().as_ref()],
+        bump = request.load()?.bump
+    )]
+    pub request: AccountLoader<'info, RequestAccountData>,
+    #[account(
+        seeds = [HOLDER_SNAPSHOT_SEED, marginfi_pda.key().as_ref()],
+        bump = holder_snapshot.load()?.bump
+    )]
+    pub holder_snapshot: AccountLoader<'info, HolderSnapshotAccount>,
+    #[account(
+        mint::authority = marginfi_pda,
+    )]
+    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    // the winning entry's token account, the prize goes to whoever owns it now: the weight it
+    // drew with is the synced one, so moving tokens after the commit can't hold up the draw
+    #[account(
+        token::mint = jarezi_mint,
+    )]
+    pub winner_token_account: Box<InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>>,
 }
 
 #[derive(Accounts)]
@@ -715,15 +917,34 @@ pub struct Winner<'info> {
         bump
     )]
     pub marginfi_pda_switchboard: Box<Account<'info, MarginFiPdaSwitchboard>>,
-    #[account(mut)]
+    #[account(mut,
+        constraint = winner_winner_chickum_dinner.key() == marginfi_pda.winner_winner_chickum_dinner,
+    )]
     /// CHECK:
     pub winner_winner_chickum_dinner: AccountInfo<'info>,
     #[account(mut,
+        constraint = actual_destination.key() == marginfi_pda.winner_token_account,
         token::authority = winner_winner_chickum_dinner,
         token::mint = jarezi_mint,
         token::token_program = token_program_2022
     )]
     pub actual_destination: Box<InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>>,
+    #[account(mut,
+        constraint = kickback_destination.key() == marginfi_pda.kickback_destination,
+        token::mint = jarezi_mint,
+        token::token_program = token_program_2022
+    )]
+    /// Left out while the vault has no kickback destination set
+    pub kickback_destination: Option<Box<InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>>>,
+    // harvested transfer fees, paid to the winner on top of the prize
+    #[account(mut,
+        address = prize_pool_address(&marginfi_pda.key(), &jarezi_mint.key()),
+        token::authority = marginfi_pda,
+        token::mint = jarezi_mint,
+        token::token_program = token_program_2022
+    )]
+    /// Left out until harvest_withheld_fees has created it
+    pub prize_pool: Option<Box<InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>>>,
    
     pub system_program: Program<'info, System>,
     pub token_program_2022: Program<'info, Token2022>,
@@ -739,6 +960,12 @@ pub struct Winner<'info> {
 
     #[account(mut)]
     pub pool_mint: Box<Account<'info, Mint>>,
+    #[account(
+        seeds = [COLLATERAL_POOL_SEED, marginfi_pda.key().as_ref()],
+        bump = collateral_pool.bump,
+        has_one = pool_mint @ USDY_USDC_ORACLEError::InvalidCollateralPool,
+    )]
+    pub collateral_pool: Box<Account<'info, CollateralPool>>,
      // We use this to verify the functions enclave state was verified successfully
    #[account(
     constraint =
@@ -755,341 +982,411 @@ pub struct Winner<'info> {
         bump = oracle.load()?.bump
     )]
     pub oracle: AccountLoader<'info, MyOracleState>,
-    /// CHECK:
-    #[account(mut)]
+    /// CHECK: the vault's Solend obligation, its owner is checked by the handler
+    #[account(mut,
+        owner = SolendProgram::id() @ USDY_USDC_ORACLEError::InvalidObligation,
+    )]
     pub obligation_pubkey: AccountInfo<'info>,
+    /// CHECK: read for its collateral exchange rate, address is checked
+    #[account(
+        address = collateral_pool.reserve @ USDY_USDC_ORACLEError::InvalidCollateralPool,
+    )]
+    pub marginfi_bank: AccountInfo<'info>,
 }
-/// Collateral exchange rate
-#[derive(Clone, Copy, Debug)]
-pub struct CollateralExchangeRate(Rate);
-
-impl CollateralExchangeRate {
-    /// Convert reserve collateral to liquidity
-    pub fn collateral_to_liquidity(&self, collateral_amount: u64) -> anchor_lang::Result<u64> {
-        Ok(self.decimal_collateral_to_liquidity(collateral_amount.into())?
-            .try_floor_u64().unwrap())
+impl<'info> Deposit<'info> {
+    /// Value of the whole position in lamports, see `net_asset_value`, priced with the
+    /// collateral pool's oracle feed. Expects a refreshed obligation.
+    pub fn vault_net_asset_value(&self) -> anchor_lang::Result<Decimal> {
+        let pool_price = self.collateral_pool.price(&*self.oracle.load()?, Clock::get()?.unix_timestamp)?;
+        let reserve = Reserve::unpack(&self.marginfi_bank.try_borrow_data()?)?;
+        net_asset_value(
+            &self.obligation()?,
+            &self.marginfi_bank.key(),
+            &reserve,
+            self.pool_token_receiver_account.amount,
+            pool_price,
+        )
     }
 
-    /// Convert reserve collateral to liquidity
-    pub fn decimal_collateral_to_liquidity(
-        &self,
-        collateral_amount: Decimal,
-    ) -> anchor_lang::Result<Decimal> {
-        Ok(collateral_amount.try_div(self.0).unwrap())
+    /// (collateral tokens, wSOL debt, idle pool tokens) owned by `amount` jarezi, i.e. its
+    /// share of every part of `vault_net_asset_value`. Assets are taken after the withdraw
+    /// haircut and round down, debt rounds up, so the holders left behind never pick up the
+    /// difference.
+    pub fn withdraw_shares(&self, amount: u64) -> anchor_lang::Result<(u64, u64, u64)> {
+        let supply = self.jarezi_mint.supply;
+        if amount == 0 || amount > supply {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidWithdrawAmount));
+        }
+        let paid_out = self.leverage_config.after_withdraw_haircut(amount)?;
+        let obligation = self.obligation()?;
+        let (collateral, _) = obligation.find_collateral_in_deposits(self.marginfi_bank.key())?;
+        let collateral_share = to_amount(
+            Decimal::from(collateral.deposited_amount)
+                .try_mul(paid_out)?
+                .try_div(supply)?,
+            Rounding::Down,
+        )?;
+        let debt_share = match obligation.find_liquidity_in_borrows(self.marginfi_bank_wsol.key()) {
+            Ok((liquidity, _)) => to_amount(
+                liquidity.borrowed_amount_wads.try_mul(amount)?.try_div(supply)?,
+                Rounding::Up,
+            )?,
+            // nothing borrowed yet
+            Err(_) => 0,
+        };
+        let idle_share = to_amount(
+            Decimal::from(self.pool_token_receiver_account.amount)
+                .try_mul(paid_out)?
+                .try_div(supply)?,
+            Rounding::Down,
+        )?;
+        Ok((collateral_share, debt_share, idle_share))
+    }
+
+    /// See `MarginFiPda::trip_breaker`.
+    fn trip_breaker(&mut self) -> anchor_lang::Result<bool> {
+        let vault = self.marginfi_pda.key();
+        let mean = self.oracle.load()?.latest_mean(self.collateral_pool.oracle_symbol);
+        self.marginfi_pda.trip_breaker(vault, mean, self.signer.key())
     }
 
-    /// Convert reserve liquidity to collateral
-    pub fn liquidity_to_collateral(&self, liquidity_amount: u64) -> anchor_lang::Result<u64> {
-        Ok(self.decimal_liquidity_to_collateral(liquidity_amount.into())?
-            .try_floor_u64().unwrap())
+    fn obligation(&self) -> anchor_lang::Result<Obligation> {
+        Ok(Obligation::unpack(&self.obligation_pubkey.try_borrow_data()?)?)
     }
 
-    /// Convert reserve liquidity to collateral
-    pub fn decimal_liquidity_to_collateral(
+    /// wSOL lamports that can still be borrowed before the obligation drops under the
+    /// configured health-factor floor or Solend's allowed borrow value.
+    fn borrow_headroom(
         &self,
-        liquidity_amount: Decimal,
-    ) -> anchor_lang::Result<Decimal> {
-        Ok(liquidity_amount.try_mul(self.0).unwrap())
+        obligation: &Obligation,
+        leverage_config: &LeverageConfig,
+    ) -> anchor_lang::Result<u64> {
+        let headroom = leverage_config.borrow_headroom(obligation)?;
+        let reserve = Reserve::unpack(&self.marginfi_bank_wsol.try_borrow_data()?)?;
+        to_amount(
+            headroom
+                .try_div(reserve.liquidity.market_price)?
+                .try_mul(10u64.pow(reserve.liquidity.mint_decimals as u32))?,
+            Rounding::Down,
+        )
     }
-}
 
-impl From<CollateralExchangeRate> for Rate {
-    fn from(exchange_rate: CollateralExchangeRate) -> Self {
-        exchange_rate.0
+    fn refresh_obligation(&self, signer: &[&[&[u8]]]) -> anchor_lang::Result<()> {
+        staged(VaultError::Refresh, 0, || invoke_signed(
+            &solend_sdk::instruction::refresh_reserve(
+                self.solend_sdk.key(),
+                self.marginfi_bank.key(),
+                self.pyth_oracle.key(),
+                self.switchboard_oracle.key(),
+            ),
+            &[
+                self.marginfi_bank.to_account_info(),
+                self.pyth_oracle.to_account_info(),
+                self.solend_sdk.to_account_info(),
+                self.lending_market_authority_pubkey.to_account_info(),
+                self.switchboard_oracle.to_account_info(),
+            ],
+            signer,
+        ))?;
+        staged(VaultError::Refresh, 0, || invoke_signed(
+            &solend_sdk::instruction::refresh_reserve(
+                self.solend_sdk.key(),
+                self.marginfi_bank_wsol.key(),
+                self.pyth_oracle2.key(),
+                self.switchboard_oracle2.key(),
+            ),
+            &[
+                self.marginfi_bank_wsol.to_account_info(),
+                self.pyth_oracle2.to_account_info(),
+                self.solend_sdk.to_account_info(),
+                self.lending_market_authority_pubkey.to_account_info(),
+                self.switchboard_oracle2.to_account_info(),
+            ],
+            signer,
+        ))?;
+        staged(VaultError::Refresh, 0, || invoke_signed(
+            &solend_sdk::instruction::refresh_obligation(
+                self.solend_sdk.key(),
+                self.obligation_pubkey.key(),
+                vec![self.marginfi_bank.key(), self.marginfi_bank_wsol.key()],
+            ),
+            &[
+                self.obligation_pubkey.to_account_info(),
+                self.solend_sdk.to_account_info(),
+                self.lending_market_authority_pubkey.to_account_info(),
+                self.marginfi_bank.to_account_info(),
+                self.marginfi_bank_wsol.to_account_info(),
+            ],
+            signer,
+        ))?;
+        Ok(())
+    }
+
+    fn deposit_collateral(&self, amount: u64, signer: &[&[&[u8]]]) -> anchor_lang::Result<()> {
+        staged(VaultError::SolendDeposit, amount, || invoke_signed(
+            &solend_sdk::instruction::deposit_reserve_liquidity_and_obligation_collateral(
+                self.solend_sdk.key(),
+                amount,
+                self.pool_token_receiver_account.key(),
+                self.user_collateral_pubkey.key(),
+                self.marginfi_bank.key(),
+                self.liquidity_vault.key(),
+                self.reserve_collateral_mint_pubkey.key(),
+                self.lending_market_pubkey.key(),
+                self.destination_deposit_collateral_pubkey.key(),
+                self.obligation_pubkey.key(),
+                self.marginfi_pda.key(),
+                self.pyth_oracle.key(),
+                self.switchboard_oracle.key(),
+                self.marginfi_pda.key(),
+            ),
+            &[
+                self.pool_token_receiver_account.to_account_info(),
+                self.user_collateral_pubkey.to_account_info(),
+                self.marginfi_bank.to_account_info(),
+                self.liquidity_vault.to_account_info(),
+                self.destination_deposit_collateral_pubkey.to_account_info(),
+                self.lending_market_pubkey.to_account_info(),
+                self.reserve_collateral_mint_pubkey.to_account_info(),
+                self.obligation_pubkey.to_account_info(),
+                self.marginfi_pda.to_account_info(),
+                self.pyth_oracle.to_account_info(),
+                self.system_program.to_account_info(),
+                self.solend_sdk.to_account_info(),
+                self.lending_market_authority_pubkey.to_account_info(),
+                self.switchboard_oracle.to_account_info(),
+            ],
+            signer,
+        ))?;
+        Ok(())
+    }
+
+    fn borrow_wsol(&self, amount: u64, signer: &[&[&[u8]]]) -> anchor_lang::Result<()> {
+        staged(VaultError::Borrow, amount, || invoke_signed(
+            &solend_sdk::instruction::borrow_obligation_liquidity(
+                self.solend_sdk.key(),
+                amount,
+                self.liquidity_vault_wsol.key(),
+                self.pool_token_receiver_account_wsol.key(),
+                self.marginfi_bank_wsol.key(),
+                self.stake_pool_withdraw_authority_wsol.key(),
+                self.obligation_pubkey.key(),
+                self.lending_market_pubkey.key(),
+                self.marginfi_pda.key(),
+                Some(self.hydra_host_fee_account.key()),
+            ),
+            &[
+                self.lending_market_authority_pubkey.to_account_info(),
+                self.liquidity_vault_wsol.to_account_info(),
+                self.pool_token_receiver_account_wsol.to_account_info(),
+                self.marginfi_bank_wsol.to_account_info(),
+                self.stake_pool_withdraw_authority_wsol.to_account_info(),
+                self.obligation_pubkey.to_account_info(),
+                self.lending_market_pubkey.to_account_info(),
+                self.marginfi_pda.to_account_info(),
+                self.system_program.to_account_info(),
+                self.solend_sdk.to_account_info(),
+                self.token_program.to_account_info(),
+                self.hydra_host_fee_account.to_account_info(),
+            ],
+            signer,
+        ))?;
+        Ok(())
+    }
+
+    /// Unwraps the borrowed wSOL to the depositor so it can be restaked.
+    fn close_wsol(&self, amount: u64, signer: &[&[&[u8]]]) -> anchor_lang::Result<()> {
+        staged(VaultError::CloseWsol, amount, || invoke_signed(
+            &spl_token::instruction::close_account(
+                &anchor_spl::token::ID,
+                &self.pool_token_receiver_account_wsol.key(),
+                &self.signer.key(),
+                &self.marginfi_pda.key(),
+                &[], // TODO: support multisig
+            )?,
+            &[
+                self.pool_token_receiver_account_wsol.to_account_info(),
+                self.signer.to_account_info(),
+                self.marginfi_pda.to_account_info(),
+                self.token_program.to_account_info(),
+            ],
+            signer,
+        ))?;
+        Ok(())
+    }
+
+    fn create_wsol_account(&self) -> anchor_lang::Result<()> {
+        anchor_spl::associated_token::create_idempotent(CpiContext::new(
+            self.associated_token_program.to_account_info(),
+            anchor_spl::associated_token::Create {
+                payer: self.signer.to_account_info(),
+                associated_token: self.pool_token_receiver_account_wsol.to_account_info(),
+                authority: self.marginfi_pda.to_account_info(),
+                mint: self.pool_mint_wsol.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+            },
+        ))
+    }
+
+    fn marinade_account(account: &Option<AccountInfo<'info>>) -> anchor_lang::Result<AccountInfo<'info>> {
+        match account {
+            Some(account) => Ok(account.clone()),
+            None => Err(error!(USDY_USDC_ORACLEError::MissingMarinadeAccounts)),
+        }
     }
-}
-/// Return the current collateral exchange rate.
-pub fn exchange_rate(
-    total_liquidity: u64,
-    mint_total_supply: u64
-) -> anchor_lang::Result<CollateralExchangeRate> {
-    
-    let mint_total_supply = Decimal::from(mint_total_supply);
-    let rate = Rate::try_from(mint_total_supply.try_div(Decimal::from(total_liquidity))?)?;
 
-    Ok(CollateralExchangeRate(rate))
+    fn stake_sol(&self, amount: u64) -> anchor_lang::Result<()> {
+        if self.collateral_pool.kind == PoolKind::Marinade {
+            let liq_pool_sol_leg_pda = Self::marinade_account(&self.liq_pool_sol_leg_pda)?;
+            let liq_pool_msol_leg_authority = Self::marinade_account(&self.liq_pool_msol_leg_authority)?;
+            staged(VaultError::StakePoolDeposit, amount, || invoke(
+                &marinade_deposit(
+                    &self.stake_pool_program.key(),
+                    &self.stake_pool.key(),
+                    &self.pool_mint.key(),
+                    &self.manager_fee_account.key(),
+                    &self.signer.key(),
+                    &self.pool_token_receiver_account.key(),
+                    amount,
+                ),
+                &[
+                    self.stake_pool.to_account_info(),
+                    self.pool_mint.to_account_info(),
+                    liq_pool_sol_leg_pda,
+                    self.manager_fee_account.to_account_info(),
+                    liq_pool_msol_leg_authority,
+                    self.reserve_stake_account.to_account_info(),
+                    self.signer.to_account_info(),
+                    self.pool_token_receiver_account.to_account_info(),
+                    self.stake_pool_withdraw_authority.to_account_info(),
+                    self.system_program.to_account_info(),
+                    self.token_program.to_account_info(),
+                    self.stake_pool_program.to_account_info(),
+                ],
+            ))?;
+            return Ok(());
+        }
+        staged(VaultError::StakePoolDeposit, amount, || invoke(
+            &spl_stake_pool::instruction::deposit_sol(
+                &self.stake_pool_program.key(),
+                &self.stake_pool.key(),
+                &self.stake_pool_withdraw_authority.key(),
+                &self.reserve_stake_account.key(),
+                &self.signer.key(),
+                &self.pool_token_receiver_account.key(),
+                &self.manager_fee_account.key(),
+                &self.hydra_referrer.key(),
+                &self.pool_mint.key(),
+                &anchor_spl::token::ID,
+                amount,
+            ),
+            &[
+                self.signer.to_account_info(),
+                self.reserve_stake_account.to_account_info(),
+                self.pool_token_receiver_account.to_account_info(),
+                self.stake_pool_withdraw_authority.to_account_info(),
+                self.manager_fee_account.to_account_info(),
+                self.pool_mint.to_account_info(),
+                self.stake_pool.to_account_info(),
+                self.stake_pool_program.to_account_info(),
+                self.system_program.to_account_info(),
+                self.token_program.to_account_info(),
+                self.hydra_referrer.to_account_info(),
+            ],
+        ))?;
+        Ok(())
+    }
 }
 impl Deposit<'_> {
     pub fn deposit(
         ctx: Context<Deposit>,
         amount: u64,
     ) -> anchor_lang::Result<()> {
-        let bsol_price = ctx.accounts.oracle.load()?.bsol_sol.mean;
+        ctx.accounts.marginfi_pda.check_not_paused(PAUSE_DEPOSIT)?;
+        if ctx.accounts.trip_breaker()? {
+            return Ok(());
+        }
+        ctx.accounts
+            .marginfi_pda
+            .throttle_inflow(Clock::get()?.slot, Decimal::from(amount))?;
+        let pool_price = ctx
+            .accounts
+            .collateral_pool
+            .price(&*ctx.accounts.oracle.load()?, Clock::get()?.unix_timestamp)?;
+        let leverage_config = ctx.accounts.leverage_config.clone();
+        let pool_tokens_before = ctx.accounts.pool_token_receiver_account.amount;
 
         let winner = ctx.accounts.marginfi_pda.thewinnerog;
         let signer: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(),
          &[ctx.accounts.marginfi_pda.bump]]];
-        // stake bsol
-        {
-            invoke(
-                &spl_stake_pool::instruction::deposit_sol(
-                    &spl_stake_pool::id(),
-                    &ctx.accounts.stake_pool.key(),
-                    &ctx.accounts.stake_pool_withdraw_authority.key(),
-                    &ctx.accounts.reserve_stake_account.key(),
-                    &ctx.accounts.signer.key(),
-                    &ctx.accounts.pool_token_receiver_account.key(),
-                    &ctx.accounts.manager_fee_account.key(),
-                    &ctx.accounts.hydra_referrer.key(),
-                    &ctx.accounts.pool_mint.key(),
-                    &anchor_spl::token::ID,
-                    amount,
-                ),
-                &[
-                    ctx.accounts.hydra_referrer.to_account_info(),
-                    ctx.accounts.signer.to_account_info(),
-                    ctx.accounts.reserve_stake_account.to_account_info(),
-                    ctx.accounts.pool_token_receiver_account.to_account_info(),
-                    ctx.accounts.stake_pool_withdraw_authority.to_account_info(),
-                    ctx.accounts.manager_fee_account.to_account_info(),
-                    ctx.accounts.pool_mint.to_account_info(),
-                    ctx.accounts.stake_pool.to_account_info(),
-                    ctx.accounts.stake_pool_program.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                    ctx.accounts.token_program.to_account_info(),
-                ],
-            )?;
-        }
-        {
-            
-
-            let rate: f64 = 1_000_000_000.0 / bsol_price as f64;
-            msg!("rate: {}", rate);
-            let stake_pool_tokens = amount as f64 * rate * (1.0-0.0005-0.00146);
+        // jarezi is minted for the value this deposit adds, at the NAV per share it found
+        ctx.accounts.refresh_obligation(signer)?;
+        let nav_before = ctx.accounts.vault_net_asset_value()?;
+        let supply = ctx.accounts.jarezi_mint.supply;
+        let deposited = amount;
+        // stake into the vault's collateral pool
+        ctx.accounts.stake_sol(amount)?;
+        let mut collateral_tokens = {
+            let stake_pool_tokens = leverage_config.stake_pool_tokens(amount, pool_price)?;
             msg!("stake_pool_tokens: {}", stake_pool_tokens);
-            invoke_signed(
-                &solend_sdk::instruction::deposit_reserve_liquidity_and_obligation_collateral(
-                    ctx.accounts.solend_sdk.key(),
-                    stake_pool_tokens as u64,
-                    ctx.accounts.pool_token_receiver_account.key(),
-                    ctx.accounts.user_collateral_pubkey.key(),
-                    ctx.accounts.marginfi_bank.key(),
-                    ctx.accounts.liquidity_vault.key(),
-                    ctx.accounts.reserve_collateral_mint_pubkey.key(),
-                    ctx.accounts.lending_market_pubkey.key(),
-                    ctx.accounts.destination_deposit_collateral_pubkey.key(),
-                    ctx.accounts.obligation_pubkey.key(),
-                    ctx.accounts.marginfi_pda.key(),
-                    ctx.accounts.pyth_oracle.key(),
-                    ctx.accounts.switchboard_oracle.key(),
-                    ctx.accounts.marginfi_pda.key(),
-                ),
-                &[
-                    ctx.accounts.pool_token_receiver_account.to_account_info(),
-                    ctx.accounts.user_collateral_pubkey.to_account_info(),
-                    ctx.accounts.marginfi_bank.to_account_info(),
-                    ctx.accounts.liquidity_vault.to_account_info(),
-                    ctx.accounts
-                        .destination_deposit_collateral_pubkey
-                        .to_account_info(),
-                    ctx.accounts.lending_market_pubkey.to_account_info(),
-                    ctx.accounts
-                        .reserve_collateral_mint_pubkey
-                        .to_account_info(),
-                    ctx.accounts.obligation_pubkey.to_account_info(),
-                    ctx.accounts.marginfi_pda.to_account_info(),
-                    ctx.accounts.pyth_oracle.to_account_info(),
-                    ctx.accounts.marginfi_pda.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.switchboard_oracle.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            invoke_signed(
-                &solend_sdk::instruction::refresh_reserve(
-                    ctx.accounts.solend_sdk.key(),
-                    ctx.accounts.marginfi_bank.key(),
-                    ctx.accounts.pyth_oracle.key(),
-                    ctx.accounts.switchboard_oracle.key(),
-                ),
-                &[
-                    ctx.accounts.marginfi_bank.to_account_info(),
-                    ctx.accounts.pyth_oracle.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.switchboard_oracle.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            invoke_signed(
-                &solend_sdk::instruction::refresh_reserve(
-                    ctx.accounts.solend_sdk.key(),
-                    ctx.accounts.marginfi_bank_wsol.key(),
-                    ctx.accounts.pyth_oracle2.key(),
-                    ctx.accounts.switchboard_oracle2.key(),
-                ),
-                &[
-                    ctx.accounts.marginfi_bank_wsol.to_account_info(),
-                    ctx.accounts.pyth_oracle2.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.switchboard_oracle2.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            invoke_signed(
-                &solend_sdk::instruction::refresh_obligation(
-                    ctx.accounts.solend_sdk.key(),
-                    ctx.accounts.obligation_pubkey.key(),
-                    vec![
-                        ctx.accounts.marginfi_bank.key(),
-                        ctx.accounts.marginfi_bank_wsol.key(),
-                    ],
-                ),
-                &[
-                    ctx.accounts.obligation_pubkey.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.marginfi_bank.to_account_info(),
-                    ctx.accounts.marginfi_bank_wsol.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-
-        {
-            let rate: f64 = 1_000_000_000.0 / bsol_price as f64;
 
-            let stake_pool_tokens = amount as f64 * rate * (1.0-0.0005-0.00146);
-            let ltv: f64 = 0.535;
-            msg!("ltv: {}", ltv);
-            let amount = stake_pool_tokens * ltv;
-            msg!("amount: {}", amount);
-
-            invoke_signed(
-                &solend_sdk::instruction::borrow_obligation_liquidity(
-                    ctx.accounts.solend_sdk.key(),
-                    amount as u64,
-                    ctx.accounts.liquidity_vault_wsol.key(),
-                    ctx.accounts.pool_token_receiver_account_wsol.key(),
-                    ctx.accounts.marginfi_bank_wsol.key(),
-                    ctx.accounts.stake_pool_withdraw_authority_wsol.key(),
-                    ctx.accounts.obligation_pubkey.key(),
-                    ctx.accounts.lending_market_pubkey.key(),
-                    ctx.accounts.marginfi_pda.key(),
-                    Some(ctx.accounts.hydra_host_fee_account.key()),
-                ),
-                &[
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.liquidity_vault_wsol.to_account_info(),
-                    ctx.accounts
-                        .pool_token_receiver_account_wsol
-                        .to_account_info(),
-                    ctx.accounts.marginfi_bank_wsol.to_account_info(),
-                    ctx.accounts
-                        .stake_pool_withdraw_authority_wsol
-                        .to_account_info(),
-                    ctx.accounts.obligation_pubkey.to_account_info(),
-                    ctx.accounts
-                        .lending_market_pubkey
-                        .to_owned()
-                        .to_account_info(),
-                    ctx.accounts.marginfi_pda.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts.token_program.to_account_info(),
-                    ctx.accounts.hydra_host_fee_account.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            solana_program::program::invoke_signed(
-                &spl_token::instruction::close_account(
-                    &anchor_spl::token::ID,
-                    &ctx.accounts.pool_token_receiver_account_wsol.key(),
-                    &ctx.accounts.signer.key(),
-                    &ctx.accounts.marginfi_pda.key(),
-                    &[], // TODO: support multisig
-                )?,
-                &[
-                    ctx.accounts
-                        .pool_token_receiver_account_wsol
-                        .to_account_info(),
-                    ctx.accounts.signer.to_account_info(),
-                    ctx.accounts.marginfi_pda.to_account_info(),
-                    ctx.accounts.token_program.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        
-        {
-            let rate: f64 = 1_000_000_000.0 / bsol_price as f64;
+            ctx.accounts.pool_token_receiver_account.reload()?;
+            let received = checked_sub(ctx.accounts.pool_token_receiver_account.amount, pool_tokens_before)?;
+            msg!("received: {}", received);
+            if received < leverage_config.min_received(stake_pool_tokens)? {
+                return Err(error!(USDY_USDC_ORACLEError::SlippageExceeded));
+            }
+            // the quote only bounds slippage, what the pool actually minted is what gets posted
+            ctx.accounts.deposit_collateral(received, signer)?;
+            received
+        };
+        // borrow wSOL against the collateral, restake it and post the new pool tokens as
+        // collateral, until the target leverage or the health-factor floor is hit
+        for i in 0..leverage_config.loop_count {
+            ctx.accounts.refresh_obligation(signer)?;
+            let obligation = ctx.accounts.obligation()?;
+            if leverage_config.leverage_reached(&obligation)? {
+                msg!("target leverage reached after {} loops", i);
+                break;
+            }
+            let wanted = leverage_config.borrow_amount(collateral_tokens, pool_price)?;
+            let headroom = ctx.accounts.borrow_headroom(&obligation, &leverage_config)?;
+            let amount = wanted.min(headroom);
+            msg!("loop {} borrow: {} (headroom {})", i, amount, headroom);
+            if amount == 0 {
+                break;
+            }
 
+            if i > 0 {
+                // the wSOL account is closed at the end of every pass
+                ctx.accounts.create_wsol_account()?;
+            }
+            ctx.accounts.borrow_wsol(amount, signer)?;
+            ctx.accounts.close_wsol(amount, signer)?;
 
-            let stake_pool_tokens = amount as f64 * rate * (1.0-0.0005-0.00146);
-                        let ltv: f64 = 0.535;
+            let pool_tokens_before = ctx.accounts.pool_token_receiver_account.amount;
+            ctx.accounts.stake_sol(amount)?;
+            ctx.accounts.pool_token_receiver_account.reload()?;
+            let restaked = checked_sub(ctx.accounts.pool_token_receiver_account.amount, pool_tokens_before)?;
+            msg!("restaked: {}", restaked);
+            ctx.accounts.deposit_collateral(restaked, signer)?;
 
-            let amount = stake_pool_tokens * ltv;
-            invoke(
-                &spl_stake_pool::instruction::deposit_sol(
-                    &spl_stake_pool::id(),
-                    &ctx.accounts.stake_pool.key(),
-                    &ctx.accounts.stake_pool_withdraw_authority.key(),
-                    &ctx.accounts.reserve_stake_account.key(),
-                    &ctx.accounts.signer.key(),
-                    &ctx.accounts.pool_token_receiver_account.key(),
-                    &ctx.accounts.manager_fee_account.key(),
-                    &ctx.accounts.hydra_referrer.key(),
-                    &ctx.accounts.pool_mint.key(),
-                    &anchor_spl::token::ID,
-                    amount as u64,
-                ),
-                &[
-                    ctx.accounts.signer.to_account_info(),
-                    ctx.accounts.reserve_stake_account.to_account_info(),
-                    ctx.accounts.pool_token_receiver_account.to_account_info(),
-                    ctx.accounts.stake_pool_withdraw_authority.to_account_info(),
-                    ctx.accounts.manager_fee_account.to_account_info(),
-                    ctx.accounts.pool_mint.to_account_info(),
-                    ctx.accounts.stake_pool.to_account_info(),
-                    ctx.accounts.stake_pool_program.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                    ctx.accounts.token_program.to_account_info(),
-                    ctx.accounts.hydra_referrer.to_account_info(),
-                ],
-            )
-            .unwrap();
+            collateral_tokens = restaked;
+        }
+        ctx.accounts.refresh_obligation(signer)?;
+        if !leverage_config.health_ok(&ctx.accounts.obligation()?)? {
+            return Err(error!(USDY_USDC_ORACLEError::HealthFactorTooLow));
         }
         {
-
-            let rate: f64 = 1_000_000_000.0 / bsol_price as f64;
-            let ltv: f64 = 0.535;
-            let amount = amount as f64 * ltv;
-
-
-            let amount = amount as f64 * rate * (1.0-0.0005-0.00146);
-                        let rate: f64 = 1_000_000_000.0 / bsol_price as f64;
-            msg!("rate: {}", rate);
-
-            let amount = amount * rate as f64;
+            ctx.accounts.pool_token_receiver_account.reload()?;
+            let nav_after = ctx.accounts.vault_net_asset_value()?;
+            let added = nav_after.try_sub(nav_before)?;
+            msg!("nav: {} -> {}", to_amount(nav_before, Rounding::Down)?, to_amount(nav_after, Rounding::Down)?);
+            // mints round down
+            let amount = shares_for_value(added, nav_before, supply)?;
             msg!("amount: {}", amount);
-            anchor_spl::token_interface::mint_to(CpiContext::new_with_signer(
+            staged(VaultError::Mint, amount, || anchor_spl::token_interface::mint_to(CpiContext::new_with_signer(
                 ctx.accounts.token_program_2022.to_account_info(),
                 MintTo {
                     mint: ctx.accounts.jarezi_mint.to_account_info(),
@@ -1097,17 +1394,60 @@ impl Deposit<'_> {
                     authority: ctx.accounts.marginfi_pda.to_account_info(),
                 },
                 &signer,
-            ), amount as u64).unwrap();
+            ), amount))?;
+            emit!(Deposited {
+                vault: ctx.accounts.marginfi_pda.key(),
+                user: ctx.accounts.signer.key(),
+                lamports: deposited,
+                jarezi_minted: amount,
+                exchange_rate: share_price(nav_after, checked_add(supply, amount)?)?,
+                pool_price,
+                slot: Clock::get()?.slot,
+            });
         }
 
         Ok(())
     }
     pub fn set_winner_winner_chickum_dinner(
         ctx: Context<SetWinner>,
+        params: SetWinnerParams,
     ) -> anchor_lang::Result<()> {
         let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        let draw = ctx.accounts.request.load()?.draw(marginfi_pda.snapshot_total_weight)?;
+        msg!("draw: {}", draw);
+
+        // the snapshot can't be synced between post_holder_snapshot and this draw
+        let snapshot_info = ctx.accounts.holder_snapshot.to_account_info();
+        let snapshot_data = snapshot_info.try_borrow_data()?;
+        let entries = HolderEntries::new(&snapshot_data)?;
+        let index = params.index as usize;
+        let entry = entries
+            .get(index)
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::WinnerOutOfRange))?;
+        let range_start = entries.range_start(index)?;
+        if draw < range_start || draw - range_start >= entry.weight {
+            return Err(error!(USDY_USDC_ORACLEError::WinnerOutOfRange));
+        }
+        let owner = ctx.accounts.new_winner_winner_chickum_dinner.key();
+        if owner == marginfi_pda.key() {
+            return Err(error!(USDY_USDC_ORACLEError::VaultCannotWin));
+        }
+        let winner_token_account = &ctx.accounts.winner_token_account;
+        if winner_token_account.key() != entry.token_account || winner_token_account.owner != owner {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidHolderProof));
+        }
 
+        marginfi_pda.last_draw = draw;
+        marginfi_pda.winner_token_account = entry.token_account;
         marginfi_pda.winner_winner_chickum_dinner = ctx.accounts.new_winner_winner_chickum_dinner.key();
+        emit!(WinnerSet {
+            vault: marginfi_pda.key(),
+            winner: marginfi_pda.winner_winner_chickum_dinner,
+            token_account: entry.token_account,
+            draw,
+            snapshot_total_weight: marginfi_pda.snapshot_total_weight,
+            slot: Clock::get()?.slot,
+        });
         
         Ok(())   
     }
@@ -1117,40 +1457,65 @@ impl Deposit<'_> {
     ) -> anchor_lang::Result<()> {
         let marginfi_pda_switchboard = &mut ctx.accounts.marginfi_pda_switchboard;
         marginfi_pda_switchboard.switchboard_function = ctx.accounts.switchboard_function.key();
-
+        emit_config_changed(
+            ctx.accounts.marginfi_pda.key(),
+            ctx.accounts.authority.key(),
+            ConfigKind::VaultSwitchboardFunction,
+        )?;
 
         Ok(())   
     }
+    /// Unwinds the caller's pro-rata share of the leveraged position so the vault's LTV is
+    /// unchanged by the exit. Solend refuses flash loans over CPI, so the caller wraps this
+    /// instruction in the transaction:
+    ///
+    /// 0. `flash_borrow_reserve_liquidity` of the debt share (see `withdraw_shares`) into `user_wsol_account`
+    /// 1. `withdraw`
+    /// 2. `flash_repay_reserve_liquidity` from `user_wsol_account`, signed by the caller
+    ///
+    /// The flash-borrowed wSOL repays the share of the debt, the freed collateral is redeemed
+    /// and unstaked, and the flash loan plus its fee is wrapped back into `user_wsol_account`.
+    /// Whatever SOL is left is the caller's.
     pub fn withdraw(
         ctx: Context<Deposit>,
         amount: u64,
     ) -> anchor_lang::Result<()> {
-
-        let bsol_price = ctx.accounts.oracle.load()?.bsol_sol.mean ;
-        let wsol_borrow_rate = ctx.accounts.oracle.load()?.wsol_borrow.mean as f64 // this is a 10^18 we want it as a f64 so we divide by 10^18
-        / 1_000_000_000.0;
-        msg!("wsol_borrow_rate: {}", wsol_borrow_rate);
-        let wsol_borrow_rate = 1.0 + wsol_borrow_rate;
-        let marginfi_pda = ctx.accounts.marginfi_pda.clone();
+        ctx.accounts.marginfi_pda.check_not_paused(PAUSE_WITHDRAW)?;
+        if ctx.accounts.trip_breaker()? {
+            return Ok(());
+        }
         let winner = ctx.accounts.marginfi_pda.thewinnerog;
         let signer: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(),
-        &[marginfi_pda.bump]]];
-        let mint_supply = ctx.accounts.jarezi_mint.supply;
-            let rate: CollateralExchangeRate = exchange_rate(
-                ctx.accounts.pool_token_receiver_account.amount,
-                mint_supply,
-            ).unwrap();
-        let amount = (amount as f64 * 0.999) as u64;
+        &[ctx.accounts.marginfi_pda.bump]]];
+        let user_wsol_account = match &ctx.accounts.user_wsol_account {
+            Some(user_wsol_account) => user_wsol_account.to_account_info(),
+            None => return Err(error!(USDY_USDC_ORACLEError::MissingWsolAccount)),
+        };
+
+        ctx.accounts.refresh_obligation(signer)?;
+        let nav = ctx.accounts.vault_net_asset_value()?;
+        msg!("nav: {}", to_amount(nav, Rounding::Down)?);
+        let supply = ctx.accounts.jarezi_mint.supply;
+        let (collateral_share, debt_share, idle_share) = ctx.accounts.withdraw_shares(amount)?;
+        // throttled at the value the burned jarezi carries out of the vault
+        let value = nav.try_mul(amount)?.try_div(supply)?;
+        ctx.accounts
+            .marginfi_pda
+            .throttle_outflow(Clock::get()?.slot, value)?;
+        msg!("collateral_share: {}", collateral_share);
+        msg!("debt_share: {}", debt_share);
+        msg!("idle_share: {}", idle_share);
+
         // burn tokens
         {
-            solana_program::program::invoke(
+            staged(VaultError::Burn, amount, || solana_program::program::invoke(
                 &spl_token_2022::instruction::burn(
                     &ctx.accounts.token_program_2022.key(),
                     &ctx.accounts.jarezi_token_account.key(),
                     &ctx.accounts.jarezi_mint.key(),
                     &ctx.accounts.signer.key(),
                     &[],
-                    amount as u64,
+                    amount,
                 )?,
                 &[
                     ctx.accounts.token_program_2022.to_account_info(),
@@ -1159,314 +1524,108 @@ impl Deposit<'_> {
                     ctx.accounts.signer.to_account_info(),
                     ctx.accounts.clock.to_account_info(),
                 ],
-            )
-            .unwrap();
+            ))?;
         }
-        {
-            
-            invoke_signed(
-                &spl_stake_pool::instruction::withdraw_sol(
-                    &spl_stake_pool::id(),
-                    &ctx.accounts.stake_pool.key(),
-                    &ctx.accounts.stake_pool_withdraw_authority.key(),
-                    &ctx.accounts.marginfi_pda.key(),
-                    &ctx.accounts.pool_token_receiver_account.key(),
-                    &ctx.accounts.reserve_stake_account.key(),
-                    &ctx.accounts.signer.key(),
-                    &ctx.accounts.manager_fee_account.key(),
-                    &ctx.accounts.pool_mint.key(),
-                    &anchor_spl::token::ID,
-                    amount as u64,
-                ),
-                &[
-                    ctx.accounts.marginfi_pda.to_account_info(),
-                    ctx.accounts.signer.to_account_info(),
-                    ctx.accounts.stake_pool.to_account_info(),
-                    ctx.accounts.stake_pool_withdraw_authority.to_account_info(),
-                    ctx.accounts.pool_token_receiver_account.to_account_info(),
-                    ctx.accounts.reserve_stake_account.to_account_info(),
-                    ctx.accounts.manager_fee_account.to_account_info(),
-                    ctx.accounts.pool_mint.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                    ctx.accounts.token_program.to_account_info(),
-                    ctx.accounts.clock.to_account_info(),
-                    ctx.accounts.stake_history.to_account_info(),
-                    ctx.accounts.stake_program.to_account_info(),
-                    ctx.accounts.rent.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            
-            let  amount = rate.collateral_to_liquidity(amount).unwrap();
-            let mut amount = amount as f64 * bsol_price as f64 / 1_000_000_000 as f64;
-            // amount atm is the og deposit, without any of the accrued interest
-            // we want to kickback kickback_percent_bpm of the interest to the user
-            msg!("kickback_percent_bpm: {}", marginfi_pda.kickback_percent_bpm);
-            msg!("amount: {}", amount);
-            if marginfi_pda.kickback_percent_bpm > 0 {
-                amount = amount * marginfi_pda.kickback_percent_bpm as f64 / 1_000_000.0;
-                msg!("amount: {}", amount);
-            }
-            msg!("amount: {}", amount);
-            // kickback
-
-            /*tesitng 
-            let seeded_tx_ix = &solana_program::system_instruction::transfer_with_seed(
-                &ctx.accounts.to.key(),
-                &ctx.accounts.marginfi_pda.key(),
-                "robot001".to_string(),
-                ctx.accounts.system_program.key(),
-                &ctx.accounts.pool_token_receiver_account_wsol.key(),
-                amount,
-            );*/
-            invoke(
-                &solana_program::system_instruction::transfer(
-                    &ctx.accounts.signer.key(),
-                    &ctx.accounts.pool_token_receiver_account_wsol.key(),
-                    amount as u64, 
-                ),
-                &[
-                    ctx.accounts.signer.to_account_info(),
-                    ctx.accounts
-                        .pool_token_receiver_account_wsol
-                        .to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
-                ],
-            )
-            .unwrap();
-        }
-        {
-
-                anchor_spl::token::sync_native(
-                    CpiContext::new_with_signer(
-                        ctx.accounts.token_program.to_account_info(),
-                        SyncNative {
-                            account: ctx.accounts.pool_token_receiver_account_wsol.to_account_info(),
-                        },
-                        &signer,
-                    ),
-                )
-                .unwrap();
-
-               
-        }
-        {
-            
-            
-            let  amount = rate.collateral_to_liquidity(amount).unwrap();
-            let mut amount = amount as f64 * bsol_price as f64 / 1_000_000_000 as f64;
-            
-            msg!("kickback_percent_bpm: {}", marginfi_pda.kickback_percent_bpm);
-            msg!("amount: {}", amount);
-            if marginfi_pda.kickback_percent_bpm > 0 {
-                amount = amount * marginfi_pda.kickback_percent_bpm as f64 / 1_000_000.0;
-                msg!("amount: {}", amount);
-            }
-            
-            msg!("amount: {}", amount);
-
-            invoke_signed(
+        // repay the share of the debt with the flash-borrowed wSOL
+        if debt_share > 0 {
+            staged(VaultError::Repay, debt_share, || invoke(
                 &solend_sdk::instruction::repay_obligation_liquidity(
                     ctx.accounts.solend_sdk.key(),
-                    amount as u64,
-                    ctx.accounts.pool_token_receiver_account_wsol.key(),
+                    debt_share,
+                    user_wsol_account.key(),
                     ctx.accounts.liquidity_vault_wsol.key(),
                     ctx.accounts.marginfi_bank_wsol.key(),
                     ctx.accounts.obligation_pubkey.key(),
                     ctx.accounts.lending_market_pubkey.key(),
-                    ctx.accounts.marginfi_pda.key(),
+                    ctx.accounts.signer.key(),
                 ),
                 &[
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
+                    user_wsol_account.clone(),
                     ctx.accounts.liquidity_vault_wsol.to_account_info(),
-                    ctx.accounts
-                        .pool_token_receiver_account_wsol
-                        .to_account_info(),
                     ctx.accounts.marginfi_bank_wsol.to_account_info(),
                     ctx.accounts.obligation_pubkey.to_account_info(),
-                    ctx.accounts
-                        .lending_market_pubkey
-                        .to_owned()
-                        .to_account_info(),
-                    ctx.accounts.marginfi_pda.to_account_info(),
-                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.lending_market_pubkey.to_account_info(),
+                    ctx.accounts.signer.to_account_info(),
                     ctx.accounts.solend_sdk.to_account_info(),
                     ctx.accounts.token_program.to_account_info(),
                 ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            // withdraw bsol
-
-            invoke_signed(
-                &solend_sdk::instruction::refresh_reserve(
-                    ctx.accounts.solend_sdk.key(),
-                    ctx.accounts.marginfi_bank.key(),
-                    ctx.accounts.pyth_oracle.key(),
-                    ctx.accounts.switchboard_oracle.key(),
-                ),
-                &[
-                    ctx.accounts.marginfi_bank.to_account_info(),
-                    ctx.accounts.pyth_oracle.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.switchboard_oracle.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            invoke_signed(
-                &solend_sdk::instruction::refresh_reserve(
-                    ctx.accounts.solend_sdk.key(),
-                    ctx.accounts.marginfi_bank_wsol.key(),
-                    ctx.accounts.pyth_oracle2.key(),
-                    ctx.accounts.switchboard_oracle2.key(),
-                ),
-                &[
-                    ctx.accounts.marginfi_bank_wsol.to_account_info(),
-                    ctx.accounts.pyth_oracle2.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.switchboard_oracle2.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
-        }
-        {
-            invoke_signed(
-                &solend_sdk::instruction::refresh_obligation(
-                    ctx.accounts.solend_sdk.key(),
-                    ctx.accounts.obligation_pubkey.key(),
-                    vec![
-                        ctx.accounts.marginfi_bank.key(),
-                        ctx.accounts.marginfi_bank_wsol.key(),
-                    ],
-                ),
-                &[
-                    ctx.accounts.obligation_pubkey.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts
-                        .lending_market_authority_pubkey
-                        .to_account_info(),
-                    ctx.accounts.marginfi_bank.to_account_info(),
-                    ctx.accounts.marginfi_bank_wsol.to_account_info(),
-                ],
-                &signer,
-            )
-            .unwrap();
+            ))?;
         }
-        {
-            
-            
-            let  amount = rate.collateral_to_liquidity(amount).unwrap();
-            
-            let mut amount = amount as f64 / (1_000_000_000.0 / bsol_price as f64) as f64;
-            msg!("kickback_percent_bpm: {}", marginfi_pda.kickback_percent_bpm);
-            msg!("amount: {}", amount);
-            if marginfi_pda.kickback_percent_bpm > 0 {
-                amount = amount * marginfi_pda.kickback_percent_bpm as f64 / 1_000_000.0;
-                msg!("amount: {}", amount);
-            }
-            
-            msg!("amount: {}", amount);
-            let oracle = &mut ctx.accounts.oracle.load_mut()?;
-            let old_timestamp = oracle.last_borrowed_amount_timestamp;
-            
-            let time_diff = oracle.last_borrowed_amount_timestamp - old_timestamp;
-            msg!("time_diff: {}", time_diff);
-            let time_diff = time_diff as f64 / 60.0 / 60.0 / 24.0 / 365.0;
-            msg!("time_diff: {}", time_diff);
-            let rate = wsol_borrow_rate.powf(time_diff);
-            msg!("rate: {}", rate);
-            let amount = amount as f64 * rate as f64;
-            msg!("amount: {}", amount);
-
-
-            // / 0.535
-            let amount = amount as f64 / 0.535;
-            let amount = amount as u64;
-
-            invoke_signed(
-                &solend_sdk::instruction::withdraw_obligation_collateral_and_redeem_reserve_collateral(
-                    ctx.accounts.solend_sdk.key(),
-                    amount as u64,
-                    ctx.accounts.destination_deposit_collateral_pubkey.key(),
-                    ctx.accounts.user_collateral_pubkey.key(),
-                    ctx.accounts.marginfi_bank.key(),
-                    ctx.accounts.obligation_pubkey.key(),
-                    ctx.accounts.lending_market_pubkey.key(),
-                    ctx.accounts.pool_token_receiver_account.key(),
-                    ctx.accounts.reserve_collateral_mint_pubkey.key(),
-                    ctx.accounts.liquidity_vault.key(),
-                    ctx.accounts.marginfi_pda.key(),
-                    ctx.accounts.marginfi_pda.key()
+        // withdraw and redeem the freed collateral back into pool tokens
+        ctx.accounts.refresh_obligation(signer)?;
+        let pool_tokens_before = ctx.accounts.pool_token_receiver_account.amount;
+        staged(VaultError::Withdraw, collateral_share, || invoke_signed(
+            &solend_sdk::instruction::withdraw_obligation_collateral_and_redeem_reserve_collateral(
+                ctx.accounts.solend_sdk.key(),
+                collateral_share,
+                ctx.accounts.destination_deposit_collateral_pubkey.key(),
+                ctx.accounts.user_collateral_pubkey.key(),
+                ctx.accounts.marginfi_bank.key(),
+                ctx.accounts.obligation_pubkey.key(),
+                ctx.accounts.lending_market_pubkey.key(),
+                ctx.accounts.pool_token_receiver_account.key(),
+                ctx.accounts.reserve_collateral_mint_pubkey.key(),
+                ctx.accounts.liquidity_vault.key(),
+                ctx.accounts.marginfi_pda.key(),
+                ctx.accounts.marginfi_pda.key()
+            ),
+            &[
+                ctx.accounts.pool_token_receiver_account.to_account_info(),
+                ctx.accounts.user_collateral_pubkey.to_account_info(),
+                ctx.accounts.marginfi_bank.to_account_info(),
+                ctx.accounts.liquidity_vault.to_account_info(),
+                ctx.accounts.destination_deposit_collateral_pubkey.to_account_info(),
+                ctx.accounts.lending_market_pubkey.to_account_info(),
+                ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
+                ctx.accounts.obligation_pubkey.to_account_info(),
+                ctx.accounts.marginfi_pda.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.solend_sdk.to_account_info(),
+                ctx.accounts.lending_market_authority_pubkey.to_account_info(),
+            ],
+            signer,
+        ))?;
+        ctx.accounts.pool_token_receiver_account.reload()?;
+        let redeemed = checked_sub(ctx.accounts.pool_token_receiver_account.amount, pool_tokens_before)?;
+        msg!("redeemed: {}", redeemed);
+        let redeemed = checked_add(redeemed, idle_share)?;
+
+        // unstake to the caller
+        let lamports_before = ctx.accounts.signer.lamports();
+        if ctx.accounts.collateral_pool.kind == PoolKind::Marinade {
+            let liq_pool_sol_leg_pda = Deposit::marinade_account(&ctx.accounts.liq_pool_sol_leg_pda)?;
+            let treasury_msol_account = Deposit::marinade_account(&ctx.accounts.treasury_msol_account)?;
+            staged(VaultError::Unstake, redeemed, || invoke_signed(
+                &marinade_liquid_unstake(
+                    &ctx.accounts.stake_pool_program.key(),
+                    &ctx.accounts.stake_pool.key(),
+                    &ctx.accounts.pool_mint.key(),
+                    &ctx.accounts.manager_fee_account.key(),
+                    &treasury_msol_account.key(),
+                    &ctx.accounts.pool_token_receiver_account.key(),
+                    &ctx.accounts.marginfi_pda.key(),
+                    &ctx.accounts.signer.key(),
+                    redeemed,
                 ),
                 &[
+                    ctx.accounts.stake_pool.to_account_info(),
+                    ctx.accounts.pool_mint.to_account_info(),
+                    liq_pool_sol_leg_pda,
+                    ctx.accounts.manager_fee_account.to_account_info(),
+                    treasury_msol_account,
                     ctx.accounts.pool_token_receiver_account.to_account_info(),
-                    ctx.accounts.user_collateral_pubkey.to_account_info(),
-                    ctx.accounts.marginfi_bank.to_account_info(),
-                    ctx.accounts.liquidity_vault.to_account_info(),
-                    ctx.accounts.destination_deposit_collateral_pubkey.to_account_info(),
-                    ctx.accounts.lending_market_pubkey.to_account_info(),
-                    ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
-                    ctx.accounts.obligation_pubkey.to_account_info(),
-                    ctx.accounts.marginfi_pda.to_account_info(),
                     ctx.accounts.marginfi_pda.to_account_info(),
+                    ctx.accounts.signer.to_account_info(),
                     ctx.accounts.system_program.to_account_info(),
-                    ctx.accounts.solend_sdk.to_account_info(),
-                    ctx.accounts.lending_market_authority_pubkey.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.accounts.stake_pool_program.to_account_info(),
                 ],
-                 &signer,
-            )
-            .unwrap();
-        }
-        {
-            
-            
-            let  amount = rate.collateral_to_liquidity(amount).unwrap();
-            
-            let mut amount = amount as f64 / (1_000_000_000.0 / bsol_price as f64) as f64;
-            msg!("kickback_percent_bpm: {}", marginfi_pda.kickback_percent_bpm);
-            msg!("amount: {}", amount);
-            if marginfi_pda.kickback_percent_bpm > 0 {
-                amount = amount * marginfi_pda.kickback_percent_bpm as f64 / 1_000_000.0;
-                msg!("amount: {}", amount);
-            }
-            
-            msg!("amount: {}", amount);
-            let oracle = &mut ctx.accounts.oracle.load_mut()?;
-            let old_timestamp = oracle.last_borrowed_amount_timestamp;
-            
-            let time_diff = oracle.last_borrowed_amount_timestamp - old_timestamp;
-            msg!("time_diff: {}", time_diff);
-            let time_diff = time_diff as f64 / 60.0 / 60.0 / 24.0 / 365.0;
-            msg!("time_diff: {}", time_diff);
-            let rate = wsol_borrow_rate.powf(time_diff);
-            msg!("rate: {}", rate);
-            let amount = amount as f64 * rate as f64;
-            msg!("amount: {}", amount);
-            // / 0.535
-            let amount = amount as f64 / 0.535;
-            let amount = amount as u64;
-
-            
-            invoke_signed(
+                signer,
+            ))?;
+        } else {
+            staged(VaultError::Unstake, redeemed, || invoke_signed(
                 &spl_stake_pool::instruction::withdraw_sol(
-                    &spl_stake_pool::id(),
+                    &ctx.accounts.stake_pool_program.key(),
                     &ctx.accounts.stake_pool.key(),
                     &ctx.accounts.stake_pool_withdraw_authority.key(),
                     &ctx.accounts.marginfi_pda.key(),
@@ -1476,7 +1635,7 @@ impl Deposit<'_> {
                     &ctx.accounts.manager_fee_account.key(),
                     &ctx.accounts.pool_mint.key(),
                     &anchor_spl::token::ID,
-                    amount as u64,
+                    redeemed,
                 ),
                 &[
                     ctx.accounts.marginfi_pda.to_account_info(),
@@ -1494,58 +1653,134 @@ impl Deposit<'_> {
                     ctx.accounts.stake_program.to_account_info(),
                     ctx.accounts.rent.to_account_info(),
                 ],
-                &signer,
-            )
-            .unwrap();
+                signer,
+            ))?;
+        }
+        let unstaked = checked_sub(ctx.accounts.signer.lamports(), lamports_before)?;
+        msg!("unstaked: {}", unstaked);
+
+        // wrap the flash loan and its fee back up for the trailing flash_repay
+        if debt_share > 0 {
+            let reserve = Reserve::unpack(&ctx.accounts.marginfi_bank_wsol.try_borrow_data()?)?;
+            let (flash_fee, _) = reserve
+                .config
+                .fees
+                .calculate_flash_loan_fees(Decimal::from(debt_share))?;
+            let owed = checked_add(debt_share, flash_fee)?;
+            if owed > unstaked {
+                return Err(error!(USDY_USDC_ORACLEError::UnwindShortfall));
+            }
+            msg!("owed: {}", owed);
+            invoke(
+                &solana_program::system_instruction::transfer(
+                    &ctx.accounts.signer.key(),
+                    &user_wsol_account.key(),
+                    owed,
+                ),
+                &[
+                    ctx.accounts.signer.to_account_info(),
+                    user_wsol_account.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+            anchor_spl::token::sync_native(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                SyncNative {
+                    account: user_wsol_account,
+                },
+            ))?;
         }
-        let oracle = &mut ctx.accounts.oracle.load_mut()?;
-        oracle.last_borrowed_amount_timestamp = Clock::get()?.unix_timestamp;
+
+        let now = Clock::get()?;
+        let oracle = ctx.accounts.oracle.load()?;
+        emit!(Withdrawn {
+            vault: ctx.accounts.marginfi_pda.key(),
+            user: ctx.accounts.signer.key(),
+            jarezi_burned: amount,
+            lamports: unstaked,
+            debt_repaid: debt_share,
+            exchange_rate: share_price(nav, supply)?,
+            pool_price: ctx.accounts.collateral_pool.price(&oracle, now.unix_timestamp)?,
+            slot: now.slot,
+        });
         Ok(())
     }
     pub fn winner_winner_chickum_dinner_distribute(
         ctx: Context<Winner>,
-        amount: u64,
     ) -> anchor_lang::Result<()> {
-        let wsol_borrow_rate = ctx.accounts.oracle.load()?.wsol_borrow.mean as f64 // this is a 10^18 we want it as a f64 so we divide by 10^18
-        / 1_000_000_000.0;
+        ctx.accounts.marginfi_pda.check_not_paused(PAUSE_DISTRIBUTE)?;
+        let vault = ctx.accounts.marginfi_pda.key();
+        let mean = ctx.accounts.oracle.load()?.latest_mean(ctx.accounts.collateral_pool.oracle_symbol);
+        if ctx.accounts.marginfi_pda.trip_breaker(vault, mean, ctx.accounts.enclave_signer.key())? {
+            return Ok(());
+        }
+        // every payout needs its own draw, set_winner arms it and the payout below disarms it
+        if ctx.accounts.marginfi_pda.winner_token_account == Pubkey::default() {
+            return Err(error!(USDY_USDC_ORACLEError::WinnerNotSet));
+        }
+        let wsol_borrow_rate = ctx.accounts.oracle.load()?.wsol_borrow(Clock::get()?.unix_timestamp)?.mean;
+
+        let now = Clock::get()?.unix_timestamp;
+        let periods = ctx.accounts.marginfi_pda.prize_periods(now);
+        msg!("periods: {}", periods);
+        if periods == 0 {
+            return Err(error!(USDY_USDC_ORACLEError::PrizePeriodNotElapsed));
+        }
+        let prize_per_period = ctx.accounts.marginfi_pda.prize_per_period;
+        let amount = prize_per_period
+            .checked_mul(periods as u64)
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::MathOverflow))?;
+        let pool_tokens = amount;
+        let index_before = ctx.accounts.marginfi_pda.borrow_rate_index();
+        let elapsed = ctx.accounts.marginfi_pda.advance_prize_schedule(periods, wsol_borrow_rate)?;
+        msg!("elapsed: {}", elapsed);
 
         let mint_supply = ctx.accounts.jarezi_mint.supply;
         let marginfi_pda = ctx.accounts.marginfi_pda.clone();
         msg!("amount {}", amount);
-        // TODO: adjust calc for kickback
-        let kickback = marginfi_pda.kickback_percent_bpm as f64 / 1_000_000.0;
-        let rate = exchange_rate(
+        // the prize is priced off the whole position, as a deposit is: a looped vault keeps its
+        // pool tokens posted as collateral, not idle
+        let obligation = Obligation::unpack(&ctx.accounts.obligation_pubkey.try_borrow_data()?)?;
+        if obligation.owner != marginfi_pda.key() {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidObligation));
+        }
+        let reserve = Reserve::unpack(&ctx.accounts.marginfi_bank.try_borrow_data()?)?;
+        let pool_price = ctx.accounts.collateral_pool.price(&*ctx.accounts.oracle.load()?, now)?;
+        let nav = net_asset_value(
+            &obligation,
+            &ctx.accounts.marginfi_bank.key(),
+            &reserve,
             ctx.accounts.pool_token_receiver_account.amount,
-            mint_supply + amount ).unwrap().0.0.as_u64();
-        msg!("rate {}", rate);
-        let amount = amount as f64 * rate as f64 / 1_000_000_000.0;
-        msg!("amount {}", amount);
-        let amount = amount * (1.0 - kickback);
-        msg!("amount {}", amount);
-        let amount = amount as u64;
-
-        msg!("amount: {}", amount);
-        let oracle = &mut ctx.accounts.oracle.load_mut()?;
-        let old_timestamp = oracle.last_borrowed_amount_timestamp;
-        
-        let time_diff = oracle.last_borrowed_amount_timestamp - old_timestamp;
-        msg!("time_diff: {}", time_diff);
-        let time_diff = time_diff as f64 / 60.0 / 60.0 / 24.0 / 365.0;
-        msg!("time_diff: {}", time_diff);
-        let rate = wsol_borrow_rate.powf(time_diff);
-        msg!("rate: {}", rate);
-        let amount = amount as f64 * rate as f64;
+            pool_price,
+        )?;
+        msg!("nav: {}", to_amount(nav, Rounding::Down)?);
+        let value = pool_tokens_to_sol(Decimal::from(amount), pool_price)?;
+
+        // grow the prize by the borrow interest accrued over the periods it covers
+        let value = value
+            .try_mul(marginfi_pda.borrow_rate_index())?
+            .try_div(index_before)?;
+        // mints round down, the winner gets what is left after the kickback
+        let prize = prize_shares(value, nav, mint_supply)?;
+        // a vault without a kickback destination pays the whole prize to the winner
+        let kickback = if marginfi_pda.kickback_destination == Pubkey::default() {
+            0
+        } else {
+            to_amount(bpm_of(prize, marginfi_pda.kickback_percent_bpm)?, Rounding::Down)?
+        };
+        let amount = checked_sub(prize, kickback)?;
         msg!("amount: {}", amount);
-        
-        if amount > 0.0 // TODO: ? 
-        {
+        msg!("kickback: {}", kickback);
+
         let winner = ctx.accounts.marginfi_pda.thewinnerog;
         let signer: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(),
         &[marginfi_pda.bump]]];
 
+        if amount > 0
+        {
         // mint amount to actual_destination
 
-        anchor_spl::token_interface::mint_to(CpiContext::new_with_signer(
+        staged(VaultError::Mint, amount, || anchor_spl::token_interface::mint_to(CpiContext::new_with_signer(
             ctx.accounts.token_program_2022.to_account_info(),
             MintTo {
                 mint: ctx.accounts.jarezi_mint.to_account_info(),
@@ -1553,8 +1788,54 @@ impl Deposit<'_> {
                 authority: ctx.accounts.marginfi_pda.to_account_info(),
             },
             &signer,
-        ), amount as u64).unwrap();
-    }
+        ), amount))?;
+        }
+        if kickback > 0
+        {
+        let kickback_destination = match &ctx.accounts.kickback_destination {
+            Some(kickback_destination) => kickback_destination.to_account_info(),
+            None => return Err(error!(USDY_USDC_ORACLEError::MissingKickbackDestination)),
+        };
+        staged(VaultError::Mint, kickback, || anchor_spl::token_interface::mint_to(CpiContext::new_with_signer(
+            ctx.accounts.token_program_2022.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.jarezi_mint.to_account_info(),
+                to: kickback_destination,
+                authority: ctx.accounts.marginfi_pda.to_account_info(),
+            },
+            &signer,
+        ), kickback))?;
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        marginfi_pda.total_kickback = checked_add(marginfi_pda.total_kickback, kickback)?;
+        msg!("total_kickback: {}", marginfi_pda.total_kickback);
+        }
+        let fees = ctx.accounts.prize_pool.as_ref().map_or(0, |prize_pool| prize_pool.amount);
+        msg!("fees: {}", fees);
+        if let Some(prize_pool) = ctx.accounts.prize_pool.as_ref().filter(|_| fees > 0)
+        {
+        staged(VaultError::PrizePool, fees, || anchor_spl::token_interface::transfer_checked(CpiContext::new_with_signer(
+            ctx.accounts.token_program_2022.to_account_info(),
+            anchor_spl::token_interface::TransferChecked {
+                from: prize_pool.to_account_info(),
+                mint: ctx.accounts.jarezi_mint.to_account_info(),
+                to: ctx.accounts.actual_destination.to_account_info(),
+                authority: ctx.accounts.marginfi_pda.to_account_info(),
+            },
+            &signer,
+        ), fees, ctx.accounts.jarezi_mint.decimals))?;
+        }
+        ctx.accounts.marginfi_pda.winner_token_account = Pubkey::default();
+        emit!(PrizeDistributed {
+            vault: ctx.accounts.marginfi_pda.key(),
+            winner_token_account: ctx.accounts.actual_destination.key(),
+            periods,
+            pool_tokens,
+            prize: amount,
+            kickback,
+            fees,
+            exchange_rate: share_price(nav, checked_add(mint_supply, prize)?)?,
+            slot: Clock::get()?.slot,
+        });
         Ok(())
     }
 }
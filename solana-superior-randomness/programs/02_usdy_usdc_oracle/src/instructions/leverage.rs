@@ -0,0 +1,234 @@
+pub use crate::*;
+use solend_sdk::{
+    math::{Decimal, TryDiv, TryMul, TrySub},
+    state::Obligation,
+};
+
+pub const LEVERAGE_SEED: &[u8] = b"leverage";
+
+// All ratios are parts per million, same as MarginFiPda.kickback_percent_bpm
+pub const BPM: u64 = 1_000_000;
+
+#[account]
+#[derive(Default)]
+pub struct LeverageConfig {
+    pub bump: u8,
+    pub marginfi_pda: Pubkey,
+    /// Share of the staked collateral borrowed back as wSOL on each loop
+    pub target_ltv_bpm: u64,
+    /// Borrow/restake passes per deposit
+    pub loop_count: u8,
+    /// Stake pool deposit + Solend deposit fees assumed when sizing the collateral
+    pub stake_pool_fee_bpm: u64,
+    /// Haircut applied to jarezi burned on withdraw
+    pub withdraw_haircut_bpm: u64,
+    /// How far the pool tokens actually received may fall short of the expected amount
+    pub max_slippage_bpm: u64,
+    /// Looping stops once deposited value / equity reaches this
+    pub target_leverage_bpm: u64,
+    /// Lowest unhealthy_borrow_value / borrowed_value the obligation may be left at
+    pub min_health_factor_bpm: u64,
+}
+
+impl LeverageConfig {
+    /// wSOL borrowed against `collateral` pool tokens worth `pool_price` (lamports per pool
+    /// token, 1e9) on one loop, rounded down.
+    pub fn borrow_amount(&self, collateral: u64, pool_price: u64) -> anchor_lang::Result<u64> {
+        let lamports = pool_tokens_to_sol(Decimal::from(collateral), pool_price)?;
+        to_amount(lamports.try_mul(self.target_ltv_bpm)?.try_div(BPM)?, Rounding::Down)
+    }
+
+    /// Jarezi whose share is paid out after the withdraw haircut, rounded down.
+    pub fn after_withdraw_haircut(&self, amount: u64) -> anchor_lang::Result<u64> {
+        to_amount(less_bpm(Decimal::from(amount), self.withdraw_haircut_bpm)?, Rounding::Down)
+    }
+
+    /// Pool tokens expected for `amount` lamports at `pool_price` (lamports per pool token, 1e9).
+    pub fn stake_pool_tokens(&self, amount: u64, pool_price: u64) -> anchor_lang::Result<u64> {
+        let tokens = sol_to_pool_tokens(Decimal::from(amount), pool_price)?;
+        to_amount(less_bpm(tokens, self.stake_pool_fee_bpm)?, Rounding::Down)
+    }
+
+    /// Lowest amount of pool tokens accepted for an expected amount.
+    pub fn min_received(&self, expected: u64) -> anchor_lang::Result<u64> {
+        to_amount(less_bpm(Decimal::from(expected), self.max_slippage_bpm)?, Rounding::Up)
+    }
+
+    pub fn leverage_reached(&self, obligation: &Obligation) -> anchor_lang::Result<bool> {
+        if obligation.borrowed_value >= obligation.deposited_value {
+            return Ok(true);
+        }
+        let equity = obligation.deposited_value.try_sub(obligation.borrowed_value)?;
+        Ok(obligation.deposited_value.try_mul(BPM)? >= equity.try_mul(self.target_leverage_bpm)?)
+    }
+
+    pub fn health_ok(&self, obligation: &Obligation) -> anchor_lang::Result<bool> {
+        if obligation.borrowed_value == Decimal::zero() {
+            return Ok(true);
+        }
+        Ok(obligation.unhealthy_borrow_value.try_mul(BPM)?
+            >= obligation.borrowed_value.try_mul(self.min_health_factor_bpm)?)
+    }
+
+    /// Borrow value (quote currency) that can be added before the health-factor floor or
+    /// Solend's own allowed borrow value is hit.
+    pub fn borrow_headroom(&self, obligation: &Obligation) -> anchor_lang::Result<Decimal> {
+        let floor = obligation
+            .unhealthy_borrow_value
+            .try_mul(BPM)?
+            .try_div(self.min_health_factor_bpm)?;
+        let limit = floor.min(obligation.allowed_borrow_value);
+        if limit <= obligation.borrowed_value {
+            return Ok(Decimal::zero());
+        }
+        Ok(limit.try_sub(obligation.borrowed_value)?)
+    }
+
+    pub fn validate(&self) -> anchor_lang::Result<()> {
+        if self.target_ltv_bpm >= BPM
+            || self.loop_count == 0
+            || self.stake_pool_fee_bpm >= BPM
+            || self.withdraw_haircut_bpm >= BPM
+            || self.max_slippage_bpm >= BPM
+            || self.target_leverage_bpm < BPM
+            || self.min_health_factor_bpm < BPM
+        {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidLeverageConfig));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct UpdateLeverageConfig<'info> {
+    #[account(
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<LeverageConfig>(),
+        payer = authority,
+        seeds = [LEVERAGE_SEED, marginfi_pda.key().as_ref()],
+        bump
+    )]
+    pub leverage_config: Box<Account<'info, LeverageConfig>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateLeverageConfigParams {
+    pub target_ltv_bpm: u64,
+    pub loop_count: u8,
+    pub stake_pool_fee_bpm: u64,
+    pub withdraw_haircut_bpm: u64,
+    pub max_slippage_bpm: u64,
+    pub target_leverage_bpm: u64,
+    pub min_health_factor_bpm: u64,
+}
+
+impl UpdateLeverageConfig<'_> {
+    pub fn update_leverage_config(
+        ctx: Context<UpdateLeverageConfig>,
+        params: UpdateLeverageConfigParams,
+    ) -> anchor_lang::Result<()> {
+        let leverage_config = &mut ctx.accounts.leverage_config;
+        leverage_config.bump = ctx.bumps.leverage_config;
+        leverage_config.marginfi_pda = ctx.accounts.marginfi_pda.key();
+        leverage_config.target_ltv_bpm = params.target_ltv_bpm;
+        leverage_config.loop_count = params.loop_count;
+        leverage_config.stake_pool_fee_bpm = params.stake_pool_fee_bpm;
+        leverage_config.withdraw_haircut_bpm = params.withdraw_haircut_bpm;
+        leverage_config.max_slippage_bpm = params.max_slippage_bpm;
+        leverage_config.target_leverage_bpm = params.target_leverage_bpm;
+        leverage_config.min_health_factor_bpm = params.min_health_factor_bpm;
+        leverage_config.validate()?;
+
+        msg!("target_ltv_bpm: {}", leverage_config.target_ltv_bpm);
+        msg!("loop_count: {}", leverage_config.loop_count);
+        msg!("min_health_factor_bpm: {}", leverage_config.min_health_factor_bpm);
+        emit_config_changed(ctx.accounts.marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::LeverageConfig)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LeverageConfig {
+        LeverageConfig {
+            target_ltv_bpm: 600_000,
+            loop_count: 3,
+            stake_pool_fee_bpm: 1_000,
+            withdraw_haircut_bpm: 5_000,
+            max_slippage_bpm: 10_000,
+            target_leverage_bpm: 3_000_000,
+            min_health_factor_bpm: 1_200_000,
+            ..LeverageConfig::default()
+        }
+    }
+
+    fn obligation(deposited: u64, borrowed: u64, allowed: u64, unhealthy: u64) -> Obligation {
+        Obligation {
+            deposited_value: Decimal::from(deposited),
+            borrowed_value: Decimal::from(borrowed),
+            allowed_borrow_value: Decimal::from(allowed),
+            unhealthy_borrow_value: Decimal::from(unhealthy),
+            ..Obligation::default()
+        }
+    }
+
+    #[test]
+    fn borrow_amount_prices_the_collateral() {
+        let config = config();
+        // 1 pool token worth 1.1 SOL at 60% LTV
+        assert_eq!(config.borrow_amount(1_000_000_000, 1_100_000_000).unwrap(), 660_000_000);
+        assert_eq!(config.borrow_amount(1_000_000_000, PRICE_SCALE).unwrap(), 600_000_000);
+        // 1.8 lamports rounds down
+        assert_eq!(config.borrow_amount(3, PRICE_SCALE).unwrap(), 1);
+    }
+
+    #[test]
+    fn health_ok_at_the_floor() {
+        let config = config();
+        assert!(config.health_ok(&obligation(0, 0, 0, 0)).unwrap());
+        // 120 / 100 is exactly the 1.2x floor
+        assert!(config.health_ok(&obligation(200, 100, 150, 120)).unwrap());
+        assert!(!config.health_ok(&obligation(200, 101, 150, 120)).unwrap());
+    }
+
+    #[test]
+    fn borrow_headroom_takes_the_tighter_limit() {
+        let config = config();
+        // health floor at 120 / 1.2 = 100, Solend allows 90
+        assert_eq!(config.borrow_headroom(&obligation(200, 50, 90, 120)).unwrap(), Decimal::from(40u64));
+        // Solend allows 150, the health floor stops at 100
+        assert_eq!(config.borrow_headroom(&obligation(200, 50, 150, 120)).unwrap(), Decimal::from(50u64));
+        // already past a limit
+        assert_eq!(config.borrow_headroom(&obligation(200, 95, 90, 120)).unwrap(), Decimal::zero());
+    }
+
+    #[test]
+    fn leverage_reached_at_target() {
+        let config = config();
+        // 300 deposited on 100 equity is 3x
+        assert!(config.leverage_reached(&obligation(300, 200, 0, 0)).unwrap());
+        assert!(!config.leverage_reached(&obligation(300, 150, 0, 0)).unwrap());
+        assert!(config.leverage_reached(&obligation(300, 300, 0, 0)).unwrap());
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_ratios() {
+        assert!(config().validate().is_ok());
+        assert!(LeverageConfig::default().validate().is_err());
+        assert!(LeverageConfig { target_ltv_bpm: BPM, ..config() }.validate().is_err());
+        assert!(LeverageConfig { min_health_factor_bpm: BPM - 1, ..config() }.validate().is_err());
+        assert!(LeverageConfig { loop_count: 0, ..config() }.validate().is_err());
+    }
+}
@@ -0,0 +1,140 @@
+pub use crate::*;
+
+// Marinade isn't an SPL stake pool, SOL goes in through its `deposit` and comes back out
+// through `liquid_unstake`. A vault bound with PoolKind::Marinade passes Marinade's accounts
+// in the Deposit slots below; Marinade checks each of them against its State, which the
+// collateral pool pins.
+//
+// stake_pool                    -> State
+// pool_mint                     -> mSOL mint
+// stake_pool_withdraw_authority -> mSOL mint authority
+// reserve_stake_account         -> reserve PDA
+// manager_fee_account           -> liquidity pool mSOL leg
+// stake_pool_program            -> Marinade program
+
+pub const MARINADE_RESERVE_SEED: &[u8] = b"reserve";
+pub const MARINADE_MSOL_MINT_AUTHORITY_SEED: &[u8] = b"st_mint";
+pub const MARINADE_LIQ_POOL_SOL_LEG_SEED: &[u8] = b"liq_sol";
+pub const MARINADE_LIQ_POOL_MSOL_LEG_AUTHORITY_SEED: &[u8] = b"liq_st_sol_authority";
+
+/// Offsets into Marinade's State account, discriminator included.
+pub const MARINADE_TREASURY_MSOL_ACCOUNT_OFFSET: usize = 104;
+pub const MARINADE_LIQ_POOL_MSOL_LEG_OFFSET: usize = 420;
+pub const MARINADE_MSOL_PRICE_OFFSET: usize = 512;
+
+/// Marinade's mSOL price is lamports per mSOL shifted left by 32 bits.
+const MARINADE_PRICE_DENOMINATOR: u64 = 1 << 32;
+
+fn read_pubkey(state: &[u8], offset: usize) -> Option<Pubkey> {
+    state
+        .get(offset..offset + 32)
+        .map(|bytes| Pubkey::new_from_array(bytes.try_into().unwrap()))
+}
+
+pub fn marinade_treasury_msol_account(state: &[u8]) -> Option<Pubkey> {
+    read_pubkey(state, MARINADE_TREASURY_MSOL_ACCOUNT_OFFSET)
+}
+
+pub fn marinade_liq_pool_msol_leg(state: &[u8]) -> Option<Pubkey> {
+    read_pubkey(state, MARINADE_LIQ_POOL_MSOL_LEG_OFFSET)
+}
+
+/// Lamports per whole mSOL, scaled by PRICE_SCALE and rounded down, read from Marinade's State.
+pub fn marinade_msol_price(state: &[u8]) -> anchor_lang::Result<u64> {
+    let raw = state
+        .get(MARINADE_MSOL_PRICE_OFFSET..MARINADE_MSOL_PRICE_OFFSET + 8)
+        .ok_or_else(|| error!(USDY_USDC_ORACLEError::InvalidCollateralPool))?;
+    let raw = u64::from_le_bytes(raw.try_into().unwrap());
+    pool_price(raw, MARINADE_PRICE_DENOMINATOR)
+}
+
+pub fn marinade_address(program_id: &Pubkey, state: &Pubkey, seed: &[u8]) -> Pubkey {
+    Pubkey::find_program_address(&[state.as_ref(), seed], program_id).0
+}
+
+/// Marinade `deposit`: stakes `lamports` from `transfer_from` and mints the mSOL to `mint_to`.
+#[allow(clippy::too_many_arguments)]
+pub fn marinade_deposit(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    msol_mint: &Pubkey,
+    liq_pool_msol_leg: &Pubkey,
+    transfer_from: &Pubkey,
+    mint_to: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    let mut data = get_ixn_discriminator("deposit").to_vec();
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*state, false),
+            AccountMeta::new(*msol_mint, false),
+            AccountMeta::new(marinade_address(program_id, state, MARINADE_LIQ_POOL_SOL_LEG_SEED), false),
+            AccountMeta::new(*liq_pool_msol_leg, false),
+            AccountMeta::new_readonly(
+                marinade_address(program_id, state, MARINADE_LIQ_POOL_MSOL_LEG_AUTHORITY_SEED),
+                false,
+            ),
+            AccountMeta::new(marinade_address(program_id, state, MARINADE_RESERVE_SEED), false),
+            AccountMeta::new(*transfer_from, true),
+            AccountMeta::new(*mint_to, false),
+            AccountMeta::new_readonly(
+                marinade_address(program_id, state, MARINADE_MSOL_MINT_AUTHORITY_SEED),
+                false,
+            ),
+            AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+        ],
+        data,
+    }
+}
+
+/// Marinade `liquid_unstake`: swaps `msol_amount` from `get_msol_from` for SOL through the
+/// liquidity pool and sends it to `transfer_sol_to`, less the pool's fee.
+#[allow(clippy::too_many_arguments)]
+pub fn marinade_liquid_unstake(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    msol_mint: &Pubkey,
+    liq_pool_msol_leg: &Pubkey,
+    treasury_msol_account: &Pubkey,
+    get_msol_from: &Pubkey,
+    get_msol_from_authority: &Pubkey,
+    transfer_sol_to: &Pubkey,
+    msol_amount: u64,
+) -> Instruction {
+    let mut data = get_ixn_discriminator("liquid_unstake").to_vec();
+    data.extend_from_slice(&msol_amount.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*state, false),
+            AccountMeta::new(*msol_mint, false),
+            AccountMeta::new(marinade_address(program_id, state, MARINADE_LIQ_POOL_SOL_LEG_SEED), false),
+            AccountMeta::new(*liq_pool_msol_leg, false),
+            AccountMeta::new(*treasury_msol_account, false),
+            AccountMeta::new(*get_msol_from, false),
+            AccountMeta::new_readonly(*get_msol_from_authority, true),
+            AccountMeta::new(*transfer_sol_to, false),
+            AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+        ],
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msol_price() {
+        let mut state = vec![0u8; 1024];
+        // 1.25 SOL per mSOL
+        let raw = 5u64 << 30;
+        state[MARINADE_MSOL_PRICE_OFFSET..MARINADE_MSOL_PRICE_OFFSET + 8].copy_from_slice(&raw.to_le_bytes());
+        assert_eq!(marinade_msol_price(&state).unwrap(), 1_250_000_000);
+        assert!(marinade_msol_price(&state[..MARINADE_MSOL_PRICE_OFFSET]).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+pub use crate::*;
+
+// Accounts created before a field was appended are shorter than their struct, so Anchor can't
+// load them. migrate_program and migrate_vault grow them to the current size in place, paid by
+// the authority; the new bytes are zeroed, which every appended field reads as unset. Both are
+// no-ops on accounts that are already current.
+//
+// MyOracleState was reshaped rather than appended to: the three fixed OracleData rows became
+// the feed slots. Its feeds are reset and the symbols initialize registers are registered
+// again; the next refresh_oracles push fills them.
+
+/// Offsets of the pre-feed-slot MyOracleState fields carried over, discriminator included.
+const LEGACY_ORACLE_LAST_BORROWED_AMOUNT: usize = 8 + 1 + 3 * 32;
+const LEGACY_ORACLE_LAST_BORROWED_AMOUNT_TIMESTAMP: usize = LEGACY_ORACLE_LAST_BORROWED_AMOUNT + 8;
+
+/// Grows `account` to `len` bytes, topping up its rent from `payer`. Returns false if it was
+/// already that long.
+pub(crate) fn grow<'info>(
+    account: &AccountInfo<'info>,
+    len: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> anchor_lang::Result<bool> {
+    if account.data_len() >= len {
+        return Ok(false);
+    }
+    let top_up = Rent::get()?.minimum_balance(len).saturating_sub(account.lamports());
+    if top_up > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    msg!("{}: {} -> {} bytes", account.key(), account.data_len(), len);
+    account.realloc(len, true)?;
+    Ok(true)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> anchor_lang::Result<u64> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))
+}
+
+#[derive(Accounts)]
+pub struct MigrateProgram<'info> {
+    /// CHECK: may be too short to load, the authority is checked once it is grown
+    #[account(mut, seeds = [PROGRAM_SEED], bump)]
+    pub program: AccountInfo<'info>,
+    /// CHECK: may be too short to load, rebuilt by the handler
+    #[account(mut, seeds = [ORACLE_SEED], bump)]
+    pub oracle: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl MigrateProgram<'_> {
+    pub fn migrate_program(ctx: Context<MigrateProgram>) -> anchor_lang::Result<()> {
+        let payer = ctx.accounts.authority.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+
+        let program_info = ctx.accounts.program.to_account_info();
+        let program_len = 8 + std::mem::size_of::<MyProgramState>();
+        grow(&program_info, program_len, &payer, &system_program)?;
+        let authority = {
+            let data = program_info.try_borrow_data()?;
+            bytemuck::from_bytes::<MyProgramState>(&data[8..program_len]).authority
+        };
+        if authority != ctx.accounts.authority.key() {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidAuthority));
+        }
+
+        let oracle_info = ctx.accounts.oracle.to_account_info();
+        let (last_borrowed_amount, last_borrowed_amount_timestamp) = {
+            let data = oracle_info.try_borrow_data()?;
+            (
+                read_u64(&data, LEGACY_ORACLE_LAST_BORROWED_AMOUNT)?,
+                read_u64(&data, LEGACY_ORACLE_LAST_BORROWED_AMOUNT_TIMESTAMP)? as i64,
+            )
+        };
+        let oracle_len = 8 + std::mem::size_of::<MyOracleState>();
+        if grow(&oracle_info, oracle_len, &payer, &system_program)? {
+            let mut data = oracle_info.try_borrow_mut_data()?;
+            data[8..].fill(0);
+            let oracle = bytemuck::from_bytes_mut::<MyOracleState>(&mut data[8..oracle_len]);
+            oracle.bump = ctx.bumps.oracle;
+            oracle.last_borrowed_amount = last_borrowed_amount;
+            oracle.last_borrowed_amount_timestamp = last_borrowed_amount_timestamp;
+            for symbol in DEFAULT_ORACLE_SYMBOLS {
+                oracle.register(symbol)?;
+            }
+        }
+        emit_config_changed(Pubkey::default(), ctx.accounts.authority.key(), ConfigKind::Migration)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    /// CHECK: may be too short to load, its seeds and authority are checked once it is grown
+    #[account(mut, owner = crate::ID)]
+    pub marginfi_pda: AccountInfo<'info>,
+    /// CHECK: grown after the vault checks out
+    #[account(mut, seeds = [COLLATERAL_POOL_SEED, marginfi_pda.key().as_ref()], bump)]
+    pub collateral_pool: Option<AccountInfo<'info>>,
+    /// CHECK: grown after the vault checks out
+    #[account(mut, seeds = [AUTO_BUY_SEED, marginfi_pda.key().as_ref()], bump)]
+    pub auto_buy_config: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl MigrateVault<'_> {
+    /// Also grows the vault's collateral pool and auto buy config when passed. A grown auto
+    /// buy config has no game accounts pinned, so auto_buy fails until
+    /// update_auto_buy_config is run again.
+    pub fn migrate_vault(ctx: Context<MigrateVault>) -> anchor_lang::Result<()> {
+        let payer = ctx.accounts.authority.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+
+        let marginfi_pda_info = ctx.accounts.marginfi_pda.to_account_info();
+        grow(&marginfi_pda_info, 8 + std::mem::size_of::<MarginFiPda>(), &payer, &system_program)?;
+        let marginfi_pda = MarginFiPda::try_deserialize(&mut &marginfi_pda_info.try_borrow_data()?[..])?;
+        let expected = Pubkey::create_program_address(
+            &[SEED_PREFIX, marginfi_pda.thewinnerog.as_ref(), &[marginfi_pda.bump]],
+            ctx.program_id,
+        )
+        .map_err(|_| error!(anchor_lang::error::ErrorCode::ConstraintSeeds))?;
+        if expected != marginfi_pda_info.key() {
+            return Err(error!(anchor_lang::error::ErrorCode::ConstraintSeeds));
+        }
+        if marginfi_pda.authority != ctx.accounts.authority.key() {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidAuthority));
+        }
+
+        if let Some(collateral_pool) = &ctx.accounts.collateral_pool {
+            grow(collateral_pool, 8 + std::mem::size_of::<CollateralPool>(), &payer, &system_program)?;
+        }
+        if let Some(auto_buy_config) = &ctx.accounts.auto_buy_config {
+            grow(auto_buy_config, 8 + std::mem::size_of::<AutoBuyConfig>(), &payer, &system_program)?;
+        }
+        emit_config_changed(marginfi_pda_info.key(), ctx.accounts.authority.key(), ConfigKind::Migration)?;
+
+        Ok(())
+    }
+}
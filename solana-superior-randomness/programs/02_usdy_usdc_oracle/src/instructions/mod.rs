@@ -1,2 +1,22 @@
 pub mod deposit;
 pub use deposit::*;
+pub mod randomness;
+pub use randomness::*;
+pub mod leverage;
+pub use leverage::*;
+pub mod prize;
+pub use prize::*;
+pub mod auto_buy;
+pub use auto_buy::*;
+pub mod collateral;
+pub use collateral::*;
+pub mod marinade;
+pub use marinade::*;
+pub mod authority;
+pub use authority::*;
+pub mod pause;
+pub use pause::*;
+pub mod rate_limit;
+pub use rate_limit::*;
+pub mod migrate;
+pub use migrate::*;
@@ -0,0 +1,305 @@
+pub use crate::*;
+
+pub const PAUSE_DEPOSIT: u8 = 1 << 0;
+pub const PAUSE_WITHDRAW: u8 = 1 << 1;
+pub const PAUSE_DISTRIBUTE: u8 = 1 << 2;
+pub const PAUSE_ORACLE_REFRESH: u8 = 1 << 3;
+/// Flags a vault can carry, oracle refresh is shared by every vault and paused program-wide
+pub const PAUSE_VAULT: u8 = PAUSE_DEPOSIT | PAUSE_WITHDRAW | PAUSE_DISTRIBUTE;
+
+/// What a vault's circuit breaker pauses. Oracle refresh keeps running so the feed can be
+/// watched settling before the authority unpauses.
+pub const PAUSE_ON_TRIP: u8 = PAUSE_VAULT;
+
+/// Fails with the flag's own error while `flag` is set in `paused_flags`.
+fn check_flag(paused_flags: u8, flag: u8) -> anchor_lang::Result<()> {
+    if paused_flags & flag == 0 {
+        return Ok(());
+    }
+    Err(match flag {
+        PAUSE_DEPOSIT => error!(USDY_USDC_ORACLEError::DepositsPaused),
+        PAUSE_WITHDRAW => error!(USDY_USDC_ORACLEError::WithdrawalsPaused),
+        PAUSE_DISTRIBUTE => error!(USDY_USDC_ORACLEError::DistributionPaused),
+        _ => error!(USDY_USDC_ORACLEError::OracleRefreshPaused),
+    })
+}
+
+/// The authority sets the flags outright. The guardian can only add flags, so a leaked
+/// guardian key can't reopen what the authority paused.
+fn next_flags(current: u8, requested: u8, allowed: u8, by_authority: bool) -> anchor_lang::Result<u8> {
+    if requested & !allowed != 0 {
+        return Err(error!(USDY_USDC_ORACLEError::InvalidPausedFlags));
+    }
+    if !by_authority && requested & current != current {
+        return Err(error!(USDY_USDC_ORACLEError::InvalidAuthority));
+    }
+    Ok(requested)
+}
+
+impl MyProgramState {
+    pub fn check_not_paused(&self, flag: u8) -> anchor_lang::Result<()> {
+        check_flag(self.paused_flags, flag)
+    }
+}
+
+impl MarginFiPda {
+    pub fn check_not_paused(&self, flag: u8) -> anchor_lang::Result<()> {
+        check_flag(self.paused_flags, flag)
+    }
+
+    /// Whether `data` moved the vault's collateral feed further from `previous` than its
+    /// circuit breaker allows.
+    pub fn trips_breaker(&self, data: &OracleData, previous: u64) -> bool {
+        self.trip_bpm > 0 && previous != 0 && data.deviates_from(previous, self.trip_bpm)
+    }
+
+    /// Checks `mean`, the collateral feed's latest push, against the mean the breaker last ran
+    /// against and keeps it for the next check. A move past trip_bpm pauses the vault.
+    pub fn check_breaker(&mut self, mean: u64) -> bool {
+        let previous = std::mem::replace(&mut self.breaker_mean, mean);
+        let tripped = self.trip_bpm > 0 && previous != 0 && deviates(mean, previous, self.trip_bpm);
+        if tripped {
+            self.paused_flags |= PAUSE_ON_TRIP;
+        }
+        tripped
+    }
+
+    /// Runs the breaker ahead of deposit, withdraw and distribute, so it covers every push
+    /// whether or not the pusher passed this vault to refresh_oracles. True once it has paused
+    /// the vault: the caller skips its instruction and returns Ok, an error would roll the
+    /// pause back.
+    pub fn trip_breaker(&mut self, vault: Pubkey, mean: u64, signer: Pubkey) -> anchor_lang::Result<bool> {
+        if !self.check_breaker(mean) {
+            return Ok(false);
+        }
+        msg!("collateral feed moved to {}, pausing {}", mean, vault);
+        emit_config_changed(vault, signer, ConfigKind::Paused)?;
+        Ok(true)
+    }
+}
+
+/// Runs each vault's circuit breaker against the rows refresh_oracles just saved. `vaults`
+/// are (vault, collateral pool) pairs; `previous` is each row's latest mean before the push.
+/// A tripped vault is paused, the rows stay saved either way so the next push compares
+/// against the new price. Vaults left out here are caught by their next deposit, withdraw or
+/// distribute, see `MarginFiPda::trip_breaker`.
+pub fn trip_breakers(
+    vaults: &[AccountInfo],
+    rows: &[OracleDataWithTradingSymbol],
+    previous: &[u64],
+    signer: Pubkey,
+) -> anchor_lang::Result<()> {
+    if vaults.len() % 2 != 0 {
+        return Err(error!(USDY_USDC_ORACLEError::InvalidCollateralPool));
+    }
+    for pair in vaults.chunks(2) {
+        let (vault, pool) = (&pair[0], &pair[1]);
+        if *vault.owner != crate::ID || *pool.owner != crate::ID || !vault.is_writable {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidCollateralPool));
+        }
+        let mut marginfi_pda = MarginFiPda::try_deserialize(&mut &vault.try_borrow_data()?[..])?;
+        let collateral_pool = CollateralPool::try_deserialize(&mut &pool.try_borrow_data()?[..])?;
+        if collateral_pool.marginfi_pda != vault.key() {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidCollateralPool));
+        }
+        let symbol = collateral_pool.oracle_symbol;
+        let tripped = rows
+            .iter()
+            .zip(previous)
+            .filter(|(row, _)| row.symbol == symbol)
+            .any(|(row, previous)| marginfi_pda.trips_breaker(&row.data.into(), *previous));
+        if !tripped || marginfi_pda.paused_flags & PAUSE_ON_TRIP == PAUSE_ON_TRIP {
+            continue;
+        }
+        msg!("{:?} tripped {}, pausing", symbol, vault.key());
+        marginfi_pda.paused_flags |= PAUSE_ON_TRIP;
+        marginfi_pda.try_serialize(&mut &mut vault.try_borrow_mut_data()?[..])?;
+        emit_config_changed(vault.key(), signer, ConfigKind::Paused)?;
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(mut,
+        constraint = marginfi_pda.authority == signer.key()
+            || marginfi_pda.guardian == signer.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetOraclePaused<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_SEED],
+        bump = program.load()?.bump,
+        constraint = program.load()?.authority == signer.key()
+            || program.load()?.guardian == signer.key() @ USDY_USDC_ORACLEError::InvalidAuthority
+    )]
+    pub program: AccountLoader<'info, MyProgramState>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCircuitBreaker<'info> {
+    #[account(mut,
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOracleGuardian<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_SEED],
+        bump = program.load()?.bump,
+        has_one = authority @ USDY_USDC_ORACLEError::InvalidAuthority
+    )]
+    pub program: AccountLoader<'info, MyProgramState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateCircuitBreakerParams {
+    /// Can pause the vault but not unpause it, Pubkey::default() disables it
+    pub guardian: Pubkey,
+    /// Move of the vault's collateral feed between pushes that pauses the vault, parts per
+    /// million, 0 disables it
+    pub trip_bpm: u64,
+}
+
+impl SetPaused<'_> {
+    pub fn set_paused(ctx: Context<SetPaused>, paused_flags: u8) -> anchor_lang::Result<()> {
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        let by_authority = marginfi_pda.authority == ctx.accounts.signer.key();
+        marginfi_pda.paused_flags = next_flags(marginfi_pda.paused_flags, paused_flags, PAUSE_VAULT, by_authority)?;
+        if by_authority {
+            // the feed may have settled anywhere, the next check starts from there
+            marginfi_pda.breaker_mean = 0;
+        }
+        msg!("paused_flags: {:#06b}", paused_flags);
+        emit_config_changed(marginfi_pda.key(), ctx.accounts.signer.key(), ConfigKind::Paused)?;
+
+        Ok(())
+    }
+}
+
+impl SetOraclePaused<'_> {
+    pub fn set_oracle_paused(ctx: Context<SetOraclePaused>, paused: bool) -> anchor_lang::Result<()> {
+        let program = &mut ctx.accounts.program.load_mut()?;
+        let by_authority = program.authority == ctx.accounts.signer.key();
+        let requested = if paused { PAUSE_ORACLE_REFRESH } else { 0 };
+        program.paused_flags = next_flags(program.paused_flags, requested, PAUSE_ORACLE_REFRESH, by_authority)?;
+        msg!("oracle refresh paused: {}", paused);
+        emit_config_changed(Pubkey::default(), ctx.accounts.signer.key(), ConfigKind::Paused)?;
+
+        Ok(())
+    }
+}
+
+impl UpdateCircuitBreaker<'_> {
+    pub fn update_circuit_breaker(
+        ctx: Context<UpdateCircuitBreaker>,
+        params: UpdateCircuitBreakerParams,
+    ) -> anchor_lang::Result<()> {
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        marginfi_pda.guardian = params.guardian;
+        marginfi_pda.trip_bpm = params.trip_bpm;
+        msg!("guardian: {}", params.guardian);
+        msg!("trip_bpm: {}", params.trip_bpm);
+        emit_config_changed(marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::CircuitBreaker)?;
+
+        Ok(())
+    }
+}
+
+impl UpdateOracleGuardian<'_> {
+    /// `guardian` can pause oracle refresh but not unpause it, Pubkey::default() disables it.
+    pub fn update_oracle_guardian(ctx: Context<UpdateOracleGuardian>, guardian: Pubkey) -> anchor_lang::Result<()> {
+        let program = &mut ctx.accounts.program.load_mut()?;
+        program.guardian = guardian;
+        msg!("guardian: {}", guardian);
+        emit_config_changed(Pubkey::default(), ctx.accounts.authority.key(), ConfigKind::CircuitBreaker)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solend_sdk::state::RATE_LIMITER_LEN;
+
+    #[test]
+    fn guardian_can_only_add_flags() {
+        assert_eq!(next_flags(PAUSE_DEPOSIT, 0, PAUSE_VAULT, true).unwrap(), 0);
+        assert_eq!(next_flags(PAUSE_DEPOSIT, PAUSE_DEPOSIT | PAUSE_WITHDRAW, PAUSE_VAULT, false).unwrap(), 3);
+        assert!(next_flags(PAUSE_DEPOSIT, PAUSE_WITHDRAW, PAUSE_VAULT, false).is_err());
+        assert!(next_flags(0, PAUSE_ORACLE_REFRESH, PAUSE_VAULT, true).is_err());
+    }
+
+    fn vault(trip_bpm: u64) -> MarginFiPda {
+        MarginFiPda {
+            bump: 0,
+            authority: Pubkey::default(),
+            kickback_percent_bpm: 0,
+            winner_winner_chickum_dinner: Pubkey::default(),
+            seeded_seed: String::new(),
+            thewinnerog: Pubkey::default(),
+            last_draw: 0,
+            snapshot_root: [0; 32],
+            snapshot_total_weight: 0,
+            winner_token_account: Pubkey::default(),
+            last_distribution_ts: 0,
+            borrow_rate_index: 0,
+            prize_period_secs: 0,
+            prize_per_period: 0,
+            kickback_destination: Pubkey::default(),
+            total_kickback: 0,
+            pending_authority: Pubkey::default(),
+            inflow_limiter: [0; RATE_LIMITER_LEN],
+            outflow_limiter: [0; RATE_LIMITER_LEN],
+            guardian: Pubkey::default(),
+            trip_bpm,
+            paused_flags: 0,
+            breaker_mean: 0,
+        }
+    }
+
+    #[test]
+    fn breaker_trips_on_a_move_since_the_vault_last_checked() {
+        // 5%
+        let mut vault = vault(50_000);
+        // the first check only records the mean
+        assert!(!vault.check_breaker(1_000_000_000));
+        assert!(!vault.check_breaker(1_040_000_000));
+        assert_eq!(vault.paused_flags, 0);
+        // pushes the pusher never passed to refresh_oracles still count: 1.04 -> 0.98 is -5.8%
+        assert!(vault.check_breaker(980_000_000));
+        assert_eq!(vault.paused_flags, PAUSE_ON_TRIP);
+        assert_eq!(vault.breaker_mean, 980_000_000);
+    }
+
+    #[test]
+    fn disabled_breaker_never_trips() {
+        let mut vault = vault(0);
+        assert!(!vault.check_breaker(1_000_000_000));
+        assert!(!vault.check_breaker(1));
+        assert_eq!(vault.paused_flags, 0);
+    }
+
+    #[test]
+    fn flags_fail_with_their_own_error() {
+        assert!(check_flag(PAUSE_WITHDRAW, PAUSE_DEPOSIT).is_ok());
+        let err = check_flag(PAUSE_ON_TRIP, PAUSE_WITHDRAW).unwrap_err();
+        assert_eq!(err, error!(USDY_USDC_ORACLEError::WithdrawalsPaused));
+    }
+}
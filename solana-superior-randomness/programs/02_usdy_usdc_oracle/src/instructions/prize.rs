@@ -0,0 +1,229 @@
+pub use crate::*;
+use anchor_spl::{associated_token::{get_associated_token_address_with_program_id, AssociatedToken}, token_interface::Token2022};
+use solend_sdk::math::{Decimal, TryMul};
+use spl_token_2022::extension::{
+    transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+    BaseStateWithExtensions, StateWithExtensions,
+};
+
+/// The vault's jarezi account collecting harvested transfer fees, paid out with the next prize.
+pub fn prize_pool_address(marginfi_pda: &Pubkey, jarezi_mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(marginfi_pda, jarezi_mint, &Token2022::id())
+}
+
+/// Jarezi in a token account plus the transfer fees withheld in it, 0 for an account that
+/// doesn't exist yet. Summed over every account and the mint's withheld fees this is the supply.
+pub fn gross_jarezi_balance(data: &[u8]) -> anchor_lang::Result<u64> {
+    if data.is_empty() {
+        return Ok(0);
+    }
+    let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(data)?;
+    let withheld = state
+        .get_extension::<TransferFeeAmount>()
+        .map(|fee| u64::from(fee.withheld_amount))
+        .unwrap_or(0);
+    checked_add(state.base.amount, withheld)
+}
+
+/// Transfer fees harvested into the jarezi mint and not yet withdrawn to the prize pool.
+pub fn mint_withheld_fees(data: &[u8]) -> anchor_lang::Result<u64> {
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(data)?;
+    Ok(u64::from(state.get_extension::<TransferFeeConfig>()?.withheld_amount))
+}
+
+#[derive(Accounts)]
+pub struct UpdatePrizeSchedule<'info> {
+    #[account(mut,
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct UpdatePrizeScheduleParams {
+    pub prize_period_secs: i64,
+    /// Pool tokens paid out per elapsed period, before interest and kickback
+    pub prize_per_period: u64,
+}
+
+#[derive(Accounts)]
+pub struct SetKickback<'info> {
+    #[account(mut,
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(
+        mint::authority = marginfi_pda,
+    )]
+    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    // a hydra fanout holding account or a treasury, anything that holds jarezi
+    #[account(
+        token::mint = jarezi_mint,
+    )]
+    pub kickback_destination: Box<InterfaceAccount<'info, anchor_spl::token_interface::TokenAccount>>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct SetKickbackParams {
+    pub kickback_percent_bpm: u64,
+}
+
+#[derive(Accounts)]
+pub struct HarvestWithheldFees<'info> {
+    #[account(
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(mut,
+        mint::authority = marginfi_pda,
+        mint::token_program = token_program_2022,
+    )]
+    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    /// CHECK: created if missing, address is checked
+    #[account(mut,
+        address = prize_pool_address(&marginfi_pda.key(), &jarezi_mint.key()),
+    )]
+    pub prize_pool: AccountInfo<'info>,
+    // anyone can crank the harvest, they only pay for the prize pool account the first time
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub token_program_2022: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl MarginFiPda {
+    pub fn borrow_rate_index(&self) -> Decimal {
+        if self.borrow_rate_index == 0 {
+            return Decimal::one();
+        }
+        Decimal::from_scaled_val(self.borrow_rate_index)
+    }
+
+    /// Whole prize periods elapsed since the last distribution.
+    pub fn prize_periods(&self, now: i64) -> i64 {
+        if self.prize_period_secs <= 0 || now <= self.last_distribution_ts {
+            return 0;
+        }
+        (now - self.last_distribution_ts) / self.prize_period_secs
+    }
+
+    /// Advances the schedule and the borrow-rate index by `periods`, returning the seconds
+    /// covered. The schedule moves in whole periods so draws don't drift later over time.
+    pub fn advance_prize_schedule(&mut self, periods: i64, wsol_borrow_rate: u64) -> anchor_lang::Result<i64> {
+        let elapsed = periods
+            .checked_mul(self.prize_period_secs)
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::MathOverflow))?;
+        let index = self
+            .borrow_rate_index()
+            .try_mul(compounded_interest(wsol_borrow_rate, elapsed)?)?;
+        self.borrow_rate_index = index.to_scaled_val()?;
+        self.last_distribution_ts += elapsed;
+        Ok(elapsed)
+    }
+}
+
+impl UpdatePrizeSchedule<'_> {
+    pub fn update_prize_schedule(
+        ctx: Context<UpdatePrizeSchedule>,
+        params: UpdatePrizeScheduleParams,
+    ) -> anchor_lang::Result<()> {
+        if params.prize_period_secs <= 0 {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidPrizeSchedule));
+        }
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        marginfi_pda.prize_period_secs = params.prize_period_secs;
+        marginfi_pda.prize_per_period = params.prize_per_period;
+        if marginfi_pda.last_distribution_ts == 0 {
+            // the first period starts when the schedule is set
+            marginfi_pda.last_distribution_ts = Clock::get()?.unix_timestamp;
+        }
+        msg!("prize_period_secs: {}", params.prize_period_secs);
+        msg!("prize_per_period: {}", params.prize_per_period);
+        emit_config_changed(ctx.accounts.marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::PrizeSchedule)?;
+
+        Ok(())
+    }
+}
+
+impl SetKickback<'_> {
+    pub fn set_kickback(ctx: Context<SetKickback>, params: SetKickbackParams) -> anchor_lang::Result<()> {
+        if params.kickback_percent_bpm > BPM {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidKickback));
+        }
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        marginfi_pda.kickback_percent_bpm = params.kickback_percent_bpm;
+        marginfi_pda.kickback_destination = ctx.accounts.kickback_destination.key();
+        msg!("kickback_percent_bpm: {}", params.kickback_percent_bpm);
+        msg!("kickback_destination: {}", marginfi_pda.kickback_destination);
+        emit_config_changed(ctx.accounts.marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::Kickback)?;
+
+        Ok(())
+    }
+}
+
+impl<'info> HarvestWithheldFees<'info> {
+    /// Moves the fees withheld in the jarezi accounts passed as remaining accounts into the
+    /// mint, then withdraws everything the mint holds to the prize pool.
+    pub fn harvest_withheld_fees(
+        ctx: Context<'_, '_, '_, 'info, HarvestWithheldFees<'info>>,
+    ) -> anchor_lang::Result<()> {
+        let mint = ctx.accounts.jarezi_mint.to_account_info();
+        let token_program_2022 = ctx.accounts.token_program_2022.key();
+        anchor_spl::associated_token::create_idempotent(CpiContext::new(
+            ctx.accounts.associated_token_program.to_account_info(),
+            anchor_spl::associated_token::Create {
+                payer: ctx.accounts.payer.to_account_info(),
+                associated_token: ctx.accounts.prize_pool.to_account_info(),
+                authority: ctx.accounts.marginfi_pda.to_account_info(),
+                mint: mint.clone(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                token_program: ctx.accounts.token_program_2022.to_account_info(),
+            },
+        ))?;
+
+        if !ctx.remaining_accounts.is_empty() {
+            let sources: Vec<&Pubkey> = ctx.remaining_accounts.iter().map(|a| a.key).collect();
+            let mut accounts = vec![mint.clone()];
+            accounts.extend(ctx.remaining_accounts.iter().cloned());
+            staged(VaultError::HarvestFees, 0, || invoke(
+                &spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+                    &token_program_2022,
+                    &mint.key(),
+                    &sources,
+                )?,
+                &accounts,
+            ))?;
+        }
+
+        let withheld = mint_withheld_fees(&mint.try_borrow_data()?)?;
+        msg!("withheld: {}", withheld);
+        if withheld == 0 {
+            return Ok(());
+        }
+
+        let marginfi_pda = &ctx.accounts.marginfi_pda;
+        let winner = marginfi_pda.thewinnerog;
+        let signer: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(), &[marginfi_pda.bump]]];
+        staged(VaultError::HarvestFees, withheld, || invoke_signed(
+            &spl_token_2022::extension::transfer_fee::instruction::withdraw_withheld_tokens_from_mint(
+                &token_program_2022,
+                &mint.key(),
+                &ctx.accounts.prize_pool.key(),
+                &marginfi_pda.key(),
+                &[],
+            )?,
+            &[mint.clone(), ctx.accounts.prize_pool.to_account_info(), marginfi_pda.to_account_info()],
+            signer,
+        ))?;
+
+        Ok(())
+    }
+}
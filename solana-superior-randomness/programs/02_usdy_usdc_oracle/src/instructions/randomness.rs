@@ -0,0 +1,539 @@
+pub use crate::*;
+use anchor_lang::solana_program::{hash::hashv, sysvar::slot_hashes};
+use anchor_lang::Discriminator;
+use spl_token_2022::extension::StateWithExtensions;
+
+#[derive(Accounts)]
+pub struct RequestRandomness<'info> {
+    #[account(
+        constraint = marginfi_pda.authority == authority.key(),
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(
+        constraint = marginfi_pda.key() == marginfi_pda_switchboard.marginfi_pda,
+        seeds = [SEED_PREFIX, marginfi_pda.key().as_ref()],
+        bump
+    )]
+    pub marginfi_pda_switchboard: Box<Account<'info, MarginFiPdaSwitchboard>>,
+    #[account(init,
+        space = 8 + std::mem::size_of::<RequestAccountData>(),
+        payer = authority,
+        seeds = [REQUEST_SEED, marginfi_pda.key().as_ref()],
+        bump
+    )]
+    pub request: AccountLoader<'info, RequestAccountData>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SeedRandomness<'info> {
+    #[account(
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(mut,
+        constraint = request.load()?.switchboard_request == switchboard_function.key()
+            @ USDY_USDC_ORACLEError::IncorrectSwitchboardFunction,
+        seeds = [REQUEST_SEED, marginfi_pda.key().as_ref()],
+        bump = request.load()?.bump
+    )]
+    pub request: AccountLoader<'info, RequestAccountData>,
+    // We use this to verify the functions enclave state was verified successfully
+    #[account(
+        constraint =
+                switchboard_function.load()?.validate(
+                &enclave_signer.to_account_info()
+            )? @ USDY_USDC_ORACLEError::FunctionValidationFailed
+    )]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    pub enclave_signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevealRandomness<'info> {
+    #[account(
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(mut,
+        constraint = request.load()?.switchboard_request == switchboard_function.key()
+            @ USDY_USDC_ORACLEError::IncorrectSwitchboardFunction,
+        seeds = [REQUEST_SEED, marginfi_pda.key().as_ref()],
+        bump = request.load()?.bump
+    )]
+    pub request: AccountLoader<'info, RequestAccountData>,
+    // We use this to verify the functions enclave state was verified successfully
+    #[account(
+        constraint =
+                switchboard_function.load()?.validate(
+                &enclave_signer.to_account_info()
+            )? @ USDY_USDC_ORACLEError::FunctionValidationFailed
+    )]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    pub enclave_signer: Signer<'info>,
+    /// CHECK: address is explicit, read manually since SlotHashes is too large to deserialize
+    #[account(address = slot_hashes::ID)]
+    pub slot_hashes: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRandomness<'info> {
+    #[account(
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    #[account(mut,
+        close = authority,
+        seeds = [REQUEST_SEED, marginfi_pda.key().as_ref()],
+        bump = request.load()?.bump
+    )]
+    pub request: AccountLoader<'info, RequestAccountData>,
+    /// CHECK: only receives the request's rent, which it paid
+    #[account(mut,
+        address = marginfi_pda.authority @ USDY_USDC_ORACLEError::InvalidAuthority
+    )]
+    pub authority: AccountInfo<'info>,
+    // the vault authority, or the enclave signer of the function the request is bound to
+    pub signer: Signer<'info>,
+    pub switchboard_function: Option<AccountLoader<'info, FunctionAccountData>>,
+    /// CHECK: address is explicit, read manually since SlotHashes is too large to deserialize
+    #[account(address = slot_hashes::ID)]
+    pub slot_hashes: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SyncHolderSnapshot<'info> {
+    #[account(
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    // entries only move while the next draw's request is open and its snapshot isn't posted
+    #[account(
+        constraint = request.load()?.switchboard_request == switchboard_function.key()
+            @ USDY_USDC_ORACLEError::IncorrectSwitchboardFunction,
+        constraint = !request.load()?.has_snapshot() @ USDY_USDC_ORACLEError::SnapshotAlreadyPosted,
+        seeds = [REQUEST_SEED, marginfi_pda.key().as_ref()],
+        bump = request.load()?.bump
+    )]
+    pub request: AccountLoader<'info, RequestAccountData>,
+    /// CHECK: created by the first sync and grown as holders are added, which init_if_needed
+    /// can't do, so the handler checks its owner and discriminator
+    #[account(mut,
+        seeds = [HOLDER_SNAPSHOT_SEED, marginfi_pda.key().as_ref()],
+        bump
+    )]
+    pub holder_snapshot: AccountInfo<'info>,
+    #[account(
+        mint::authority = marginfi_pda,
+    )]
+    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    // We use this to verify the functions enclave state was verified successfully
+    #[account(
+        constraint =
+                switchboard_function.load()?.validate(
+                &enclave_signer.to_account_info()
+            )? @ USDY_USDC_ORACLEError::FunctionValidationFailed
+    )]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    // pays for the snapshot to be created and to grow
+    #[account(mut)]
+    pub enclave_signer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PostHolderSnapshot<'info> {
+    #[account(mut,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    // the snapshot has to be committed before the randomness it is drawn against
+    #[account(mut,
+        constraint = request.load()?.switchboard_request == switchboard_function.key()
+            @ USDY_USDC_ORACLEError::IncorrectSwitchboardFunction,
+        constraint = !request.load()?.is_seeded() @ USDY_USDC_ORACLEError::RequestAlreadySeeded,
+        seeds = [REQUEST_SEED, marginfi_pda.key().as_ref()],
+        bump = request.load()?.bump
+    )]
+    pub request: AccountLoader<'info, RequestAccountData>,
+    #[account(
+        mint::authority = marginfi_pda,
+    )]
+    pub jarezi_mint: Box<InterfaceAccount<'info, anchor_spl::token_interface::Mint>>,
+    /// CHECK: may not exist yet, address is checked
+    #[account(
+        address = prize_pool_address(&marginfi_pda.key(), &jarezi_mint.key()),
+    )]
+    pub prize_pool: AccountInfo<'info>,
+    #[account(
+        seeds = [HOLDER_SNAPSHOT_SEED, marginfi_pda.key().as_ref()],
+        bump = holder_snapshot.load()?.bump
+    )]
+    pub holder_snapshot: AccountLoader<'info, HolderSnapshotAccount>,
+    // We use this to verify the functions enclave state was verified successfully
+    #[account(
+        constraint =
+                switchboard_function.load()?.validate(
+                &enclave_signer.to_account_info()
+            )? @ USDY_USDC_ORACLEError::FunctionValidationFailed
+    )]
+    pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
+    pub enclave_signer: Signer<'info>,
+}
+
+impl RequestAccountData {
+    pub fn is_seeded(&self) -> bool {
+        self.seed_timestamp != 0
+    }
+
+    pub fn is_revealed(&self) -> bool {
+        self.reveal_timestamp != 0
+    }
+
+    pub fn has_snapshot(&self) -> bool {
+        self.snapshot_slot != 0
+    }
+
+    /// Seeded but never revealed, and the seed slot's hash is gone from `slot_hashes`, so
+    /// reveal_randomness can't succeed anymore.
+    pub fn is_unrevealable(&self, slot: u64, slot_hashes: &[u8]) -> bool {
+        let seed_slot = self.seed_slot;
+        self.is_seeded() && !self.is_revealed() && slot > seed_slot && find_slot_hash(slot_hashes, seed_slot).is_none()
+    }
+
+    /// The draw used to pick a winner, uniform over [0, total_weight).
+    pub fn draw(&self, total_weight: u64) -> anchor_lang::Result<u64> {
+        if !self.is_revealed() {
+            return Err(error!(USDY_USDC_ORACLEError::RequestNotRevealed));
+        }
+        draw_from_result(&{ self.result }, total_weight)
+    }
+}
+
+/// Maps a randomness result onto [0, total_weight). The Switchboard function uses the same
+/// routine to find the winner before the reveal lands.
+pub fn draw_from_result(result: &[u8; 32], total_weight: u64) -> anchor_lang::Result<u64> {
+    if total_weight == 0 {
+        return Err(error!(USDY_USDC_ORACLEError::EmptyDrawWeight));
+    }
+    let raw = u64::from_le_bytes(result[..8].try_into().unwrap());
+    Ok(raw % total_weight)
+}
+
+/// Derive the randomness result from the revealed key, the hash of the slot the request was
+/// seeded in and the requester's seed. The Switchboard function uses the same routine off-chain.
+pub fn derive_randomness_result(key: &[u8; 32], blockhash: &[u8; 32], seed: u32) -> [u8; 32] {
+    hashv(&[key.as_ref(), blockhash.as_ref(), &seed.to_le_bytes()]).to_bytes()
+}
+
+/// Looks a slot's hash up in the SlotHashes sysvar data: a u64 length prefix followed by
+/// (slot, hash) entries, newest first. None once the slot has aged out, or if it was skipped.
+pub fn find_slot_hash(data: &[u8], slot: u64) -> Option<[u8; 32]> {
+    let len = u64::from_le_bytes(data.get(..8)?.try_into().unwrap()) as usize;
+    data.get(8..)?
+        .chunks_exact(40)
+        .take(len)
+        .find(|entry| u64::from_le_bytes(entry[..8].try_into().unwrap()) == slot)
+        .map(|entry| entry[8..].try_into().unwrap())
+}
+
+impl RequestRandomness<'_> {
+    pub fn request_randomness(
+        ctx: Context<RequestRandomness>,
+        seed: u32,
+    ) -> anchor_lang::Result<()> {
+        let request = &mut ctx.accounts.request.load_init()?;
+        request.bump = ctx.bumps.request;
+        request.switchboard_request = ctx.accounts.marginfi_pda_switchboard.switchboard_function;
+        request.seed = seed;
+        request.request_timestamp = Clock::get()?.unix_timestamp;
+
+        Ok(())
+    }
+}
+
+impl SeedRandomness<'_> {
+    pub fn seed_randomness(
+        ctx: Context<SeedRandomness>,
+        pubkey_hash: [u8; 32],
+    ) -> anchor_lang::Result<()> {
+        let request = &mut ctx.accounts.request.load_mut()?;
+        if request.is_seeded() {
+            return Err(error!(USDY_USDC_ORACLEError::RequestAlreadySeeded));
+        }
+        // the draw can only pick from holders committed to before the randomness
+        if !request.has_snapshot() {
+            return Err(error!(USDY_USDC_ORACLEError::SnapshotNotPosted));
+        }
+
+        // The result mixes in the hash of this slot, which doesn't exist until the slot is over.
+        // Neither the committer nor anyone watching the transaction can know it yet.
+        let clock = Clock::get()?;
+        request.pubkey_hash = pubkey_hash;
+        request.seed_slot = clock.slot;
+        request.seed_timestamp = clock.unix_timestamp;
+
+        Ok(())
+    }
+}
+
+impl RevealRandomness<'_> {
+    pub fn reveal_randomness(
+        ctx: Context<RevealRandomness>,
+        key: [u8; 32],
+    ) -> anchor_lang::Result<()> {
+        let request = &mut ctx.accounts.request.load_mut()?;
+        if !request.is_seeded() {
+            return Err(error!(USDY_USDC_ORACLEError::RequestNotSeeded));
+        }
+        if request.is_revealed() {
+            return Err(error!(USDY_USDC_ORACLEError::RequestAlreadyRevealed));
+        }
+        let pubkey_hash = request.pubkey_hash;
+        if hashv(&[key.as_ref()]).to_bytes() != pubkey_hash {
+            return Err(error!(USDY_USDC_ORACLEError::KeyVerifyFailed));
+        }
+
+        // the seed slot only has a hash once a later slot has started
+        let clock = Clock::get()?;
+        let seed_slot = request.seed_slot;
+        if clock.slot <= seed_slot {
+            return Err(error!(USDY_USDC_ORACLEError::RevealTooEarly));
+        }
+        let data = ctx.accounts.slot_hashes.try_borrow_data()?;
+        let blockhash = find_slot_hash(&data, seed_slot)
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::SlotHashUnavailable))?;
+
+        request.blockhash = blockhash;
+        request.result = derive_randomness_result(&key, &blockhash, request.seed);
+        request.reveal_timestamp = clock.unix_timestamp;
+        msg!("result: {:?}", { request.result });
+
+        Ok(())
+    }
+}
+
+impl CancelRandomness<'_> {
+    // Lets the authority start over, or the function give up on a draw that can't be revealed
+    // anymore because the seed slot's hash aged out of SlotHashes. The function can't cancel a
+    // draw it could still reveal, or one already revealed, so it can't throw away a result.
+    pub fn cancel_randomness(ctx: Context<CancelRandomness>) -> anchor_lang::Result<()> {
+        let signer = ctx.accounts.signer.to_account_info();
+        if signer.key() == ctx.accounts.marginfi_pda.authority {
+            return Ok(());
+        }
+        let switchboard_function = ctx
+            .accounts
+            .switchboard_function
+            .as_ref()
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::InvalidAuthority))?;
+        if switchboard_function.key() != ctx.accounts.request.load()?.switchboard_request {
+            return Err(error!(USDY_USDC_ORACLEError::IncorrectSwitchboardFunction));
+        }
+        if !switchboard_function.load()?.validate(&signer)? {
+            return Err(error!(USDY_USDC_ORACLEError::FunctionValidationFailed));
+        }
+        let slot = Clock::get()?.slot;
+        let slot_hashes = ctx.accounts.slot_hashes.try_borrow_data()?;
+        if !ctx.accounts.request.load()?.is_unrevealable(slot, &slot_hashes) {
+            return Err(error!(USDY_USDC_ORACLEError::RequestStillRevealable));
+        }
+
+        Ok(())
+    }
+}
+
+/// Draw weight of a jarezi account: its balance plus the fees withheld in it. An account that
+/// was closed, or that the vault owns, weighs nothing.
+fn holder_weight(
+    account: &AccountInfo,
+    token_program: &Pubkey,
+    jarezi_mint: &Pubkey,
+    marginfi_pda: &Pubkey,
+) -> anchor_lang::Result<u64> {
+    if account.data_is_empty() {
+        return Ok(0);
+    }
+    if account.owner != token_program {
+        return Err(error!(USDY_USDC_ORACLEError::InvalidHolderAccount));
+    }
+    let data = account.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)
+        .map_err(|_| error!(USDY_USDC_ORACLEError::InvalidHolderAccount))?;
+    if state.base.mint != *jarezi_mint {
+        return Err(error!(USDY_USDC_ORACLEError::InvalidHolderAccount));
+    }
+    if state.base.owner == *marginfi_pda {
+        return Ok(0);
+    }
+    gross_jarezi_balance(&data)
+}
+
+impl SyncHolderSnapshot<'_> {
+    /// Writes the weight of every jarezi account passed as a remaining account into the
+    /// snapshot, adding, updating or dropping its entry.
+    pub fn sync_holder_snapshot<'info>(
+        ctx: Context<'_, '_, '_, 'info, SyncHolderSnapshot<'info>>,
+    ) -> anchor_lang::Result<()> {
+        let marginfi_pda = ctx.accounts.marginfi_pda.key();
+        let jarezi_mint = ctx.accounts.jarezi_mint.to_account_info();
+        let snapshot_info = ctx.accounts.holder_snapshot.to_account_info();
+        let payer = ctx.accounts.enclave_signer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        if snapshot_info.data_is_empty() {
+            let bump = ctx.bumps.holder_snapshot;
+            let seeds: &[&[u8]] = &[HOLDER_SNAPSHOT_SEED, marginfi_pda.as_ref(), &[bump]];
+            create_holder_snapshot(&snapshot_info, bump, seeds, &payer, &system_program, ctx.program_id)?;
+        } else if snapshot_info.owner != ctx.program_id {
+            return Err(error!(anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram));
+        }
+        let mut len = HolderEntries::new(&snapshot_info.try_borrow_data()?)?.len();
+
+        // room for every account to be new, the slack is kept for the next sync
+        let header_len = HOLDER_SNAPSHOT_HEADER_LEN;
+        grow(&snapshot_info, header_len + (len + ctx.remaining_accounts.len()) * HolderEntry::LEN, &payer, &system_program)?;
+
+        let mut data = snapshot_info.try_borrow_mut_data()?;
+        for account in ctx.remaining_accounts {
+            let weight = holder_weight(account, jarezi_mint.owner, &jarezi_mint.key(), &marginfi_pda)?;
+            len = upsert_holder(&mut data[header_len..], len, HolderEntry { token_account: account.key(), weight })?;
+        }
+        bytemuck::from_bytes_mut::<HolderSnapshotAccount>(&mut data[8..header_len]).len = len as u32;
+        msg!("holders: {}", len);
+
+        Ok(())
+    }
+}
+
+/// Creates the snapshot account at its PDA. Anyone can send lamports to the address first, so
+/// it is topped up, allocated and assigned rather than made with create_account.
+fn create_holder_snapshot<'info>(
+    account: &AccountInfo<'info>,
+    bump: u8,
+    seeds: &[&[u8]],
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> anchor_lang::Result<()> {
+    let top_up = Rent::get()?
+        .minimum_balance(HOLDER_SNAPSHOT_HEADER_LEN)
+        .saturating_sub(account.lamports());
+    if top_up > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    anchor_lang::system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            anchor_lang::system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            &[seeds],
+        ),
+        HOLDER_SNAPSHOT_HEADER_LEN as u64,
+    )?;
+    anchor_lang::system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            anchor_lang::system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            &[seeds],
+        ),
+        program_id,
+    )?;
+    let mut data = account.try_borrow_mut_data()?;
+    data[..8].copy_from_slice(&HolderSnapshotAccount::discriminator());
+    bytemuck::from_bytes_mut::<HolderSnapshotAccount>(&mut data[8..HOLDER_SNAPSHOT_HEADER_LEN]).bump = bump;
+    Ok(())
+}
+
+impl PostHolderSnapshot<'_> {
+    pub fn post_holder_snapshot(
+        ctx: Context<PostHolderSnapshot>,
+    ) -> anchor_lang::Result<()> {
+        let snapshot_info = ctx.accounts.holder_snapshot.to_account_info();
+        let data = snapshot_info.try_borrow_data()?;
+        let entries = HolderEntries::new(&data)?;
+        entries.verify()?;
+        let total_weight = entries.total_weight()?;
+
+        // every jarezi outside the vault has to be in the draw, otherwise weight could silently
+        // be left out. The vault's own prize pool never draws.
+        let mint_withheld = mint_withheld_fees(&ctx.accounts.jarezi_mint.to_account_info().try_borrow_data()?)?;
+        let prize_pool = gross_jarezi_balance(&ctx.accounts.prize_pool.try_borrow_data()?)?;
+        let outside_vault = checked_sub(
+            checked_sub(ctx.accounts.jarezi_mint.supply, mint_withheld)?,
+            prize_pool,
+        )?;
+        if total_weight != outside_vault {
+            msg!("snapshot holds {}, {} is outside the vault", total_weight, outside_vault);
+            return Err(error!(USDY_USDC_ORACLEError::SnapshotWeightMismatch));
+        }
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        marginfi_pda.snapshot_root = entries.hash();
+        marginfi_pda.snapshot_total_weight = total_weight;
+        ctx.accounts.request.load_mut()?.snapshot_slot = Clock::get()?.slot;
+        msg!("snapshot_total_weight: {}", total_weight);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot_hashes(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend(slot.to_le_bytes());
+            data.extend([7; 32]);
+        }
+        data
+    }
+
+    fn request(seed_slot: u64, revealed: bool) -> RequestAccountData {
+        let mut request: RequestAccountData = bytemuck::Zeroable::zeroed();
+        request.seed_slot = seed_slot;
+        request.seed_timestamp = 1;
+        request.reveal_timestamp = revealed as i64;
+        request
+    }
+
+    #[test]
+    fn unrevealable_once_the_seed_slot_leaves_slot_hashes() {
+        assert!(request(100, false).is_unrevealable(700, &slot_hashes(&[699, 698, 250])));
+        // skipped seed slots never get a hash either
+        assert!(request(100, false).is_unrevealable(102, &slot_hashes(&[102, 99])));
+    }
+
+    #[test]
+    fn revealable_requests_are_not_unrevealable() {
+        assert!(!request(100, false).is_unrevealable(150, &slot_hashes(&[149, 100, 99])));
+        // the seed slot's hash doesn't exist until the slot is over
+        assert!(!request(100, false).is_unrevealable(100, &slot_hashes(&[99])));
+        assert!(!request(100, true).is_unrevealable(700, &slot_hashes(&[699])));
+
+        let mut unseeded = request(0, false);
+        unseeded.seed_timestamp = 0;
+        assert!(!unseeded.is_unrevealable(700, &slot_hashes(&[699])));
+    }
+}
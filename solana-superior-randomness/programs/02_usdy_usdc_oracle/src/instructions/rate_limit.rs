@@ -0,0 +1,127 @@
+pub use crate::*;
+use solana_program::program_pack::Pack;
+use solend_sdk::{math::Decimal, state::{RateLimiter, RateLimiterConfig, RATE_LIMITER_LEN}};
+
+/// Packed solend_sdk RateLimiter. All zeroes unpacks to a zero window, which the limiter
+/// treats as disabled.
+pub type PackedRateLimiter = [u8; RATE_LIMITER_LEN];
+
+#[derive(Accounts)]
+pub struct UpdateRateLimits<'info> {
+    #[account(mut,
+        constraint = marginfi_pda.authority == authority.key() @ USDY_USDC_ORACLEError::InvalidAuthority,
+        seeds = [SEED_PREFIX, marginfi_pda.thewinnerog.as_ref()],
+        bump
+    )]
+    pub marginfi_pda: Box<Account<'info, MarginFiPda>>,
+    pub authority: Signer<'info>,
+}
+
+/// Windows are in slots and caps in lamports, a zero window disables that side.
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateRateLimitsParams {
+    pub inflow_window_slots: u64,
+    pub max_inflow_lamports: u64,
+    pub outflow_window_slots: u64,
+    pub max_outflow_lamports: u64,
+}
+
+fn throttle(
+    packed: &mut PackedRateLimiter,
+    slot: u64,
+    lamports: Decimal,
+    limited: USDY_USDC_ORACLEError,
+) -> anchor_lang::Result<()> {
+    let mut limiter = RateLimiter::unpack_from_slice(packed)?;
+    if limiter.update(slot, lamports).is_err() {
+        msg!("rate limited, remaining {}", limiter.remaining_outflow(slot)?);
+        return Err(error!(limited));
+    }
+    limiter.pack_into_slice(packed);
+    Ok(())
+}
+
+impl MarginFiPda {
+    /// Counts SOL coming in against the inflow limiter, failing once the window is full.
+    pub fn throttle_inflow(&mut self, slot: u64, lamports: Decimal) -> anchor_lang::Result<()> {
+        throttle(&mut self.inflow_limiter, slot, lamports, USDY_USDC_ORACLEError::DepositRateLimited)
+    }
+
+    /// Counts SOL going out against the outflow limiter, failing once the window is full.
+    pub fn throttle_outflow(&mut self, slot: u64, lamports: Decimal) -> anchor_lang::Result<()> {
+        throttle(&mut self.outflow_limiter, slot, lamports, USDY_USDC_ORACLEError::WithdrawRateLimited)
+    }
+}
+
+impl UpdateRateLimits<'_> {
+    /// Replaces both limiters, which also resets their windows.
+    pub fn update_rate_limits(
+        ctx: Context<UpdateRateLimits>,
+        params: UpdateRateLimitsParams,
+    ) -> anchor_lang::Result<()> {
+        let slot = Clock::get()?.slot;
+        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
+        RateLimiter::new(
+            RateLimiterConfig {
+                window_duration: params.inflow_window_slots,
+                max_outflow: params.max_inflow_lamports,
+            },
+            slot,
+        )
+        .pack_into_slice(&mut marginfi_pda.inflow_limiter);
+        RateLimiter::new(
+            RateLimiterConfig {
+                window_duration: params.outflow_window_slots,
+                max_outflow: params.max_outflow_lamports,
+            },
+            slot,
+        )
+        .pack_into_slice(&mut marginfi_pda.outflow_limiter);
+        msg!("inflow: {} lamports per {} slots", params.max_inflow_lamports, params.inflow_window_slots);
+        msg!("outflow: {} lamports per {} slots", params.max_outflow_lamports, params.outflow_window_slots);
+        emit_config_changed(ctx.accounts.marginfi_pda.key(), ctx.accounts.authority.key(), ConfigKind::RateLimits)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(window_slots: u64, max_lamports: u64, slot: u64) -> PackedRateLimiter {
+        let mut packed = [0u8; RATE_LIMITER_LEN];
+        RateLimiter::new(
+            RateLimiterConfig {
+                window_duration: window_slots,
+                max_outflow: max_lamports,
+            },
+            slot,
+        )
+        .pack_into_slice(&mut packed);
+        packed
+    }
+
+    #[test]
+    fn zeroed_limiter_is_disabled() {
+        let mut packed = [0u8; RATE_LIMITER_LEN];
+        throttle(&mut packed, 100, Decimal::from(u64::MAX), USDY_USDC_ORACLEError::DepositRateLimited).unwrap();
+    }
+
+    #[test]
+    fn throttles_once_the_window_is_full() {
+        let mut packed = limiter(100, 1_000, 100);
+        throttle(&mut packed, 100, Decimal::from(600u64), USDY_USDC_ORACLEError::DepositRateLimited).unwrap();
+        throttle(&mut packed, 150, Decimal::from(400u64), USDY_USDC_ORACLEError::DepositRateLimited).unwrap();
+
+        let before = packed;
+        let err = throttle(&mut packed, 199, Decimal::from(1u64), USDY_USDC_ORACLEError::WithdrawRateLimited)
+            .unwrap_err();
+        assert_eq!(err, error!(USDY_USDC_ORACLEError::WithdrawRateLimited));
+        // a rejected amount isn't counted
+        assert_eq!(packed, before);
+
+        // two windows on, the earlier flow has rolled off
+        throttle(&mut packed, 300, Decimal::from(1_000u64), USDY_USDC_ORACLEError::DepositRateLimited).unwrap();
+    }
+}
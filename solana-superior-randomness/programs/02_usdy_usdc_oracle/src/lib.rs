@@ -5,10 +5,24 @@ pub mod instructions;
 pub mod models;
 pub use models::*;
 
+pub mod snapshot;
+pub use snapshot::*;
+
+pub mod math;
+pub use math::*;
+
+pub mod events;
+pub use events::*;
+
+#[cfg(feature = "client")]
+pub mod client;
+
 pub const PROGRAM_SEED: &[u8] = b"USDY_USDC_ORACLE_V2";
 
 pub const ORACLE_SEED: &[u8] = b"ORACLE_USDY_SEED_V2";
 
+pub const REQUEST_SEED: &[u8] = b"REQUEST_RANDOMNESS_V1";
+
 pub use instructions::*;
 pub use switchboard_solana::*;
 declare_id!("Gyb6RKsLsZa1UCJkCmKYHtEJQF15wF6ZeEqMUSCneh9d");
@@ -25,14 +39,17 @@ pub struct RequestAccountData {
     pub request_timestamp: i64,
     pub seed_timestamp: i64,
     pub reveal_timestamp: i64,
+    pub seed_slot: u64,
+    pub snapshot_slot: u64,
 }
 
 #[program]
 pub mod superior_randomness {
 
     use super::*;
-    pub fn init_mrgn_fi_pda(ctx: Context<InitMrgnFiPda>, bump: u8, kickback: u64, seeded_seed: String, seed2: String) -> anchor_lang::Result<()> {
-        InitMrgnFiPda::init_mrgn_fi_pda(ctx, bump, kickback, seeded_seed, seed2)
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_mrgn_fi_pda(ctx: Context<InitMrgnFiPda>, bump: u8, kickback: u64, seeded_seed: String, seed2: String, name: String, symbol: String, uri: String) -> anchor_lang::Result<()> {
+        InitMrgnFiPda::init_mrgn_fi_pda(ctx, bump, kickback, seeded_seed, seed2, name, symbol, uri)
     }
 
     pub fn set_jarezi_mint_metadata(ctx: Context<SetMetadata>, name: String, symbol: String, uri: String) -> anchor_lang::Result<()> {
@@ -57,21 +74,92 @@ pub mod superior_randomness {
     ) -> anchor_lang::Result<()> {
         Deposit::withdraw(ctx, amount)
     }
+    pub fn update_leverage_config(
+        ctx: Context<UpdateLeverageConfig>,
+        params: UpdateLeverageConfigParams,
+    ) -> anchor_lang::Result<()> {
+        UpdateLeverageConfig::update_leverage_config(ctx, params)
+    }
+    pub fn update_rate_limits(
+        ctx: Context<UpdateRateLimits>,
+        params: UpdateRateLimitsParams,
+    ) -> anchor_lang::Result<()> {
+        UpdateRateLimits::update_rate_limits(ctx, params)
+    }
+    pub fn update_collateral_pool(
+        ctx: Context<UpdateCollateralPool>,
+        params: UpdateCollateralPoolParams,
+    ) -> anchor_lang::Result<()> {
+        UpdateCollateralPool::update_collateral_pool(ctx, params)
+    }
     pub fn set_function(
         ctx: Context<SetFunction>,
     ) -> anchor_lang::Result<()> {
         Deposit::set_function(ctx)
     }
+    pub fn request_randomness(
+        ctx: Context<RequestRandomness>,
+        seed: u32,
+    ) -> anchor_lang::Result<()> {
+        RequestRandomness::request_randomness(ctx, seed)
+    }
+    pub fn seed_randomness(
+        ctx: Context<SeedRandomness>,
+        pubkey_hash: [u8; 32],
+    ) -> anchor_lang::Result<()> {
+        SeedRandomness::seed_randomness(ctx, pubkey_hash)
+    }
+    pub fn reveal_randomness(
+        ctx: Context<RevealRandomness>,
+        key: [u8; 32],
+    ) -> anchor_lang::Result<()> {
+        RevealRandomness::reveal_randomness(ctx, key)
+    }
+    pub fn cancel_randomness(
+        ctx: Context<CancelRandomness>,
+    ) -> anchor_lang::Result<()> {
+        CancelRandomness::cancel_randomness(ctx)
+    }
+    pub fn sync_holder_snapshot<'info>(
+        ctx: Context<'_, '_, '_, 'info, SyncHolderSnapshot<'info>>,
+    ) -> anchor_lang::Result<()> {
+        SyncHolderSnapshot::sync_holder_snapshot(ctx)
+    }
+    pub fn post_holder_snapshot(
+        ctx: Context<PostHolderSnapshot>,
+    ) -> anchor_lang::Result<()> {
+        PostHolderSnapshot::post_holder_snapshot(ctx)
+    }
     pub fn set_winner_winner_chickum_dinner(
         ctx: Context<SetWinner>,
+        params: SetWinnerParams,
     ) -> anchor_lang::Result<()> {
-        Deposit::set_winner_winner_chickum_dinner(ctx)
+        Deposit::set_winner_winner_chickum_dinner(ctx, params)
     }
     pub fn winner_winner_chickum_dinner_distribute(
         ctx: Context<Winner>,
-        amount: u64
     ) -> anchor_lang::Result<()> {
-        Deposit::winner_winner_chickum_dinner_distribute(ctx, amount)
+        Deposit::winner_winner_chickum_dinner_distribute(ctx)
+    }
+
+    pub fn harvest_withheld_fees<'info>(
+        ctx: Context<'_, '_, '_, 'info, HarvestWithheldFees<'info>>,
+    ) -> anchor_lang::Result<()> {
+        HarvestWithheldFees::harvest_withheld_fees(ctx)
+    }
+
+    pub fn update_prize_schedule(
+        ctx: Context<UpdatePrizeSchedule>,
+        params: UpdatePrizeScheduleParams,
+    ) -> anchor_lang::Result<()> {
+        UpdatePrizeSchedule::update_prize_schedule(ctx, params)
+    }
+
+    pub fn set_kickback(
+        ctx: Context<SetKickback>,
+        params: SetKickbackParams,
+    ) -> anchor_lang::Result<()> {
+        SetKickback::set_kickback(ctx, params)
     }
 
     pub fn initialize(ctx: Context<Initialize>, bump: u8, bump2: u8) -> anchor_lang::Result<()> {
@@ -86,148 +174,140 @@ pub mod superior_randomness {
 
         let oracle = &mut ctx.accounts.oracle.load_init()?;
         oracle.bump = bump2;
+        for symbol in DEFAULT_ORACLE_SYMBOLS {
+            oracle.register(symbol)?;
+        }
 
         Ok(())
     }
 
 
-    pub fn update(ctx: Context<Initialize>, bump: u8, bump2: u8) -> anchor_lang::Result<()> {
+    pub fn migrate_program(ctx: Context<MigrateProgram>) -> anchor_lang::Result<()> {
+        MigrateProgram::migrate_program(ctx)
+    }
+
+    pub fn migrate_vault(ctx: Context<MigrateVault>) -> anchor_lang::Result<()> {
+        MigrateVault::migrate_vault(ctx)
+    }
+
+    pub fn update(ctx: Context<UpdateProgram>) -> anchor_lang::Result<()> {
         let program = &mut ctx.accounts.program.load_mut()?;
-        program.bump = bump;
-        program.authority = ctx.accounts.authority.key();
 
         // Optionally set the switchboard_function if provided
         if let Some(switchboard_function) = ctx.accounts.switchboard_function.as_ref() {
             program.switchboard_function = switchboard_function.key();
         }
+        emit_config_changed(Pubkey::default(), ctx.accounts.authority.key(), ConfigKind::SwitchboardFunction)?;
 
-        let oracle = &mut ctx.accounts.oracle.load_init()?;
-        oracle.bump = bump2;
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused_flags: u8) -> anchor_lang::Result<()> {
+        SetPaused::set_paused(ctx, paused_flags)
+    }
+
+    pub fn update_circuit_breaker(
+        ctx: Context<UpdateCircuitBreaker>,
+        params: UpdateCircuitBreakerParams,
+    ) -> anchor_lang::Result<()> {
+        UpdateCircuitBreaker::update_circuit_breaker(ctx, params)
+    }
+
+    pub fn set_oracle_paused(ctx: Context<SetOraclePaused>, paused: bool) -> anchor_lang::Result<()> {
+        SetOraclePaused::set_oracle_paused(ctx, paused)
+    }
+
+    pub fn update_oracle_guardian(ctx: Context<UpdateOracleGuardian>, guardian: Pubkey) -> anchor_lang::Result<()> {
+        UpdateOracleGuardian::update_oracle_guardian(ctx, guardian)
+    }
+
+    pub fn propose_program_authority(
+        ctx: Context<ProposeProgramAuthority>,
+        new_authority: Pubkey,
+    ) -> anchor_lang::Result<()> {
+        ProposeProgramAuthority::propose_program_authority(ctx, new_authority)
+    }
+
+    pub fn accept_program_authority(ctx: Context<AcceptProgramAuthority>) -> anchor_lang::Result<()> {
+        AcceptProgramAuthority::accept_program_authority(ctx)
+    }
+
+    pub fn propose_vault_authority(
+        ctx: Context<ProposeVaultAuthority>,
+        new_authority: Pubkey,
+    ) -> anchor_lang::Result<()> {
+        ProposeVaultAuthority::propose_vault_authority(ctx, new_authority)
+    }
+
+    pub fn accept_vault_authority(ctx: Context<AcceptVaultAuthority>) -> anchor_lang::Result<()> {
+        AcceptVaultAuthority::accept_vault_authority(ctx)
+    }
+
+    pub fn register_oracle_symbol(
+        ctx: Context<ManageOracleSymbol>,
+        symbol: TradingSymbol,
+    ) -> anchor_lang::Result<()> {
+        let oracle = &mut ctx.accounts.oracle.load_mut()?;
+        oracle.register(symbol)?;
+        msg!("registered {:?}", symbol);
+        emit_config_changed(Pubkey::default(), ctx.accounts.authority.key(), ConfigKind::OracleFeed)?;
 
         Ok(())
     }
 
+    pub fn retire_oracle_symbol(
+        ctx: Context<ManageOracleSymbol>,
+        symbol: TradingSymbol,
+    ) -> anchor_lang::Result<()> {
+        let oracle = &mut ctx.accounts.oracle.load_mut()?;
+        oracle.retire(symbol)?;
+        msg!("retired {:?}", symbol);
+        emit_config_changed(Pubkey::default(), ctx.accounts.authority.key(), ConfigKind::OracleFeed)?;
+
+        Ok(())
+    }
+
+    pub fn configure_oracle_feed(
+        ctx: Context<ManageOracleSymbol>,
+        params: ConfigureOracleFeedParams,
+    ) -> anchor_lang::Result<()> {
+        let oracle = &mut ctx.accounts.oracle.load_mut()?;
+        oracle.configure(&params)?;
+        msg!("configured {:?}", params.symbol);
+        emit_config_changed(Pubkey::default(), ctx.accounts.authority.key(), ConfigKind::OracleFeed)?;
+
+        Ok(())
+    }
+
+    /// Remaining accounts are (vault, collateral pool) pairs whose circuit breakers run
+    /// against this push, see trip_breakers.
     pub fn refresh_oracles(
         ctx: Context<RefreshOracles>,
         params: RefreshOraclesParams,
     ) -> anchor_lang::Result<()> {
+        ctx.accounts.program.load()?.check_not_paused(PAUSE_ORACLE_REFRESH)?;
         let oracle = &mut ctx.accounts.oracle.load_mut()?;
+        let previous: Vec<u64> = params.rows.iter().map(|row| oracle.latest_mean(row.symbol)).collect();
         msg!("saving oracle data");
-        oracle.save_rows(&params.rows)?;
-        msg!("${}", {oracle.jitosol_sol.mean});
-        msg!("${}", {oracle.jitosol_sol.median});
-        msg!("{}%", {oracle.jitosol_sol.std});
-        msg!("${}", {oracle.bsol_sol.mean});
-        msg!("${}", {oracle.bsol_sol.median});
-        msg!("{}%", {oracle.bsol_sol.std});
-        
-        let signer = ctx.accounts.signer.clone();
-        let contract = ctx.accounts.contract.clone();
-        let contract_token_account = ctx.accounts.contract_token_account.clone();
-        let game_user = ctx.accounts.game_user.clone();
-        let buyer_token_account = ctx.accounts.buyer_token_account.clone();
-        let token_program = ctx.accounts.token_program.clone();
-        let raffle = ctx.accounts.raffle.clone();
-        let instruction_sysvar = ctx.accounts.instruction_sysvar.clone();
-        let system_program = ctx.accounts.system_program.clone();
-        let remaining_accounts_1 = ctx.accounts.remaining_accounts_1.clone();
-        let remaining_accounts_2 = ctx.accounts.remaining_accounts_2.clone();
-        let remaining_accounts_3 = ctx.accounts.remaining_accounts_3.clone();
-        let remaining_accounts_4 = ctx.accounts.remaining_accounts_4.clone();
-        let remaining_accounts_5 = ctx.accounts.remaining_accounts_5.clone();
-        let remaining_accounts_6 = ctx.accounts.remaining_accounts_6.clone();
-        let remaining_accounts_7 = ctx.accounts.remaining_accounts_7.clone();
-        let remaining_accounts_8 = ctx.accounts.remaining_accounts_8.clone();
-        let remaining_accounts_9 = ctx.accounts.remaining_accounts_9.clone();
-        let remaining_accounts_10 = ctx.accounts.remaining_accounts_10.clone();
-        let memo_program = ctx.accounts.memo_program.clone();
-        let svbonk_program = ctx.accounts.svbonk_program.clone();
-let mut data = get_ixn_discriminator("buy").to_vec();
-data.extend_from_slice(&99_i32.to_le_bytes());
-data.extend_from_slice(&1_i32.to_le_bytes());
-data.extend_from_slice(&1_i32.to_le_bytes());
-        let ix = Instruction {
-            program_id: svbonk_program.key(),
-            accounts: vec![
-                AccountMeta::new(signer.key(), true),
-                AccountMeta::new(contract.key(), false),
-                AccountMeta::new(game_user.key(), false),
-                AccountMeta::new(contract_token_account.key(), false),
-                AccountMeta::new(buyer_token_account.key(), false),
-                AccountMeta::new_readonly(instruction_sysvar.key(), false),
-                AccountMeta::new(raffle.key(), false),
-                AccountMeta::new_readonly(token_program.key(), false),
-                AccountMeta::new_readonly(system_program.key(), false),
-                AccountMeta::new(remaining_accounts_1.key(), false),
-                AccountMeta::new(remaining_accounts_2.key(), false),
-                AccountMeta::new(remaining_accounts_3.key(), false),
-                AccountMeta::new(remaining_accounts_4.key(), false),
-                AccountMeta::new(remaining_accounts_5.key(), false),
-                AccountMeta::new(remaining_accounts_6.key(), false),
-                AccountMeta::new(remaining_accounts_7.key(), false),
-                AccountMeta::new(remaining_accounts_8.key(), false),
-                AccountMeta::new(remaining_accounts_9.key(), false),
-                AccountMeta::new(remaining_accounts_10.key(), false),
-            ],
-            data
-        };  
-        /*#4 Memo Program v2 instruction
-> Program log: Signed by azothGVTnmiTHfKwHGrNSmToHfbpWUbVjGzVcuTQ93o
-> Program log: Memo (len 51): "azothGVTnmiTHfKwHGrNSmToHfbpWUbVjGzVcuTQ93o-0-125-1"
-> Program Memo Program v2  consumed 32920 of 219891 compute units
-> Program returned success
- */
-        let memo_data = format!("{}-0-1-1", signer.key().to_string());
-        let memo_ix = anchor_lang::solana_program::instruction::Instruction {
-            program_id: memo_program.key(),
-            accounts: vec![
-                AccountMeta::new(signer.key(), true),
-            ],
-            data: memo_data.as_bytes().to_vec()
-        };
-
-        let marginfi_pda = &mut ctx.accounts.marginfi_pda;
-        let winner = marginfi_pda.thewinnerog;
-        let SEED_PREFIX: &[u8] = b"jarezi";
-
-        let seeds: &[&[&[u8]]] = &[&[&SEED_PREFIX[..], winner.as_ref(), &[marginfi_pda.bump]]];
-        invoke_signed(
-            &ix,
-            &[
-                signer.to_account_info().clone(),
-                contract.to_account_info().clone(),
-                contract_token_account.to_account_info().clone(),
-                game_user.to_account_info().clone(),
-                buyer_token_account.to_account_info().clone(),
-                token_program.to_account_info().clone(),
-                raffle.to_account_info().clone(),
-                instruction_sysvar.to_account_info().clone(),
-                system_program.to_account_info().clone(),
-                remaining_accounts_1.to_account_info().clone(),
-                remaining_accounts_2.to_account_info().clone(),
-                remaining_accounts_3.to_account_info().clone(),
-                remaining_accounts_4.to_account_info().clone(),
-                remaining_accounts_5.to_account_info().clone(),
-                remaining_accounts_6.to_account_info().clone(),
-                remaining_accounts_7.to_account_info().clone(),
-                remaining_accounts_8.to_account_info().clone(),
-                remaining_accounts_9.to_account_info().clone(),
-                remaining_accounts_10.to_account_info().clone(),
-                svbonk_program.to_account_info().clone(),
-            ],
-            &seeds 
-        )?;
-
-        invoke_signed(
-            &memo_ix,
-            &[
-                signer.to_account_info().clone(),
-            ],
-            &seeds 
-        )?; 
+        oracle.save_rows(&params.rows, Clock::get()?.unix_timestamp)?;
+        for row in params.rows.iter() {
+            msg!("{:?}: ${} ${} {}%", row.symbol, row.data.mean, row.data.median, row.data.std);
+        }
+        trip_breakers(ctx.remaining_accounts, &params.rows, &previous, ctx.accounts.enclave_signer.key())?;
 
         Ok(())
     }
+
+    pub fn update_auto_buy_config(
+        ctx: Context<UpdateAutoBuyConfig>,
+        params: UpdateAutoBuyConfigParams,
+    ) -> anchor_lang::Result<()> {
+        UpdateAutoBuyConfig::update_auto_buy_config(ctx, params)
+    }
+
+    pub fn auto_buy<'info>(ctx: Context<'_, '_, '_, 'info, AutoBuy<'info>>) -> anchor_lang::Result<()> {
+        AutoBuy::auto_buy(ctx)
+    }
     pub fn trigger_function(ctx: Context<TriggerFunction>) -> anchor_lang::Result<()> {
         FunctionTrigger {
             function: ctx.accounts.switchboard_function.to_account_info(),
@@ -240,16 +320,81 @@ data.extend_from_slice(&1_i32.to_le_bytes());
 }
 // Program: Solana TWAP Oracle
 // This Solana program will allow you to peridoically relay information from EtherPrices to your
-// program and store in an account. When a user interacts with our program they will reference
-// the price from the previous push.
+// program and store in an account. Each feed keeps a ring buffer of recent pushes, and when a
+// user interacts with our program they are priced at the time-weighted average over the feed's
+// window rather than a single push. Deposits, withdrawals, draws, oracle pushes and config changes
+// each emit an event (see events.rs) so the vault's history can be rebuilt from the chain.
 // - initialize:        Initializes the program and creates the accounts.
 // - set_function:      Sets the Switchboard Function for our program. This is the only function
 //                      allowed to push data to our program.
 // - refresh_oracle:    This is the instruction our Switchboard Function will emit to update
-//                      our oracle prices.
+//                      our oracle prices. Rows for symbols without a feed slot are dropped with an
+//                      UnknownOracleSymbol event.
+// - auto_buy:          Buys svbonk game tickets as the vault, with the game accounts passed as
+//                      remaining accounts and the quantities from update_auto_buy_config. The
+//                      game accounts have to be the ones update_auto_buy_config pinned.
+// - register_oracle_symbol / retire_oracle_symbol: Authority adds or frees a feed slot.
+// - configure_oracle_feed: Authority sets a feed's max age and deviation bands, or lets the next
+//                      push through regardless of how far it moved.
 // - trigger_function:  Our Switchboard Function will be configured to push data on a pre-defined
 //                      schedule. This instruction will allow us to manually request a new price
 //                      from the off-chain oracles.
+// - request_randomness: Opens a commit-reveal request for the next lottery draw.
+// - seed_randomness:   The Switchboard Function commits a hashed key, the program records the
+//                      slot it landed in.
+// - sync_holder_snapshot: The Switchboard Function passes the jarezi accounts whose balance moved
+//                      since they were last synced, the program writes each one's weight into
+//                      the vault's holder snapshot from the account itself.
+// - post_holder_snapshot: The Switchboard Function commits the holder snapshot before the request
+//                      is seeded. Weights have to add up to the jarezi held outside the vault,
+//                      and the snapshot can't be synced again until the draw is over, so
+//                      transfers after the commit don't change the draw, only who the winning
+//                      token account pays out to.
+// - reveal_randomness: The Switchboard Function reveals the key in a later slot, the program
+//                      derives the result from it and the seed slot's hash, which nobody knew when
+//                      the key was committed. set_winner_winner_chickum_dinner draws from it, and
+//                      the draw has to fall in the range of the snapshot entry the winner is
+//                      picked by.
+// - cancel_randomness: The vault authority closes the request at any time. The function can
+//                      only close one that was seeded but can't be revealed anymore, its seed
+//                      slot's hash having left SlotHashes.
+// - update_prize_schedule: Sets the prize period and the pool tokens paid out per period.
+//                      winner_winner_chickum_dinner_distribute pays every whole period elapsed
+//                      since the last draw, grown by the wSOL borrow rate over that time, and
+//                      clears the winner so the next payout needs a new set_winner.
+// - update_collateral_pool: Binds the vault to the stake pool (SPL or Marinade), pool mint,
+//                      Solend reserve and oracle feed its jarezi is backed by. Deposit, withdraw
+//                      and distribute only accept that pool's accounts. The binding is frozen
+//                      while any jarezi is outstanding.
+// - init_mrgn_fi_pda:  Creates the vault PDA, its Solend obligation and the jarezi mint. The mint
+//                      carries a 1.38% transfer fee and its own TokenMetadata, the vault PDA holds
+//                      every authority. set_jarezi_mint_metadata updates the name, symbol and uri.
+// - harvest_withheld_fees: Sweeps the transfer fees withheld in jarezi accounts into the prize
+//                      pool, which winner_winner_chickum_dinner_distribute pays to the winner.
+//                      The first harvest creates the pool, distribute runs without it until then.
+// - migrate_program / migrate_vault: Authority grows accounts created before fields were
+//                      appended to the current size, zero-filling the new fields. The oracle's
+//                      feeds are reset to the default symbols. No-ops on current accounts.
+// - update:           Authority points the program at a new Switchboard Function.
+// - propose_program_authority / accept_program_authority: Hands the program authority to a new
+//                      key, which has to sign the accept. propose_vault_authority /
+//                      accept_vault_authority do the same for a vault's authority.
+// - set_paused:       A vault's authority or guardian pauses its deposit, withdraw or distribute.
+//                      Only the authority can unpause. set_oracle_paused does the same for oracle
+//                      refresh with the program authority and the guardian update_oracle_guardian
+//                      sets.
+// - update_circuit_breaker: Sets a vault's guardian and how far its collateral pool's oracle
+//                      feed may move between pushes. A bigger move still saves the push, so the
+//                      next one compares against the new price, and pauses that vault's deposit,
+//                      withdraw and distribute. Deposit, withdraw and distribute also run the
+//                      breaker against the feed's move since the vault last checked it, so a
+//                      vault left out of refresh_oracles is still covered; the authority
+//                      unpausing restarts that check from the price it unpaused at.
+// - update_rate_limits: Caps the SOL deposited and withdrawn per sliding window of slots, using
+//                      Solend's RateLimiter. Withdrawals count at NAV.
+// - set_kickback:      Sets the share of each prize minted to the kickback destination (a hydra
+//                      fanout or treasury jarezi account) instead of the winner. Until one is
+//                      set the winner gets the whole prize.
 
 
 #[derive(Accounts)]
@@ -282,12 +427,47 @@ pub struct Initialize<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateProgram<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_SEED],
+        bump = program.load()?.bump,
+        has_one = authority @ USDY_USDC_ORACLEError::InvalidAuthority
+    )]
+    pub program: AccountLoader<'info, MyProgramState>,
+
+    pub authority: Signer<'info>,
+
+    pub switchboard_function: Option<AccountLoader<'info, FunctionAccountData>>,
+}
+
+#[derive(Accounts)]
+pub struct ManageOracleSymbol<'info> {
+    #[account(
+        seeds = [PROGRAM_SEED],
+        bump = program.load()?.bump,
+        has_one = authority @ USDY_USDC_ORACLEError::InvalidAuthority
+    )]
+    pub program: AccountLoader<'info, MyProgramState>,
+
+    #[account(
+        mut,
+        seeds = [ORACLE_SEED],
+        bump = oracle.load()?.bump
+    )]
+    pub oracle: AccountLoader<'info, MyOracleState>,
+
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(params: RefreshOraclesParams)] // rpc parameters hint
 pub struct RefreshOracles<'info> {
     // We need this to validate that the Switchboard Function passed to our program
     // is the expected one.
     #[account(
+        mut,
         seeds = [PROGRAM_SEED],
         bump = program.load()?.bump,
        has_one = switchboard_function
@@ -310,76 +490,6 @@ pub struct RefreshOracles<'info> {
     )]
     pub switchboard_function: AccountLoader<'info, FunctionAccountData>,
     pub enclave_signer: Signer<'info>,
-    #[account(mut)]
-    pub bonk: Account<'info, Mint>,
-    #[account(mut)]
-    pub marginfi_pda: Account<'info, MarginFiPda>,
-    /// CHECK:
-    #[account(mut)]
-    pub signer: AccountInfo<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub contract: AccountInfo<'info>,
-    /// CHECK:contractTokenAccount
-    #[account(mut)]
-    pub contract_token_account: AccountInfo<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub game_user: AccountInfo<'info>,
-    #[account(mut,
-    )]
-    pub buyer_token_account: Box<Account<'info, TokenAccount>>,
-    pub token_program: Program<'info, Token>,
-    /// CHECK:
-    #[account(mut)]
-    pub raffle: AccountInfo<'info>,
-    /// CHECK: instructionSysvarAccount
-    pub instruction_sysvar: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_1: AccountInfo<'info>,
-    /// 9 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_2: AccountInfo<'info>,
-    /// 8 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_3: AccountInfo<'info>,
-    /// 7 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_4: AccountInfo<'info>,
-    /// 6 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_5: AccountInfo<'info>,
-    /// 5 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_6: AccountInfo<'info>,
-    /// 4 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_7: AccountInfo<'info>,
-    /// 3 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_8: AccountInfo<'info>,
-    /// 2 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_9: AccountInfo<'info>,
-    /// 1 more
-    /// CHECK:
-    #[account(mut)]
-    pub remaining_accounts_10: AccountInfo<'info>,
-    /// CHECK:
-    pub memo_program: AccountInfo<'info>,
-    /// CHECK:
-    pub svbonk_program: AccountInfo<'info>,
-
 }
 
 #[derive(Clone, AnchorSerialize, AnchorDeserialize)]
@@ -438,4 +548,130 @@ pub enum USDY_USDC_ORACLEError {
     RequestAlreadySeeded,
     RequestAlreadyRevealed,
     KeyVerifyFailed,
+    #[msg("Randomness request has not been seeded yet")]
+    RequestNotSeeded,
+    #[msg("Randomness request has not been revealed yet")]
+    RequestNotRevealed,
+    #[msg("Cannot draw a winner with zero total weight")]
+    EmptyDrawWeight,
+    #[msg("Holder snapshot weight does not match the jarezi held outside the vault")]
+    SnapshotWeightMismatch,
+    #[msg("Draw does not fall in the claimed winner's range")]
+    WinnerOutOfRange,
+    #[msg("Winner is not included in the committed holder snapshot")]
+    InvalidHolderProof,
+    #[msg("Leverage config ratios must be below 100%, leverage and health floor at least 1x, and loop count non-zero")]
+    InvalidLeverageConfig,
+    #[msg("Received fewer pool tokens than the configured slippage allows")]
+    SlippageExceeded,
+    #[msg("Obligation health factor would fall under the configured floor")]
+    HealthFactorTooLow,
+    #[msg("Withdraw needs the caller's wSOL account holding the flash loan")]
+    MissingWsolAccount,
+    #[msg("Withdraw amount must be non-zero and at most the jarezi supply")]
+    InvalidWithdrawAmount,
+    #[msg("Unstaked SOL does not cover the flash loan and its fee")]
+    UnwindShortfall,
+    #[msg("Checked math overflowed")]
+    MathOverflow,
+    #[msg("Prize period must be positive")]
+    InvalidPrizeSchedule,
+    #[msg("No full prize period has elapsed since the last distribution")]
+    PrizePeriodNotElapsed,
+    #[msg("Kickback can be at most 100%")]
+    InvalidKickback,
+    #[msg("Oracle symbol already has a feed slot")]
+    SymbolAlreadyRegistered,
+    #[msg("Oracle price moved outside the configured band")]
+    PriceDeviation,
+    #[msg("Accounts or oracle feed don't match the vault's collateral pool")]
+    InvalidCollateralPool,
+    #[msg("Collateral pool can't change while jarezi is outstanding")]
+    CollateralPoolInUse,
+    #[msg("Signer is not the proposed authority")]
+    InvalidPendingAuthority,
+    #[msg("Deposits are paused")]
+    DepositsPaused,
+    #[msg("Withdrawals are paused")]
+    WithdrawalsPaused,
+    #[msg("Prize distribution is paused")]
+    DistributionPaused,
+    #[msg("Oracle refresh is paused")]
+    OracleRefreshPaused,
+    #[msg("Unknown pause flags")]
+    InvalidPausedFlags,
+    #[msg("Deposit would exceed the vault's inflow rate limit")]
+    DepositRateLimited,
+    #[msg("Withdrawal would exceed the vault's outflow rate limit")]
+    WithdrawRateLimited,
+    #[msg("Randomness can't be revealed in the slot it was seeded in")]
+    RevealTooEarly,
+    #[msg("Seed slot hash is no longer in SlotHashes, cancel the request")]
+    SlotHashUnavailable,
+    #[msg("Post the holder snapshot before seeding the request")]
+    SnapshotNotPosted,
+    #[msg("The vault can't win its own draw")]
+    VaultCannotWin,
+    #[msg("Winner's token account holds less than its snapshot balance")]
+    WinnerBalanceTooLow,
+    #[msg("Vault has jarezi outstanding but nothing backing them")]
+    VaultInsolvent,
+    #[msg("No winner is set, run set_winner_winner_chickum_dinner for this draw first")]
+    WinnerNotSet,
+    #[msg("Vault has a kickback destination but none was passed")]
+    MissingKickbackDestination,
+    #[msg("Oracle push is stamped in the future")]
+    FutureOracleTimestamp,
+    #[msg("Game accounts don't match the auto buy config")]
+    InvalidGameAccounts,
+    #[msg("Marinade vaults need the liquidity pool and treasury accounts")]
+    MissingMarinadeAccounts,
+    #[msg("Oracle push is not newer than the feed's last push")]
+    StaleOracleTimestamp,
+    #[msg("Holder snapshot entries are out of order, repeated or empty")]
+    InvalidHolderSnapshot,
+    #[msg("Holder snapshot accounts have to be jarezi token accounts")]
+    InvalidHolderAccount,
+    #[msg("Holder snapshot is committed until the draw is over")]
+    SnapshotAlreadyPosted,
+    #[msg("The function can only cancel a request whose seed slot left SlotHashes before the reveal")]
+    RequestStillRevealable,
+    #[msg("Obligation isn't the vault's Solend obligation")]
+    InvalidObligation,
+}
+
+/// Vault CPI legs. Each leg logs its stage and amount before it invokes. A callee error aborts
+/// the transaction, so that log line is what names the failed leg; errors raised before the
+/// invoke, e.g. building the instruction, are logged and surface as the stage's error.
+#[error_code(offset = 7000)]
+#[derive(Eq, PartialEq)]
+pub enum VaultError {
+    #[msg("Stake pool deposit failed")]
+    StakePoolDeposit,
+    #[msg("Solend collateral deposit failed")]
+    SolendDeposit,
+    #[msg("Solend reserve or obligation refresh failed")]
+    Refresh,
+    #[msg("Solend wSOL borrow failed")]
+    Borrow,
+    #[msg("Closing the borrowed wSOL account failed")]
+    CloseWsol,
+    #[msg("Minting jarezi failed")]
+    Mint,
+    #[msg("Burning jarezi failed")]
+    Burn,
+    #[msg("Solend wSOL repay failed")]
+    Repay,
+    #[msg("Solend collateral withdraw failed")]
+    Withdraw,
+    #[msg("Stake pool withdraw failed")]
+    Unstake,
+    #[msg("Harvesting withheld jarezi transfer fees failed")]
+    HarvestFees,
+    #[msg("Paying out the prize pool failed")]
+    PrizePool,
+    #[msg("Creating the Solend obligation account failed")]
+    CreateObligation,
+    #[msg("Initializing the Solend obligation failed")]
+    InitObligation,
 }
@@ -0,0 +1,341 @@
+use crate::*;
+use solend_sdk::math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub};
+use solend_sdk::state::{Obligation, Reserve};
+
+// Oracle prices are lamports per pool token, scaled by 1e9
+pub const PRICE_SCALE: u64 = 1_000_000_000;
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Token amounts leave fixed-point through one of these so every site states which way it
+/// rounds: anything paid out of the vault (mint, withdraw) rounds down, anything paid into
+/// it (burn, repay) rounds up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+pub fn to_amount(value: Decimal, rounding: Rounding) -> anchor_lang::Result<u64> {
+    let amount = match rounding {
+        Rounding::Down => value.try_floor_u64(),
+        Rounding::Up => value.try_ceil_u64(),
+    };
+    amount.map_err(|_| error!(USDY_USDC_ORACLEError::MathOverflow))
+}
+
+pub fn checked_add(a: u64, b: u64) -> anchor_lang::Result<u64> {
+    a.checked_add(b)
+        .ok_or_else(|| error!(USDY_USDC_ORACLEError::MathOverflow))
+}
+
+pub fn checked_sub(a: u64, b: u64) -> anchor_lang::Result<u64> {
+    a.checked_sub(b)
+        .ok_or_else(|| error!(USDY_USDC_ORACLEError::MathOverflow))
+}
+
+/// `amount * bpm / BPM`
+pub fn bpm_of(amount: u64, bpm: u64) -> anchor_lang::Result<Decimal> {
+    Ok(Decimal::from(amount).try_mul(bpm)?.try_div(BPM)?)
+}
+
+/// `amount * (BPM - bpm) / BPM`, e.g. what is left after a fee.
+pub fn less_bpm(amount: Decimal, bpm: u64) -> anchor_lang::Result<Decimal> {
+    Ok(amount.try_mul(checked_sub(BPM, bpm)?)?.try_div(BPM)?)
+}
+
+pub fn sol_to_pool_tokens(lamports: Decimal, price: u64) -> anchor_lang::Result<Decimal> {
+    if price == 0 {
+        return Err(error!(USDY_USDC_ORACLEError::MathOverflow));
+    }
+    Ok(lamports.try_mul(PRICE_SCALE)?.try_div(price)?)
+}
+
+pub fn pool_tokens_to_sol(pool_tokens: Decimal, price: u64) -> anchor_lang::Result<Decimal> {
+    Ok(pool_tokens.try_mul(price)?.try_div(PRICE_SCALE)?)
+}
+
+/// A Solend rate, e.g. a reserve's current borrow rate, scaled by PRICE_SCALE like the oracle
+/// rows, rounded down.
+pub fn scaled_rate(rate: Rate) -> anchor_lang::Result<u64> {
+    to_amount(Decimal::from(rate).try_mul(PRICE_SCALE)?, Rounding::Down)
+}
+
+/// Lamports per whole pool token of a stake pool holding `total_lamports` against
+/// `pool_token_supply`, scaled by PRICE_SCALE and rounded down.
+pub fn pool_price(total_lamports: u64, pool_token_supply: u64) -> anchor_lang::Result<u64> {
+    if pool_token_supply == 0 {
+        return Err(error!(USDY_USDC_ORACLEError::MathOverflow));
+    }
+    to_amount(
+        Decimal::from(total_lamports).try_mul(PRICE_SCALE)?.try_div(pool_token_supply)?,
+        Rounding::Down,
+    )
+}
+
+/// `price / quote` scaled by PRICE_SCALE and rounded down, e.g. a pool token's USD price over
+/// SOL's.
+pub fn price_ratio(price: Decimal, quote: Decimal) -> anchor_lang::Result<u64> {
+    if quote == Decimal::zero() {
+        return Err(error!(USDY_USDC_ORACLEError::MathOverflow));
+    }
+    to_amount(price.try_mul(PRICE_SCALE)?.try_div(quote)?, Rounding::Down)
+}
+
+/// Growth factor of a debt over `elapsed` seconds at `apr` (scaled by 1e9), compounded
+/// every second the same way Solend compounds per slot.
+pub fn compounded_interest(apr: u64, elapsed: i64) -> anchor_lang::Result<Rate> {
+    if elapsed <= 0 {
+        return Ok(Rate::one());
+    }
+    let apr = Rate::try_from(Decimal::from(apr).try_div(PRICE_SCALE)?)?;
+    let per_second = apr.try_div(SECONDS_PER_YEAR)?;
+    Ok(Rate::one().try_add(per_second)?.try_pow(elapsed as u64)?)
+}
+
+/// Interest accrued on `principal` over `elapsed` seconds.
+pub fn accrued_interest(principal: Decimal, apr: u64, elapsed: i64) -> anchor_lang::Result<Decimal> {
+    let grown = principal.try_mul(compounded_interest(apr, elapsed)?)?;
+    Ok(grown.try_sub(principal)?)
+}
+
+/// Jarezi minted for `value` lamports added to a vault worth `nav` with `supply` outstanding,
+/// rounded down. The first deposit into an empty vault mints 1:1; a vault whose jarezi are
+/// backed by nothing can't take deposits, they would be shared with the existing holders.
+pub fn shares_for_value(value: Decimal, nav: Decimal, supply: u64) -> anchor_lang::Result<u64> {
+    if supply == 0 {
+        return to_amount(value, Rounding::Down);
+    }
+    if nav == Decimal::zero() {
+        return Err(error!(USDY_USDC_ORACLEError::VaultInsolvent));
+    }
+    to_amount(value.try_mul(supply)?.try_div(nav)?, Rounding::Down)
+}
+
+/// Lamports per whole jarezi, scaled by PRICE_SCALE. An empty vault is 1:1.
+pub fn share_price(nav: Decimal, supply: u64) -> anchor_lang::Result<u64> {
+    if supply == 0 {
+        return Ok(PRICE_SCALE);
+    }
+    to_amount(nav.try_mul(PRICE_SCALE)?.try_div(supply)?, Rounding::Down)
+}
+
+/// Jarezi minted to pay a prize worth `value` lamports out of a vault worth `nav` with `supply`
+/// outstanding, rounded down. Nothing is deposited for it, the holders pay it by dilution, so
+/// it is priced at what a jarezi is worth once the prize has left: `value * supply / (nav - value)`.
+pub fn prize_shares(value: Decimal, nav: Decimal, supply: u64) -> anchor_lang::Result<u64> {
+    shares_for_value(value, nav.try_sub(value)?, supply)
+}
+
+/// Value of a vault's position in lamports: the collateral its obligation holds in
+/// `collateral_reserve` redeemed at the reserve's collateral exchange rate plus
+/// `idle_pool_tokens`, priced at `pool_price`, less its debt. A vault obligation only ever
+/// borrows wSOL, so every borrow on it counts. Taken as of the obligation's last refresh.
+pub fn net_asset_value(
+    obligation: &Obligation,
+    collateral_reserve: &Pubkey,
+    reserve: &Reserve,
+    idle_pool_tokens: u64,
+    pool_price: u64,
+) -> anchor_lang::Result<Decimal> {
+    let collateral = match obligation.find_collateral_in_deposits(*collateral_reserve) {
+        Ok((collateral, _)) => reserve
+            .collateral_exchange_rate()?
+            .decimal_collateral_to_liquidity(Decimal::from(collateral.deposited_amount))?,
+        Err(_) => Decimal::zero(),
+    };
+    let pool_tokens = collateral.try_add(Decimal::from(idle_pool_tokens))?;
+    let assets = pool_tokens_to_sol(pool_tokens, pool_price)?;
+    let debt = obligation
+        .borrows
+        .iter()
+        .try_fold(Decimal::zero(), |debt, borrow| debt.try_add(borrow.borrowed_amount_wads))?;
+    Ok(assets.try_sub(debt)?)
+}
+
+/// Collateral exchange rate
+#[derive(Clone, Copy, Debug)]
+pub struct CollateralExchangeRate(Rate);
+
+impl CollateralExchangeRate {
+    /// Convert reserve collateral to liquidity
+    pub fn collateral_to_liquidity(&self, collateral_amount: u64) -> anchor_lang::Result<u64> {
+        to_amount(self.decimal_collateral_to_liquidity(collateral_amount.into())?, Rounding::Down)
+    }
+
+    /// Convert reserve collateral to liquidity
+    pub fn decimal_collateral_to_liquidity(
+        &self,
+        collateral_amount: Decimal,
+    ) -> anchor_lang::Result<Decimal> {
+        Ok(collateral_amount.try_div(self.0)?)
+    }
+
+    /// Convert reserve liquidity to collateral
+    pub fn liquidity_to_collateral(&self, liquidity_amount: u64) -> anchor_lang::Result<u64> {
+        to_amount(self.decimal_liquidity_to_collateral(liquidity_amount.into())?, Rounding::Down)
+    }
+
+    /// Convert reserve liquidity to collateral
+    pub fn decimal_liquidity_to_collateral(
+        &self,
+        liquidity_amount: Decimal,
+    ) -> anchor_lang::Result<Decimal> {
+        Ok(liquidity_amount.try_mul(self.0)?)
+    }
+}
+
+impl From<CollateralExchangeRate> for Rate {
+    fn from(exchange_rate: CollateralExchangeRate) -> Self {
+        exchange_rate.0
+    }
+}
+
+/// Return the current collateral exchange rate.
+pub fn exchange_rate(
+    total_liquidity: u64,
+    mint_total_supply: u64,
+) -> anchor_lang::Result<CollateralExchangeRate> {
+    let mint_total_supply = Decimal::from(mint_total_supply);
+    let rate = Rate::try_from(mint_total_supply.try_div(Decimal::from(total_liquidity))?)?;
+
+    Ok(CollateralExchangeRate(rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_amount_rounds_the_stated_way() {
+        let half = Decimal::from(3u64).try_div(2u64).unwrap();
+        assert_eq!(to_amount(half, Rounding::Down).unwrap(), 1);
+        assert_eq!(to_amount(half, Rounding::Up).unwrap(), 2);
+        assert_eq!(to_amount(Decimal::from(7u64), Rounding::Up).unwrap(), 7);
+        assert!(to_amount(Decimal::from(u64::MAX).try_add(Decimal::one()).unwrap(), Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn bpm_helpers() {
+        assert_eq!(to_amount(bpm_of(1_000, 250_000).unwrap(), Rounding::Down).unwrap(), 250);
+        assert_eq!(to_amount(bpm_of(3, 500_000).unwrap(), Rounding::Down).unwrap(), 1);
+        assert_eq!(to_amount(bpm_of(3, 500_000).unwrap(), Rounding::Up).unwrap(), 2);
+        assert_eq!(to_amount(less_bpm(Decimal::from(1_000u64), 10_000).unwrap(), Rounding::Down).unwrap(), 990);
+        assert!(less_bpm(Decimal::from(1u64), BPM + 1).is_err());
+    }
+
+    #[test]
+    fn pool_token_conversions() {
+        // 1.25 SOL per pool token
+        let price = 1_250_000_000;
+        let tokens = sol_to_pool_tokens(Decimal::from(1_000u64), price).unwrap();
+        assert_eq!(to_amount(tokens, Rounding::Down).unwrap(), 800);
+        assert_eq!(to_amount(pool_tokens_to_sol(tokens, price).unwrap(), Rounding::Down).unwrap(), 1_000);
+        // 999 lamports are 799.2 pool tokens
+        let tokens = sol_to_pool_tokens(Decimal::from(999u64), price).unwrap();
+        assert_eq!(to_amount(tokens, Rounding::Down).unwrap(), 799);
+        assert_eq!(to_amount(tokens, Rounding::Up).unwrap(), 800);
+        assert!(sol_to_pool_tokens(Decimal::from(1u64), 0).is_err());
+    }
+
+    #[test]
+    fn price_helpers() {
+        assert_eq!(pool_price(1_100, 1_000).unwrap(), 1_100_000_000);
+        assert_eq!(pool_price(2, 3).unwrap(), 666_666_666);
+        assert!(pool_price(1, 0).is_err());
+        let ratio = price_ratio(Decimal::from(220u64), Decimal::from(200u64)).unwrap();
+        assert_eq!(ratio, 1_100_000_000);
+        assert!(price_ratio(Decimal::one(), Decimal::zero()).is_err());
+        assert_eq!(scaled_rate(Rate::from_percent(5)).unwrap(), 50_000_000);
+    }
+
+    #[test]
+    fn interest() {
+        assert_eq!(compounded_interest(50_000_000, 0).unwrap(), Rate::one());
+        assert_eq!(compounded_interest(50_000_000, -1).unwrap(), Rate::one());
+        // 5% a year, compounded per second, is a little over 5.12%
+        let principal = Decimal::from(1_000_000_000u64);
+        let year = SECONDS_PER_YEAR as i64;
+        let interest = to_amount(accrued_interest(principal, 50_000_000, year).unwrap(), Rounding::Down).unwrap();
+        assert!(interest > 51_200_000 && interest < 51_300_000, "{}", interest);
+    }
+
+    #[test]
+    fn shares() {
+        // the first deposit mints 1:1
+        assert_eq!(shares_for_value(Decimal::from(500u64), Decimal::zero(), 0).unwrap(), 500);
+        // 10 lamports into a vault worth 3 per 2 jarezi is 6.67 jarezi, rounded down
+        assert_eq!(shares_for_value(Decimal::from(10u64), Decimal::from(300u64), 200).unwrap(), 6);
+        // jarezi backed by nothing
+        assert!(shares_for_value(Decimal::from(10u64), Decimal::zero(), 200).is_err());
+
+        assert_eq!(share_price(Decimal::zero(), 0).unwrap(), PRICE_SCALE);
+        assert_eq!(share_price(Decimal::from(300u64), 200).unwrap(), 1_500_000_000);
+        assert_eq!(share_price(Decimal::from(2u64), 3).unwrap(), 666_666_666);
+    }
+
+    #[test]
+    fn prizes_are_priced_after_they_leave() {
+        // 100 lamports out of a vault worth 1000 on 1000 jarezi: 111 jarezi, worth 99.9 once
+        // minted
+        assert_eq!(prize_shares(Decimal::from(100u64), Decimal::from(1_000u64), 1_000).unwrap(), 111);
+        // a prize the vault can't cover
+        assert!(prize_shares(Decimal::from(1_000u64), Decimal::from(1_000u64), 1_000).is_err());
+        assert!(prize_shares(Decimal::from(1_001u64), Decimal::from(1_000u64), 1_000).is_err());
+    }
+
+    #[test]
+    fn looped_vault_prize_is_priced_off_the_whole_position() {
+        use solend_sdk::state::{ObligationCollateral, ObligationLiquidity, ReserveCollateral, ReserveLiquidity};
+
+        let collateral_reserve = Pubkey::new_unique();
+        // 1 collateral token redeems 2 pool tokens
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 2_000_000_000_000,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 1_000_000_000_000,
+                ..ReserveCollateral::default()
+            },
+            ..Reserve::default()
+        };
+        // every pool token is posted as collateral and 1200 SOL is borrowed against it, nothing
+        // is left idle
+        let obligation = Obligation {
+            deposits: vec![ObligationCollateral {
+                deposit_reserve: collateral_reserve,
+                deposited_amount: 1_000_000_000_000,
+                ..ObligationCollateral::default()
+            }],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve: Pubkey::new_unique(),
+                borrowed_amount_wads: Decimal::from(1_200_000_000_000u64),
+                ..ObligationLiquidity::default()
+            }],
+            ..Obligation::default()
+        };
+        // 2000 pool tokens at 1.1 SOL less 1200 SOL of debt
+        let nav = net_asset_value(&obligation, &collateral_reserve, &reserve, 0, 1_100_000_000).unwrap();
+        assert_eq!(nav, Decimal::from(1_000_000_000_000u64));
+
+        // 100 SOL of prize on 1000 jarezi
+        let supply = 1_000_000_000_000;
+        let value = pool_tokens_to_sol(Decimal::from(100_000_000_000u64), PRICE_SCALE).unwrap();
+        let prize = prize_shares(value, nav, supply).unwrap();
+        assert_eq!(prize, 111_111_111_111);
+        // the winner's jarezi are worth the prize, not more
+        let worth = nav.try_mul(prize).unwrap().try_div(supply + prize).unwrap();
+        assert!(worth <= value);
+        assert_eq!(to_amount(worth, Rounding::Up).unwrap(), 100_000_000_000);
+    }
+
+    #[test]
+    fn collateral_exchange_rate() {
+        // 200 liquidity backing 100 collateral
+        let rate = exchange_rate(200, 100).unwrap();
+        assert_eq!(rate.collateral_to_liquidity(3).unwrap(), 6);
+        assert_eq!(rate.liquidity_to_collateral(5).unwrap(), 2);
+    }
+}
@@ -6,6 +6,12 @@ pub struct MyProgramState {
     pub bump: u8,
     pub authority: Pubkey,
     pub switchboard_function: Pubkey,
+    /// Proposed by the authority, takes over once it signs accept_program_authority
+    pub pending_authority: Pubkey,
+    /// Second key allowed to pause oracle refresh, see set_oracle_paused
+    pub guardian: Pubkey,
+    /// PAUSE_ORACLE_REFRESH only, vaults carry their own flags
+    pub paused_flags: u8,
 }
 
 #[repr(packed)]
@@ -41,49 +47,309 @@ pub struct OracleDataWithTradingSymbol {
     pub data: OracleDataBorsh,
 }
 
+/// Whether `mean` moved more than `band_bpm` (parts per million) away from `previous`.
+pub fn deviates(mean: u64, previous: u64, band_bpm: u64) -> bool {
+    let (mean, previous) = (mean as u128, previous as u128);
+    mean.abs_diff(previous) * BPM as u128 > previous * band_bpm as u128
+}
+
 impl OracleData {
-    pub fn get_fair_price(&self) -> anchor_lang::Result<f64> {
-        // Check the price was updated in the last 10 seconds
+    pub fn is_stale(&self, now: i64, max_age_secs: i64) -> bool {
+        let oracle_timestamp = self.oracle_timestamp;
+        oracle_timestamp == 0 || now.saturating_sub(oracle_timestamp) > max_age_secs
+    }
 
-        // Do some logic here based on the twap
+    /// Whether `mean` moved more than `band_bpm` (parts per million) away from `previous`.
+    pub fn deviates_from(&self, previous: u64, band_bpm: u64) -> bool {
+        deviates(self.mean, previous, band_bpm)
+    }
 
-        let price: f64 = SwitchboardDecimal {
-            mantissa: self.mean as i128,
-            scale: 18,
-        }
-        .try_into()?;
+    /// Whether the sources behind this push disagreed by more than `max_std_bpm` of the mean.
+    pub fn too_dispersed(&self, max_std_bpm: u64) -> bool {
+        let (mean, std) = (self.mean as u128, self.std as u128);
+        std * BPM as u128 > mean * max_std_bpm as u128
+    }
+}
+
+pub const MAX_ORACLE_FEEDS: usize = 8;
+
+/// Feeds initialize (and migrate_program) registers, more go through register_oracle_symbol
+pub const DEFAULT_ORACLE_SYMBOLS: [TradingSymbol; 5] = [
+    TradingSymbol::Bsol_sol,
+    TradingSymbol::Jitosol_sol,
+    TradingSymbol::Wsol_borrow,
+    TradingSymbol::Bsol_redemption,
+    TradingSymbol::Jitosol_redemption,
+];
+
+pub const DEFAULT_MAX_AGE_SECS: i64 = 300;
+pub const DEFAULT_MAX_DEVIATION_BPM: u64 = 50_000;
+pub const DEFAULT_MAX_STD_BPM: u64 = 20_000;
+pub const DEFAULT_TWAP_WINDOW_SECS: i64 = 30 * 60;
+/// How far ahead of the cluster clock a push may be stamped, for drift between the enclave and
+/// the validator
+pub const MAX_CLOCK_SKEW_SECS: i64 = 30;
+
+pub const ORACLE_HISTORY_LEN: usize = 16;
+
+#[repr(packed)]
+#[zero_copy(unsafe)]
+pub struct PriceObservation {
+    pub timestamp: i64,
+    pub price: u64,
+}
+
+#[repr(packed)]
+#[zero_copy(unsafe)]
+pub struct OracleSlot {
+    /// Unknown marks a free slot
+    pub symbol: TradingSymbol,
+    pub data: OracleData,
+    /// Reads fail with StaleData once the last push is older than this
+    pub max_age_secs: i64,
+    /// refresh_oracles rejects a mean further than this from the previous one
+    pub max_deviation_bpm: u64,
+    /// Reads fail with PriceDeviation while std is wider than this share of the mean
+    pub max_std_bpm: u64,
+    /// Set by the authority to accept the next push regardless of max_deviation_bpm
+    pub allow_next_jump: u8,
+    /// Window used by the TWAP the vault prices deposits and withdrawals with
+    pub twap_window_secs: i64,
+    /// Ring buffer of past means, history_head is the next slot to write
+    pub history: [PriceObservation; ORACLE_HISTORY_LEN],
+    pub history_head: u8,
+}
+
+impl OracleSlot {
+    fn push_observation(&mut self, timestamp: i64, price: u64) {
+        let head = self.history_head as usize % ORACLE_HISTORY_LEN;
+        self.history[head] = PriceObservation { timestamp, price };
+        self.history_head = ((head + 1) % ORACLE_HISTORY_LEN) as u8;
+    }
 
-        Ok(price)
+    /// Time-weighted mean over [now - window_secs, now]. Each observation holds until the
+    /// next one; if the buffer doesn't reach back far enough the window starts at the oldest
+    /// observation.
+    pub fn twap(&self, window_secs: i64, now: i64) -> Option<u64> {
+        let window_start = now.saturating_sub(window_secs);
+        let head = self.history_head as usize;
+        let mut end = now;
+        let mut weighted: u128 = 0;
+        let mut weight: u128 = 0;
+        let mut latest = None;
+        for i in 1..=ORACLE_HISTORY_LEN {
+            let observation = self.history[(head + ORACLE_HISTORY_LEN - i) % ORACLE_HISTORY_LEN];
+            let (timestamp, price) = (observation.timestamp, observation.price);
+            if timestamp == 0 {
+                break;
+            }
+            latest.get_or_insert(price);
+            let start = timestamp.max(window_start);
+            if end > start {
+                weighted += price as u128 * (end - start) as u128;
+                weight += (end - start) as u128;
+            }
+            if timestamp <= window_start {
+                break;
+            }
+            end = timestamp;
+        }
+        if weight == 0 {
+            return latest;
+        }
+        Some((weighted / weight) as u64)
     }
 }
 
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct ConfigureOracleFeedParams {
+    pub symbol: TradingSymbol,
+    pub max_age_secs: i64,
+    pub max_deviation_bpm: u64,
+    pub max_std_bpm: u64,
+    pub allow_next_jump: bool,
+    pub twap_window_secs: i64,
+}
+
 #[repr(packed)]
 #[account(zero_copy(unsafe))]
 pub struct MyOracleState {
     pub bump: u8,
-    pub bsol_sol: OracleData,
-    pub jitosol_sol: OracleData,
-    pub wsol_borrow: OracleData,
+    pub feeds: [OracleSlot; MAX_ORACLE_FEEDS],
     pub last_borrowed_amount: u64, /// len: 8
     pub last_borrowed_amount_timestamp: i64, // len: 8
 
-    pub _padding: [u8; 176],//192-16=176
+    pub _padding: [u8; 128],
 }
 
 impl MyOracleState {
-    pub fn save_rows(&mut self, rows: &[OracleDataWithTradingSymbol]) -> anchor_lang::Result<()> {
-        for row in rows.iter() {
-            match row.symbol {
-                TradingSymbol::Bsol_sol => {
-                    self.bsol_sol = row.data.into();
-                }
+    fn position(&self, symbol: TradingSymbol) -> Option<usize> {
+        if symbol == TradingSymbol::Unknown {
+            return None;
+        }
+        self.feeds.iter().position(|slot| slot.symbol == symbol)
+    }
+
+    fn slot(&self, symbol: TradingSymbol) -> anchor_lang::Result<&OracleSlot> {
+        match self.position(symbol) {
+            Some(i) => Ok(&self.feeds[i]),
+            None => Err(error!(USDY_USDC_ORACLEError::InvalidSymbol)),
+        }
+    }
+
+    /// Latest push for `symbol`, rejected if it is older than the feed's max age or its
+    /// sources disagree by more than the feed's max std.
+    pub fn get(&self, symbol: TradingSymbol, now: i64) -> anchor_lang::Result<OracleData> {
+        let slot = self.slot(symbol)?;
+        let data = slot.data;
+        if data.is_stale(now, slot.max_age_secs) {
+            msg!("{:?} is stale", symbol);
+            return Err(error!(USDY_USDC_ORACLEError::StaleData));
+        }
+        if data.too_dispersed(slot.max_std_bpm) {
+            msg!("{:?} std is too wide", symbol);
+            return Err(error!(USDY_USDC_ORACLEError::PriceDeviation));
+        }
+        Ok(data)
+    }
+
+    pub fn bsol_sol(&self, now: i64) -> anchor_lang::Result<OracleData> {
+        self.get(TradingSymbol::Bsol_sol, now)
+    }
+
+    pub fn jitosol_sol(&self, now: i64) -> anchor_lang::Result<OracleData> {
+        self.get(TradingSymbol::Jitosol_sol, now)
+    }
 
-                TradingSymbol::Jitosol_sol => {
-                    self.jitosol_sol = row.data.into();
+    pub fn wsol_borrow(&self, now: i64) -> anchor_lang::Result<OracleData> {
+        self.get(TradingSymbol::Wsol_borrow, now)
+    }
+
+    /// Time-weighted mean of `symbol` over the last `window_secs`. The latest push still has
+    /// to pass the staleness and dispersion checks of `get`.
+    pub fn get_twap(&self, symbol: TradingSymbol, window_secs: i64, now: i64) -> anchor_lang::Result<u64> {
+        self.get(symbol, now)?;
+        self.slot(symbol)?
+            .twap(window_secs, now)
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::StaleData))
+    }
+
+    /// TWAP of `symbol` over the feed's own configured window.
+    pub fn feed_twap(&self, symbol: TradingSymbol, now: i64) -> anchor_lang::Result<u64> {
+        let window_secs = self.slot(symbol)?.twap_window_secs;
+        self.get_twap(symbol, window_secs, now)
+    }
+
+    pub fn bsol_sol_twap(&self, now: i64) -> anchor_lang::Result<u64> {
+        self.feed_twap(TradingSymbol::Bsol_sol, now)
+    }
+
+    pub fn configure(&mut self, params: &ConfigureOracleFeedParams) -> anchor_lang::Result<()> {
+        let i = self
+            .position(params.symbol)
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::InvalidSymbol))?;
+        let slot = &mut self.feeds[i];
+        slot.max_age_secs = params.max_age_secs;
+        slot.max_deviation_bpm = params.max_deviation_bpm;
+        slot.max_std_bpm = params.max_std_bpm;
+        slot.allow_next_jump = params.allow_next_jump as u8;
+        slot.twap_window_secs = params.twap_window_secs;
+
+        Ok(())
+    }
+
+    pub fn register(&mut self, symbol: TradingSymbol) -> anchor_lang::Result<()> {
+        if symbol == TradingSymbol::Unknown {
+            return Err(error!(USDY_USDC_ORACLEError::InvalidSymbol));
+        }
+        if self.position(symbol).is_some() {
+            return Err(error!(USDY_USDC_ORACLEError::SymbolAlreadyRegistered));
+        }
+        let free = self
+            .position_free()
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::ArrayOverflow))?;
+        self.feeds[free] = OracleSlot {
+            symbol,
+            data: OracleData::zeroed(),
+            max_age_secs: DEFAULT_MAX_AGE_SECS,
+            max_deviation_bpm: DEFAULT_MAX_DEVIATION_BPM,
+            max_std_bpm: DEFAULT_MAX_STD_BPM,
+            allow_next_jump: 0,
+            twap_window_secs: DEFAULT_TWAP_WINDOW_SECS,
+            history: [PriceObservation::zeroed(); ORACLE_HISTORY_LEN],
+            history_head: 0,
+        };
+
+        Ok(())
+    }
+
+    pub fn retire(&mut self, symbol: TradingSymbol) -> anchor_lang::Result<()> {
+        let i = self
+            .position(symbol)
+            .ok_or_else(|| error!(USDY_USDC_ORACLEError::InvalidSymbol))?;
+        self.feeds[i] = OracleSlot::zeroed();
+
+        Ok(())
+    }
+
+    fn position_free(&self) -> Option<usize> {
+        self.feeds
+            .iter()
+            .position(|slot| slot.symbol == TradingSymbol::Unknown)
+    }
+
+    /// Mean of the last push for `symbol`, 0 before the first one.
+    pub fn latest_mean(&self, symbol: TradingSymbol) -> u64 {
+        self.position(symbol).map_or(0, |i| self.feeds[i].data.mean)
+    }
+
+    /// Saves every row pushed at or before `now` (plus MAX_CLOCK_SKEW_SECS). A row stamped
+    /// later would stay fresh past its max age and sit ahead of every later push in the TWAP.
+    /// Each row must also be newer than the feed's last push, so a replayed or reordered push
+    /// can't roll the price back or rewrite the TWAP history.
+    pub fn save_rows(&mut self, rows: &[OracleDataWithTradingSymbol], now: i64) -> anchor_lang::Result<()> {
+        for row in rows.iter() {
+            if row.data.oracle_timestamp > now.saturating_add(MAX_CLOCK_SKEW_SECS) {
+                msg!("{:?} is stamped {}, now is {}", row.symbol, row.data.oracle_timestamp, now);
+                return Err(error!(USDY_USDC_ORACLEError::FutureOracleTimestamp));
+            }
+            match self.position(row.symbol) {
+                Some(i) => {
+                    let slot = &mut self.feeds[i];
+                    let last_timestamp = slot.data.oracle_timestamp;
+                    if last_timestamp != 0 && row.data.oracle_timestamp <= last_timestamp {
+                        msg!("{:?} is stamped {}, last push was {}", row.symbol, row.data.oracle_timestamp, last_timestamp);
+                        return Err(error!(USDY_USDC_ORACLEError::StaleOracleTimestamp));
+                    }
+                    let previous = slot.data.mean;
+                    let data: OracleData = row.data.into();
+                    // the first push has nothing to compare against
+                    if previous != 0
+                        && slot.allow_next_jump == 0
+                        && data.deviates_from(previous, slot.max_deviation_bpm)
+                    {
+                        msg!("{:?} moved from {} to {}", row.symbol, previous, row.data.mean);
+                        return Err(error!(USDY_USDC_ORACLEError::PriceDeviation));
+                    }
+                    slot.data = data;
+                    slot.allow_next_jump = 0;
+                    slot.push_observation(row.data.oracle_timestamp, row.data.mean);
+                    emit!(OracleRefreshed {
+                        symbol: row.symbol,
+                        mean: row.data.mean,
+                        median: row.data.median,
+                        std: row.data.std,
+                        oracle_timestamp: row.data.oracle_timestamp,
+                        slot: Clock::get()?.slot,
+                    });
                 }
-                _ => {
+                None => {
                     msg!("no trading symbol found for {:?}", row.symbol);
-                    // TODO: emit an event so we can detect and fix
+                    emit!(UnknownOracleSymbol {
+                        symbol: row.symbol,
+                        oracle_timestamp: row.data.oracle_timestamp,
+                        slot: Clock::get()?.slot,
+                    });
                 }
             }
         }
@@ -99,6 +365,13 @@ pub enum TradingSymbol {
     Unknown = 0,
     Bsol_sol = 1,
     Jitosol_sol = 2,
+    Wsol_borrow = 3,
+    Msol_sol = 4,
+    Inf_sol = 5,
+    /// What one pool token redeems for at its stake pool, after withdrawal fees
+    Bsol_redemption = 6,
+    Jitosol_redemption = 7,
+    Msol_redemption = 8,
 }
 
 unsafe impl Pod for TradingSymbol {}
@@ -109,6 +382,12 @@ impl From<TradingSymbol> for u8 {
         match value {
             TradingSymbol::Bsol_sol => 1,
             TradingSymbol::Jitosol_sol => 2,
+            TradingSymbol::Wsol_borrow => 3,
+            TradingSymbol::Msol_sol => 4,
+            TradingSymbol::Inf_sol => 5,
+            TradingSymbol::Bsol_redemption => 6,
+            TradingSymbol::Jitosol_redemption => 7,
+            TradingSymbol::Msol_redemption => 8,
             _ => 0,
         }
     }
@@ -118,7 +397,97 @@ impl From<u8> for TradingSymbol {
         match value {
             1 => TradingSymbol::Bsol_sol,
             2 => TradingSymbol::Jitosol_sol,
+            3 => TradingSymbol::Wsol_borrow,
+            4 => TradingSymbol::Msol_sol,
+            5 => TradingSymbol::Inf_sol,
+            6 => TradingSymbol::Bsol_redemption,
+            7 => TradingSymbol::Jitosol_redemption,
+            8 => TradingSymbol::Msol_redemption,
             _ => TradingSymbol::Unknown,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(symbol: TradingSymbol, oracle_timestamp: i64, mean: u64) -> OracleDataWithTradingSymbol {
+        OracleDataWithTradingSymbol {
+            symbol,
+            data: OracleDataBorsh {
+                oracle_timestamp,
+                mean,
+                median: mean,
+                std: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn save_rows_rejects_future_timestamps() {
+        let mut oracle = MyOracleState::zeroed();
+        oracle.register(TradingSymbol::Bsol_sol).unwrap();
+        let now = 1_700_000_000;
+        let ahead = row(TradingSymbol::Bsol_sol, now + MAX_CLOCK_SKEW_SECS + 1, 1_100_000_000);
+        assert!(oracle.save_rows(&[ahead], now).is_err());
+        assert_eq!(oracle.latest_mean(TradingSymbol::Bsol_sol), 0);
+    }
+
+    #[test]
+    fn save_rows_rejects_timestamps_not_after_the_last_push() {
+        let mut oracle = MyOracleState::zeroed();
+        oracle.register(TradingSymbol::Bsol_sol).unwrap();
+        let now = 1_700_000_000;
+        let i = oracle.position(TradingSymbol::Bsol_sol).unwrap();
+        oracle.feeds[i].data = row(TradingSymbol::Bsol_sol, now, 1_100_000_000).data.into();
+        let replayed = row(TradingSymbol::Bsol_sol, now, 1_101_000_000);
+        assert!(oracle.save_rows(&[replayed], now).is_err());
+        let older = row(TradingSymbol::Bsol_sol, now - 1, 1_101_000_000);
+        assert!(oracle.save_rows(&[older], now).is_err());
+        assert_eq!(oracle.latest_mean(TradingSymbol::Bsol_sol), 1_100_000_000);
+    }
+
+    fn slot(observations: &[(i64, u64)]) -> OracleSlot {
+        let mut slot = OracleSlot::zeroed();
+        for &(timestamp, price) in observations {
+            slot.push_observation(timestamp, price);
+        }
+        slot
+    }
+
+    #[test]
+    fn twap_without_observations() {
+        assert_eq!(slot(&[]).twap(60, 1_000), None);
+    }
+
+    #[test]
+    fn twap_holds_each_observation_until_the_next() {
+        // 10 for 60s, then 20 for 40s
+        assert_eq!(slot(&[(100, 10), (160, 20)]).twap(100, 200), Some(14));
+        // only the last 50s, all of it at the single observation
+        assert_eq!(slot(&[(100, 10)]).twap(50, 200), Some(10));
+    }
+
+    #[test]
+    fn twap_starts_at_the_oldest_observation_when_history_is_short() {
+        // 10 for 30s, 40 for 20s, the window reaches far past both
+        assert_eq!(slot(&[(150, 10), (180, 40)]).twap(1_000, 200), Some(22));
+    }
+
+    #[test]
+    fn twap_falls_back_to_the_latest_push_without_elapsed_time() {
+        assert_eq!(slot(&[(200, 50)]).twap(60, 200), Some(50));
+    }
+
+    #[test]
+    fn twap_reads_the_ring_buffer_after_wrapping() {
+        let observations: Vec<(i64, u64)> = (1..=ORACLE_HISTORY_LEN as u64 + 2)
+            .map(|i| (10 * i as i64, i))
+            .collect();
+        let slot = slot(&observations);
+        assert_eq!(slot.history_head as usize, 2);
+        // 17 over [170, 180) and 16 over [160, 170)
+        assert_eq!(slot.twap(20, 180), Some(16));
+    }
+}
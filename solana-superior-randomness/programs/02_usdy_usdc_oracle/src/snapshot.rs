@@ -0,0 +1,315 @@
+use crate::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::Discriminator;
+
+// A vault's holder snapshot lives in its own account: the header below, then `len` entries of
+// (token account, weight) sorted by token account, with no repeats and no zero weights.
+// sync_holder_snapshot writes every entry from the token account itself, and a holder's draw
+// range is [sum of the weights before it, + its weight), so the ranges tile [0, total weight)
+// by construction: nothing about them is taken from whoever posts the snapshot. The account
+// outlives the draw, the next one only has to sync the holders that changed.
+
+pub const HOLDER_SNAPSHOT_SEED: &[u8] = b"HOLDER_SNAPSHOT_V1";
+
+#[account(zero_copy(unsafe))]
+#[repr(packed)]
+pub struct HolderSnapshotAccount {
+    pub bump: u8,
+    /// Entries following the header
+    pub len: u32,
+}
+
+/// Offset of the first entry, discriminator included.
+pub const HOLDER_SNAPSHOT_HEADER_LEN: usize = 8 + std::mem::size_of::<HolderSnapshotAccount>();
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HolderEntry {
+    pub token_account: Pubkey,
+    /// Balance plus the transfer fees withheld in the account
+    pub weight: u64,
+}
+
+impl HolderEntry {
+    pub const LEN: usize = 32 + 8;
+
+    fn read(bytes: &[u8]) -> Self {
+        Self {
+            token_account: Pubkey::new_from_array(bytes[..32].try_into().unwrap()),
+            weight: u64::from_le_bytes(bytes[32..Self::LEN].try_into().unwrap()),
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8]) {
+        bytes[..32].copy_from_slice(self.token_account.as_ref());
+        bytes[32..Self::LEN].copy_from_slice(&self.weight.to_le_bytes());
+    }
+}
+
+/// Read-only view of a holder snapshot's entries. Works on the borrowed account data, the
+/// entries never have to fit on the heap.
+#[derive(Copy, Clone)]
+pub struct HolderEntries<'a> {
+    entries: &'a [u8],
+}
+
+impl<'a> HolderEntries<'a> {
+    /// `data` is the whole account, discriminator and header included.
+    pub fn new(data: &'a [u8]) -> anchor_lang::Result<Self> {
+        if data.len() < HOLDER_SNAPSHOT_HEADER_LEN {
+            return Err(anchor_lang::error::ErrorCode::AccountDidNotDeserialize.into());
+        }
+        if data[..8] != HolderSnapshotAccount::discriminator() {
+            return Err(anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into());
+        }
+        let header: HolderSnapshotAccount = bytemuck::pod_read_unaligned(&data[8..HOLDER_SNAPSHOT_HEADER_LEN]);
+        Self::from_entries(&data[HOLDER_SNAPSHOT_HEADER_LEN..], header.len as usize)
+    }
+
+    /// The first `len` entries of `entries`, the account data past the header.
+    pub fn from_entries(entries: &'a [u8], len: usize) -> anchor_lang::Result<Self> {
+        let end = len
+            .checked_mul(HolderEntry::LEN)
+            .filter(|end| *end <= entries.len())
+            .ok_or_else(|| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?;
+        Ok(Self { entries: &entries[..end] })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() / HolderEntry::LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<HolderEntry> {
+        self.entries
+            .get(index * HolderEntry::LEN..(index + 1) * HolderEntry::LEN)
+            .map(HolderEntry::read)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = HolderEntry> + 'a {
+        self.entries.chunks_exact(HolderEntry::LEN).map(HolderEntry::read)
+    }
+
+    /// Index of `token_account`, or where it would be inserted.
+    pub fn search(&self, token_account: &Pubkey) -> std::result::Result<usize, usize> {
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.get(mid).unwrap().token_account.cmp(token_account) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(lo)
+    }
+
+    /// Weight `token_account` was synced at, 0 if it has no entry.
+    pub fn weight_of(&self, token_account: &Pubkey) -> u64 {
+        self.search(token_account)
+            .map_or(0, |index| self.get(index).unwrap().weight)
+    }
+
+    /// Fails unless the entries are strictly ordered by token account and all carry weight,
+    /// which is what keeps every holder to a single range.
+    pub fn verify(&self) -> anchor_lang::Result<()> {
+        let mut previous: Option<Pubkey> = None;
+        for entry in self.iter() {
+            if entry.weight == 0 || matches!(previous, Some(previous) if previous >= entry.token_account) {
+                msg!("snapshot entry {} is out of order, repeated or empty", entry.token_account);
+                return Err(error!(USDY_USDC_ORACLEError::InvalidHolderSnapshot));
+            }
+            previous = Some(entry.token_account);
+        }
+        Ok(())
+    }
+
+    pub fn total_weight(&self) -> anchor_lang::Result<u64> {
+        self.iter().try_fold(0, |total, entry| checked_add(total, entry.weight))
+    }
+
+    /// Start of the draw range of the entry at `index`, the weight of every entry before it.
+    pub fn range_start(&self, index: usize) -> anchor_lang::Result<u64> {
+        self.iter()
+            .take(index)
+            .try_fold(0, |total, entry| checked_add(total, entry.weight))
+    }
+
+    /// Entry whose draw range holds `draw`.
+    pub fn find(&self, draw: u64) -> Option<usize> {
+        let mut range_start: u64 = 0;
+        for (index, entry) in self.iter().enumerate() {
+            range_start = range_start.checked_add(entry.weight)?;
+            if draw < range_start {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Commitment to the entries, recorded on the vault when the snapshot is posted.
+    pub fn hash(&self) -> [u8; 32] {
+        hashv(&[self.entries]).to_bytes()
+    }
+}
+
+/// Sets `entry` in the `len` entries at the start of `entries`, inserting it in order or, for a
+/// zero weight, removing it. Returns the new length; `entries` has to have room for one more
+/// entry whenever `entry` is new.
+pub fn upsert_holder(entries: &mut [u8], len: usize, entry: HolderEntry) -> anchor_lang::Result<usize> {
+    let found = HolderEntries::from_entries(entries, len)?.search(&entry.token_account);
+    let at = |index: usize| index * HolderEntry::LEN;
+    match (found, entry.weight) {
+        (Ok(index), 0) => {
+            entries.copy_within(at(index + 1)..at(len), at(index));
+            Ok(len - 1)
+        }
+        (Ok(index), _) => {
+            entry.write(&mut entries[at(index)..at(index + 1)]);
+            Ok(len)
+        }
+        (Err(_), 0) => Ok(len),
+        (Err(index), _) => {
+            if entries.len() < at(len + 1) {
+                return Err(error!(anchor_lang::error::ErrorCode::AccountDidNotSerialize));
+            }
+            entries.copy_within(at(index)..at(len), at(index + 1));
+            entry.write(&mut entries[at(index)..at(index + 1)]);
+            Ok(len + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Account data with room for `capacity` entries, synced from `holders` in that order.
+    fn account(holders: &[(Pubkey, u64)], capacity: usize) -> Vec<u8> {
+        let mut data = vec![0; HOLDER_SNAPSHOT_HEADER_LEN + capacity * HolderEntry::LEN];
+        data[..8].copy_from_slice(&HolderSnapshotAccount::discriminator());
+        let mut len = 0;
+        for &(token_account, weight) in holders {
+            len = upsert_holder(&mut data[HOLDER_SNAPSHOT_HEADER_LEN..], len, HolderEntry { token_account, weight }).unwrap();
+        }
+        set_len(&mut data, len);
+        data
+    }
+
+    fn set_len(data: &mut [u8], len: usize) {
+        data[9..HOLDER_SNAPSHOT_HEADER_LEN].copy_from_slice(&(len as u32).to_le_bytes());
+    }
+
+    fn holders(weights: &[u64]) -> Vec<(Pubkey, u64)> {
+        weights.iter().map(|weight| (Pubkey::new_unique(), *weight)).collect()
+    }
+
+    #[test]
+    fn upsert_keeps_one_sorted_entry_per_holder() {
+        let mut input = holders(&[5, 7, 11, 13]);
+        input.reverse();
+        let (first, _) = input[0];
+        // syncing a holder again replaces its entry
+        input.push((first, 8));
+        let data = account(&input, 8);
+        let entries = HolderEntries::new(&data).unwrap();
+
+        assert_eq!(entries.len(), 4);
+        entries.verify().unwrap();
+        assert_eq!(entries.weight_of(&first), 8);
+        assert_eq!(entries.total_weight().unwrap(), 8 + 7 + 11 + 13);
+    }
+
+    #[test]
+    fn zero_weight_removes_the_holder() {
+        let input = holders(&[3, 4, 5]);
+        let (emptied, _) = input[1];
+        let mut synced = input.clone();
+        synced.push((emptied, 0));
+        // a holder that never had an entry isn't added either
+        synced.push((Pubkey::new_unique(), 0));
+        let data = account(&synced, 4);
+        let entries = HolderEntries::new(&data).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries.weight_of(&emptied), 0);
+        assert_eq!(entries.total_weight().unwrap(), 8);
+    }
+
+    #[test]
+    fn insert_needs_room() {
+        let mut data = account(&holders(&[1, 2]), 2);
+        let entry = HolderEntry { token_account: Pubkey::new_unique(), weight: 3 };
+        assert!(upsert_holder(&mut data[HOLDER_SNAPSHOT_HEADER_LEN..], 2, entry).is_err());
+    }
+
+    #[test]
+    fn ranges_tile_the_total_weight() {
+        let data = account(&holders(&[3, 4, 5]), 3);
+        let entries = HolderEntries::new(&data).unwrap();
+        let total = entries.total_weight().unwrap();
+        assert_eq!(total, 12);
+
+        let mut next = 0;
+        for (index, entry) in entries.iter().enumerate() {
+            assert_eq!(entries.range_start(index).unwrap(), next);
+            next += entry.weight;
+        }
+        for draw in 0..total {
+            let index = entries.find(draw).unwrap();
+            let range_start = entries.range_start(index).unwrap();
+            assert!(draw >= range_start && draw < range_start + entries.get(index).unwrap().weight);
+        }
+        assert_eq!(entries.find(total), None);
+    }
+
+    #[test]
+    fn overlapping_entries_are_rejected() {
+        // the same token account twice would own two ranges
+        let (token_account, _) = holders(&[1])[0];
+        let mut data = account(&[], 2);
+        for (index, weight) in [5u64, 7].into_iter().enumerate() {
+            let offset = HOLDER_SNAPSHOT_HEADER_LEN + index * HolderEntry::LEN;
+            HolderEntry { token_account, weight }.write(&mut data[offset..offset + HolderEntry::LEN]);
+        }
+        set_len(&mut data, 2);
+        let entries = HolderEntries::new(&data).unwrap();
+        assert!(entries.verify().is_err());
+
+        // so would entries out of order, and an empty entry owns no range at all
+        let mut input = holders(&[5, 7]);
+        input.sort_by(|a, b| b.0.cmp(&a.0));
+        let mut data = account(&[], 2);
+        for (index, &(token_account, weight)) in input.iter().enumerate() {
+            let offset = HOLDER_SNAPSHOT_HEADER_LEN + index * HolderEntry::LEN;
+            HolderEntry { token_account, weight }.write(&mut data[offset..offset + HolderEntry::LEN]);
+        }
+        set_len(&mut data, 2);
+        assert!(HolderEntries::new(&data).unwrap().verify().is_err());
+
+        let mut data = account(&holders(&[5, 7]), 2);
+        let offset = HOLDER_SNAPSHOT_HEADER_LEN + HolderEntry::LEN;
+        data[offset + 32..offset + HolderEntry::LEN].copy_from_slice(&0u64.to_le_bytes());
+        assert!(HolderEntries::new(&data).unwrap().verify().is_err());
+    }
+
+    #[test]
+    fn len_past_the_data_is_rejected() {
+        let mut data = account(&holders(&[1, 2]), 2);
+        set_len(&mut data, 3);
+        assert!(HolderEntries::new(&data).is_err());
+    }
+
+    #[test]
+    fn hash_commits_to_every_entry() {
+        let input = holders(&[5, 7]);
+        let data = account(&input, 2);
+        let hash = HolderEntries::new(&data).unwrap().hash();
+
+        let mut changed = input.clone();
+        changed.push((input[0].0, 6));
+        assert_ne!(HolderEntries::new(&account(&changed, 2)).unwrap().hash(), hash);
+    }
+}
@@ -0,0 +1,27 @@
+//! Prints, as JSON, the register_oracle_symbol instructions the program authority has to sign
+//! before the configured feeds can be pushed. initialize only registers Bsol/Jitosol and their
+//! redemption rows, so every other feed (an Msol feed, say) needs this once. Reads the config
+//! like the function does, from FUNCTION_CONFIG:
+//!
+//!     FUNCTION_CONFIG="$(cat config.json)" cargo run --bin register_symbols -- <authority>
+use balancer_oracle_function::*;
+use std::str::FromStr;
+
+#[tokio::main]
+async fn main() {
+    let authority = std::env::args()
+        .nth(1)
+        .and_then(|authority| Pubkey::from_str(&authority).ok())
+        .unwrap_or_else(|| exit("usage: register_symbols <authority>", 2));
+    let config = Config::load(&[]).unwrap_or_else(|e| exit(e, 2));
+    let io = Io::live(&config.rpc_url);
+
+    let ixns = register_symbol_ixns(&config, authority, &io).await.unwrap_or_else(|e| exit(e, 1));
+    let ixns: Vec<InstructionJson> = ixns.iter().map(InstructionJson::from).collect();
+    println!("{}", serde_json::to_string_pretty(&ixns).unwrap());
+}
+
+fn exit(error: impl std::fmt::Debug, code: i32) -> ! {
+    eprintln!("{:?}", error);
+    std::process::exit(code)
+}
@@ -0,0 +1,247 @@
+// The oracle function's config. function_config reads it, from the container params or
+// FUNCTION_CONFIG, with RPC_URL standing in for a left out `rpc_url`:
+//
+// {
+//   "rpc_url": "https://api.devnet.solana.com",
+//   "ws_url": "wss://api.devnet.solana.com",
+//   "program_id": "<superior_randomness program id>",
+//   "vault_seed": "<thewinnerog the vault PDA is derived from>",
+//   "sol_reserve": "<Solend wSOL reserve>",
+//   "feeds": [
+//     { "symbol": "Bsol_sol", "redemption_symbol": "Bsol_redemption", "mint": "...", "stake_pool": "...", "pyth": "..." },
+//     { "symbol": "Jitosol_sol", "redemption_symbol": "Jitosol_redemption", "mint": "...", "stake_pool": "...", "pyth": "..." },
+//     { "symbol": "Msol_sol", "redemption_symbol": "Msol_redemption", "mint": "...", "stake_pool": "<Marinade State>", "pyth": "...", "kind": "marinade" }
+//   ]
+// }
+//
+// A feed's `pyth` is the pool token's USD price account, `kind` is "spl" (the default) or
+// "marinade". Both of a feed's symbols need a feed slot on-chain before its rows are kept, see
+// bin/register_symbols.rs.
+use crate::*;
+use function_config::{config_error, parse_pubkey, read_config, validate_url};
+use serde::Deserialize;
+use superior_randomness::client::VaultAddresses;
+use superior_randomness::{PoolKind, TradingSymbol};
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    rpc_url: Option<String>,
+    ws_url: Option<String>,
+    program_id: Option<String>,
+    vault_seed: Option<String>,
+    sol_reserve: Option<String>,
+    #[serde(default)]
+    feeds: Vec<RawFeed>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFeed {
+    symbol: String,
+    redemption_symbol: String,
+    mint: String,
+    stake_pool: String,
+    pyth: String,
+    kind: Option<String>,
+}
+
+/// One LST priced by the function.
+#[derive(Clone, Copy, Debug)]
+pub struct LstFeed {
+    /// Market price row, aggregated across Jupiter, the stake pool and Pyth
+    pub symbol: TradingSymbol,
+    /// Stake pool redemption value row
+    pub redemption_symbol: TradingSymbol,
+    pub mint: Pubkey,
+    /// SPL stake pool, or Marinade's State for a Marinade feed
+    pub stake_pool: Pubkey,
+    /// Pyth USD price account of the pool token
+    pub pyth_price_account: Pubkey,
+    pub kind: PoolKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub rpc_url: String,
+    /// Defaults to rpc_url
+    pub ws_url: String,
+    pub program_id: Pubkey,
+    pub vault: VaultAddresses,
+    /// Solend wSOL reserve, source of the borrow rate and the SOL Pyth feed
+    pub sol_reserve: Pubkey,
+    pub feeds: Vec<LstFeed>,
+}
+
+impl Config {
+    /// Reads and validates the config, failing on the first missing or malformed field.
+    pub fn load(params: &[u8]) -> std::result::Result<Config, SbError> {
+        let raw: RawConfig = read_config(params)?;
+        let rpc_url = function_config::rpc_url(raw.rpc_url)?;
+        let ws_url = raw.ws_url.unwrap_or_else(|| rpc_url.clone());
+        validate_url("ws_url", &ws_url, &["http://", "https://", "ws://", "wss://"])?;
+        let program_id = parse_pubkey("program_id", raw.program_id)?;
+        let vault = VaultAddresses::new(program_id, parse_pubkey("vault_seed", raw.vault_seed)?);
+        let sol_reserve = parse_pubkey("sol_reserve", raw.sol_reserve)?;
+
+        let mut feeds: Vec<LstFeed> = Vec::with_capacity(raw.feeds.len());
+        for feed in raw.feeds {
+            let feed = LstFeed {
+                symbol: parse_symbol(&feed.symbol)?,
+                redemption_symbol: parse_symbol(&feed.redemption_symbol)?,
+                mint: parse_pubkey("feeds.mint", Some(feed.mint))?,
+                stake_pool: parse_pubkey("feeds.stake_pool", Some(feed.stake_pool))?,
+                pyth_price_account: parse_pubkey("feeds.pyth", Some(feed.pyth))?,
+                kind: parse_kind(feed.kind.as_deref())?,
+            };
+            let taken = feeds.iter().any(|other| {
+                [other.symbol, other.redemption_symbol].contains(&feed.symbol)
+                    || [other.symbol, other.redemption_symbol].contains(&feed.redemption_symbol)
+            });
+            if taken || feed.symbol == feed.redemption_symbol {
+                return Err(config_error(format!("feed {:?} repeats a symbol", feed.symbol)));
+            }
+            feeds.push(feed);
+        }
+        // the market rows open with a fixed bsol, jitosol, borrow rate triple, a run that can't
+        // price either of the two fails while any other feed is skipped
+        for symbol in [TradingSymbol::Bsol_sol, TradingSymbol::Jitosol_sol] {
+            if !feeds.iter().any(|feed| feed.symbol == symbol) {
+                return Err(config_error(format!("feeds has no {:?} entry", symbol)));
+            }
+        }
+
+        Ok(Config {
+            rpc_url,
+            ws_url,
+            program_id,
+            vault,
+            sol_reserve,
+            feeds,
+        })
+    }
+
+    pub fn cluster(&self) -> anchor_client::Cluster {
+        anchor_client::Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+
+    pub fn feed(&self, symbol: TradingSymbol) -> Option<&LstFeed> {
+        self.feeds.iter().find(|feed| feed.symbol == symbol)
+    }
+}
+
+/// Symbols are spelled like the TradingSymbol variants, e.g. "Bsol_sol".
+fn parse_symbol(value: &str) -> std::result::Result<TradingSymbol, SbError> {
+    (1..=u8::MAX)
+        .map(TradingSymbol::from)
+        .take_while(|symbol| *symbol != TradingSymbol::Unknown)
+        .find(|symbol| format!("{:?}", symbol) == value)
+        .filter(|symbol| *symbol != TradingSymbol::Wsol_borrow)
+        .ok_or_else(|| config_error(format!("{} is not an LST symbol", value)))
+}
+
+fn parse_kind(value: Option<&str>) -> std::result::Result<PoolKind, SbError> {
+    match value {
+        None | Some("spl") => Ok(PoolKind::SplStakePool),
+        Some("marinade") => Ok(PoolKind::Marinade),
+        Some(value) => Err(config_error(format!("{} is not a pool kind, use spl or marinade", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn feed(symbol: &str, redemption_symbol: &str) -> Value {
+        json!({
+            "symbol": symbol,
+            "redemption_symbol": redemption_symbol,
+            "mint": Pubkey::new_unique().to_string(),
+            "stake_pool": Pubkey::new_unique().to_string(),
+            "pyth": Pubkey::new_unique().to_string(),
+        })
+    }
+
+    fn config() -> Value {
+        json!({
+            "rpc_url": "http://localhost:8899",
+            "program_id": Pubkey::new_unique().to_string(),
+            "vault_seed": Pubkey::new_unique().to_string(),
+            "sol_reserve": Pubkey::new_unique().to_string(),
+            "feeds": [feed("Bsol_sol", "Bsol_redemption"), feed("Jitosol_sol", "Jitosol_redemption")],
+        })
+    }
+
+    fn load(config: &Value) -> std::result::Result<Config, String> {
+        Config::load(&serde_json::to_vec(config).unwrap()).map_err(|e| match e {
+            SbError::CustomMessage(message) => message,
+            e => format!("{:?}", e),
+        })
+    }
+
+    #[test]
+    fn loads_a_valid_config() {
+        let raw = config();
+        let mut marinade = feed("Msol_sol", "Msol_redemption");
+        marinade["kind"] = json!("marinade");
+        let mut with_marinade = raw.clone();
+        with_marinade["feeds"].as_array_mut().unwrap().push(marinade);
+
+        let config = load(&with_marinade).unwrap();
+        let program_id = Pubkey::from_str(raw["program_id"].as_str().unwrap()).unwrap();
+        let vault_seed = Pubkey::from_str(raw["vault_seed"].as_str().unwrap()).unwrap();
+        assert_eq!(config.program_id, program_id);
+        // the vault is derived under the configured deployment, not the one the crate declares
+        assert_eq!(config.vault.marginfi_pda, VaultAddresses::new(program_id, vault_seed).marginfi_pda);
+        assert_eq!(config.ws_url, "http://localhost:8899");
+        assert_eq!(config.feeds.len(), 3);
+        assert_eq!(config.feed(TradingSymbol::Bsol_sol).unwrap().kind, PoolKind::SplStakePool);
+        assert_eq!(config.feed(TradingSymbol::Msol_sol).unwrap().kind, PoolKind::Marinade);
+    }
+
+    #[test]
+    fn rejects_bad_fields() {
+        let cases: Vec<(&str, Value, &str)> = vec![
+            ("rpc_url", json!("localhost:8899"), "rpc_url must start with"),
+            ("ws_url", json!("ftp://localhost:8900"), "ws_url must start with"),
+            ("program_id", Value::Null, "program_id is missing"),
+            ("vault_seed", json!("vault"), "vault_seed is not a pubkey"),
+            ("sol_reserve", Value::Null, "sol_reserve is missing"),
+            ("unknown", json!(1), "not a valid config"),
+        ];
+        for (field, value, expected) in cases {
+            let mut raw = config();
+            if value.is_null() {
+                raw.as_object_mut().unwrap().remove(field);
+            } else {
+                raw[field] = value;
+            }
+            let error = load(&raw).unwrap_err();
+            assert!(error.contains(expected), "{}: {}", field, error);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_feeds() {
+        let mut raw = config();
+        raw["feeds"][0]["symbol"] = json!("Wsol_borrow");
+        assert!(load(&raw).unwrap_err().contains("Wsol_borrow is not an LST symbol"));
+
+        let mut raw = config();
+        raw["feeds"][1]["redemption_symbol"] = json!("Bsol_redemption");
+        assert!(load(&raw).unwrap_err().contains("repeats a symbol"));
+
+        let mut raw = config();
+        raw["feeds"][0]["kind"] = json!("lido");
+        assert!(load(&raw).unwrap_err().contains("lido is not a pool kind"));
+
+        let mut raw = config();
+        raw["feeds"].as_array_mut().unwrap().remove(1);
+        assert!(load(&raw).unwrap_err().contains("feeds has no Jitosol_sol entry"));
+
+        let mut raw = config();
+        raw["feeds"][0].as_object_mut().unwrap().remove("pyth");
+        assert!(load(&raw).unwrap_err().contains("not a valid config"));
+    }
+}
@@ -0,0 +1,194 @@
+// Recorded inputs for the simulate binary. A fixture holds the function config, the accounts
+// the function reads (in `solana account <pubkey> --output json` format), the HTTP responses by
+// URL and the slot, epoch and time to replay at, plus optionally the instructions a run has to
+// produce:
+//
+// {
+//   "function": "<function account>",
+//   "signer": "<enclave signer>",
+//   "config": { ...same JSON as the container params, see config.rs... },
+//   "unix_timestamp": 1700000000,
+//   "slot": 230000000,
+//   "epoch": 530,
+//   "accounts": [ { "pubkey": "...", "account": { "lamports": 1, "data": ["<base64>", "base64"], "owner": "...", "executable": false, "rentEpoch": 0 } } ],
+//   "http": { "https://quote-api.jup.ag/v6/quote?...": { "outAmount": "1130000000" } },
+//   "expected": [ { "program_id": "...", "accounts": [ { "pubkey": "...", "is_signer": false, "is_writable": true } ], "data": "<base64>" } ]
+// }
+use crate::*;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use switchboard_solana::solana_sdk::account::Account;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    pub function: String,
+    pub signer: String,
+    pub config: serde_json::Value,
+    pub unix_timestamp: i64,
+    pub slot: u64,
+    pub epoch: u64,
+    #[serde(default)]
+    pub accounts: Vec<AccountDump>,
+    #[serde(default)]
+    pub http: HashMap<String, serde_json::Value>,
+    pub expected: Option<Vec<InstructionJson>>,
+}
+
+#[derive(Deserialize)]
+pub struct AccountDump {
+    pub pubkey: String,
+    pub account: UiAccountDump,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiAccountDump {
+    pub lamports: u64,
+    /// `[data, encoding]`, only base64 is accepted
+    pub data: (String, String),
+    pub owner: String,
+    pub executable: bool,
+    #[serde(default)]
+    pub rent_epoch: u64,
+}
+
+impl Fixture {
+    pub fn read(path: &str) -> std::result::Result<Fixture, SbError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| fixture_error(format!("can't read {}: {}", path, e)))?;
+        serde_json::from_str(&json).map_err(|e| fixture_error(format!("{} is not a fixture: {}", path, e)))
+    }
+
+    pub fn config(&self) -> std::result::Result<Config, SbError> {
+        let params = serde_json::to_vec(&self.config).map_err(|e| fixture_error(e.to_string()))?;
+        Config::load(&params)
+    }
+
+    pub fn keys(&self) -> std::result::Result<FunctionKeys, SbError> {
+        Ok(FunctionKeys {
+            function: parse_pubkey(&self.function)?,
+            signer: parse_pubkey(&self.signer)?,
+        })
+    }
+
+    /// Io answering every read from the fixture.
+    pub fn io(&self) -> std::result::Result<Io, SbError> {
+        let mut accounts = HashMap::with_capacity(self.accounts.len());
+        for dump in &self.accounts {
+            let (data, encoding) = &dump.account.data;
+            if encoding != "base64" {
+                return Err(fixture_error(format!("{} data is {}, not base64", dump.pubkey, encoding)));
+            }
+            let account = Account {
+                lamports: dump.account.lamports,
+                data: BASE64
+                    .decode(data)
+                    .map_err(|e| fixture_error(format!("{} data: {}", dump.pubkey, e)))?,
+                owner: parse_pubkey(&dump.account.owner)?,
+                executable: dump.account.executable,
+                rent_epoch: dump.account.rent_epoch,
+            };
+            accounts.insert(parse_pubkey(&dump.pubkey)?, account);
+        }
+        let replay = Arc::new(Replay {
+            accounts,
+            http: self.http.clone(),
+            unix_timestamp: self.unix_timestamp,
+            slot: self.slot,
+            epoch: self.epoch,
+        });
+        Ok(Io {
+            rpc: replay.clone(),
+            http: replay.clone(),
+            time: replay,
+        })
+    }
+}
+
+pub struct Replay {
+    accounts: HashMap<Pubkey, Account>,
+    http: HashMap<String, serde_json::Value>,
+    unix_timestamp: i64,
+    slot: u64,
+    epoch: u64,
+}
+
+#[async_trait]
+impl Rpc for Replay {
+    async fn get_account(&self, key: &Pubkey) -> std::result::Result<Account, SbError> {
+        self.accounts
+            .get(key)
+            .cloned()
+            .ok_or_else(|| fixture_error(format!("account {} not found", key)))
+    }
+
+    async fn get_slot(&self) -> std::result::Result<u64, SbError> {
+        Ok(self.slot)
+    }
+
+    async fn get_epoch(&self) -> std::result::Result<u64, SbError> {
+        Ok(self.epoch)
+    }
+}
+
+#[async_trait]
+impl Http for Replay {
+    async fn get_json(&self, url: &str) -> std::result::Result<serde_json::Value, SbError> {
+        self.http
+            .get(url)
+            .cloned()
+            .ok_or_else(|| fixture_error(format!("no response recorded for {}", url)))
+    }
+}
+
+impl TimeSource for Replay {
+    fn unix_timestamp(&self) -> std::result::Result<i64, SbError> {
+        Ok(self.unix_timestamp)
+    }
+}
+
+/// An instruction as printed by, and compared against in, the simulate binary.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InstructionJson {
+    pub program_id: String,
+    pub accounts: Vec<AccountMetaJson>,
+    /// base64
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AccountMetaJson {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl From<&Instruction> for InstructionJson {
+    fn from(ixn: &Instruction) -> Self {
+        InstructionJson {
+            program_id: ixn.program_id.to_string(),
+            accounts: ixn
+                .accounts
+                .iter()
+                .map(|meta| AccountMetaJson {
+                    pubkey: meta.pubkey.to_string(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: BASE64.encode(&ixn.data),
+        }
+    }
+}
+
+fn fixture_error(message: String) -> SbError {
+    SbError::CustomMessage(format!("fixture: {}", message))
+}
+
+fn parse_pubkey(value: &str) -> std::result::Result<Pubkey, SbError> {
+    Pubkey::from_str(value).map_err(|_| fixture_error(format!("{} is not a pubkey", value)))
+}
@@ -0,0 +1,307 @@
+use anchor_client::solana_sdk::program_pack::Pack;
+use solend_sdk::state::Reserve;
+use switchboard_solana::solana_sdk::commitment_config::CommitmentConfig;
+use std::sync::Arc;
+pub use switchboard_solana::prelude::*;
+pub mod config;
+pub mod etherprices;
+pub mod fixtures;
+pub mod network;
+pub mod sources;
+pub mod stake_pool;
+
+pub use config::*;
+pub use etherprices::*;
+pub use fixtures::*;
+pub use network::*;
+pub use sources::*;
+pub use stake_pool::*;
+use std::str::FromStr;
+use superior_randomness::client;
+use superior_randomness::{bpm_of, scaled_rate, to_amount, OracleDataBorsh, OracleDataWithTradingSymbol, Rounding, TradingSymbol};
+use switchboard_utils;
+use switchboard_utils::FromPrimitive;
+use switchboard_utils::SbError;
+use tokio;
+
+use ethers::types::I256;
+
+pub use superior_randomness::{id, ID};
+
+/// Markup on Solend's wSOL borrow rate, in BPM
+pub const BORROW_RATE_MARKUP_BPM: u64 = 1_135_000;
+
+/// Reads every feed through `io` and builds the instructions the function emits: the
+/// refresh_oracles push followed by the vault's auto buy.
+pub async fn oracle_ixns(
+    config: &Config,
+    keys: FunctionKeys,
+    io: &Io,
+) -> std::result::Result<Vec<Instruction>, SbError> {
+    let reserve_data = io.rpc.get_account_data(&config.sol_reserve).await?;
+    let wsol_reserve = Reserve::unpack(&reserve_data)
+        .map_err(|e| SbError::CustomMessage(format!("invalid wsol reserve {}: {}", config.sol_reserve, e)))?;
+
+    // the vault's circuit breaker only runs once its collateral pool exists
+    let vault = config.vault;
+    let bound = io.rpc.get_account_data(&vault.collateral_pool).await.is_ok();
+    let oracle_timestamp = io.time.unix_timestamp()?;
+
+    let mut prices = Vec::new();
+    let mut redemptions = Vec::new();
+    for &LstFeed { symbol, redemption_symbol, mint, stake_pool, pyth_price_account, kind } in &config.feeds {
+        let sources: Vec<Box<dyn PriceSource>> = vec![
+            Box::new(JupiterQuote { http: io.http.clone(), input_mint: mint }),
+            Box::new(StakePoolRate { rpc: io.rpc.clone(), stake_pool, kind }),
+            Box::new(PythPrice {
+                rpc: io.rpc.clone(),
+                price_account: pyth_price_account,
+                sol_price_account: wsol_reserve.liquidity.pyth_oracle_pubkey,
+            }),
+        ];
+        println!("{:?}", symbol);
+        // only bsol and jitosol are required, any other feed that can't be priced goes stale
+        match fetch_aggregate(&sources).await {
+            Ok(aggregate) => prices.push((symbol, aggregate)),
+            Err(e) => println!("{:?} skipped: {:?}", symbol, e),
+        }
+
+        // a missing redemption row only leaves that feed stale, the market rows still go out
+        match fetch_redemption(&*io.rpc, &stake_pool, kind, oracle_timestamp).await {
+            Ok(data) => redemptions.push(OracleDataWithTradingSymbol { symbol: redemption_symbol, data }),
+            Err(e) => println!("{:?} skipped: {:?}", redemption_symbol, e),
+        }
+    }
+    let price = |symbol: TradingSymbol| {
+        prices
+            .iter()
+            .find(|(feed, _)| *feed == symbol)
+            .map(|(_, aggregate)| *aggregate)
+            .ok_or_else(|| SbError::CustomMessage(format!("no {:?} price", symbol)))
+    };
+    let (bsol, jitosol) = (price(TradingSymbol::Bsol_sol)?, price(TradingSymbol::Jitosol_sol)?);
+    let markets = prices
+        .iter()
+        .filter(|(symbol, _)| ![TradingSymbol::Bsol_sol, TradingSymbol::Jitosol_sol].contains(symbol))
+        .map(|&(symbol, aggregate)| OracleDataWithTradingSymbol {
+            symbol,
+            data: OracleDataBorsh {
+                oracle_timestamp,
+                mean: aggregate.mean as u64,
+                median: aggregate.median as u64,
+                std: aggregate.std as u64,
+            },
+        })
+        .collect();
+
+    // Solend's current borrow rate runs below what the wSOL leg actually costs (4.99% against
+    // 5.66%), so it is marked up before it is pushed
+    let reserve_borrow_rate = wsol_reserve
+        .current_borrow_rate()
+        .map_err(|e| SbError::CustomMessage(format!("wsol reserve borrow rate: {}", e)))?;
+    let reserve_borrow_rate = scaled_rate(reserve_borrow_rate)
+        .and_then(|rate| bpm_of(rate, BORROW_RATE_MARKUP_BPM))
+        .and_then(|rate| to_amount(rate, Rounding::Down))
+        .map_err(math_error)?;
+    println!("reserve_borrow_rate: {:?}", reserve_borrow_rate);
+
+
+    // Finally, emit the signed quote and partially signed transaction t    o the functionRunner oracle
+    // The functionRunner oracle will use the last outputted word to stdout as the serialized result. This is what gets executed on-chain.
+    let mut etherprices = EtherPrices::fetch(
+        // implement error handling and map_err
+        ethers::types::U256::from(bsol.mean as u128),
+        ethers::types::U256::from(bsol.median as u128),
+        ethers::types::U256::from(bsol.std as u128),
+        ethers::types::U256::from(jitosol.mean as u128),
+        ethers::types::U256::from(jitosol.median as u128),
+        ethers::types::U256::from(jitosol.std as u128),
+        ethers::types::U256::from(reserve_borrow_rate),
+        ethers::types::U256::from(reserve_borrow_rate),
+        ethers::types::U256::zero(),
+        oracle_timestamp,
+    )
+    .await?;
+    etherprices.markets = markets;
+    etherprices.redemptions = redemptions;
+
+    etherprices.to_ixns(keys, &vault, bound)
+}
+
+/// register_oracle_symbol instructions for every configured symbol the oracle has no feed slot
+/// for yet, for `authority` to sign. Pushes for those symbols are otherwise dropped on-chain.
+pub async fn register_symbol_ixns(
+    config: &Config,
+    authority: Pubkey,
+    io: &Io,
+) -> std::result::Result<Vec<Instruction>, SbError> {
+    let data = io.rpc.get_account_data(&client::oracle_address(&config.program_id)).await?;
+    let oracle = client::decode_oracle(&data)
+        .map_err(|e| SbError::CustomMessage(format!("invalid oracle account: {}", e)))?;
+    let registered = |symbol: TradingSymbol| oracle.feeds.iter().any(|slot| { slot.symbol } == symbol);
+    Ok(config
+        .feeds
+        .iter()
+        .flat_map(|feed| [feed.symbol, feed.redemption_symbol])
+        .filter(|symbol| !registered(*symbol))
+        .map(|symbol| {
+            println!("{:?} is not registered", symbol);
+            client::register_oracle_symbol(&config.program_id, authority, symbol)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use superior_randomness::RefreshOraclesParams;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/oracle.json");
+
+    fn rows(ixn: &Instruction) -> Vec<OracleDataWithTradingSymbol> {
+        RefreshOraclesParams::try_from_slice(&ixn.data[8..]).unwrap().rows
+    }
+
+    #[tokio::test]
+    async fn pushes_every_feed_from_the_fixture() {
+        let fixture = Fixture::read(FIXTURE).unwrap();
+        let config = fixture.config().unwrap();
+        let ixns = oracle_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await.unwrap();
+
+        assert_eq!(ixns.len(), 2);
+        let refresh = &ixns[0];
+        assert_eq!(refresh.program_id, config.program_id);
+        // the fixture has no collateral pool, so no vault rides along for the circuit breaker
+        assert_eq!(refresh.accounts.len(), 4);
+        let rows = rows(refresh);
+        let symbols: Vec<TradingSymbol> = rows.iter().map(|row| row.symbol).collect();
+        assert_eq!(
+            symbols,
+            [
+                TradingSymbol::Bsol_sol,
+                TradingSymbol::Jitosol_sol,
+                TradingSymbol::Wsol_borrow,
+                TradingSymbol::Bsol_redemption,
+                TradingSymbol::Jitosol_redemption,
+            ]
+        );
+        // Jupiter, the stake pool and Pyth all agree, so the median is the middle source
+        assert_eq!(rows[0].data.median, 1_120_000_000);
+        assert_eq!(rows[1].data.median, 1_101_000_000);
+        // 4% at 40% utilization, marked up by BORROW_RATE_MARKUP_BPM
+        assert_eq!(rows[2].data.mean, 45_400_000);
+        // one pool token less the 0.1% withdrawal fee
+        assert_eq!(rows[3].data.mean, 1_118_880_000);
+        assert_eq!(rows[4].data.mean, 1_099_899_000);
+        assert!(rows.iter().all(|row| row.data.oracle_timestamp == fixture.unix_timestamp));
+        assert_eq!(ixns[1].program_id, config.program_id);
+    }
+
+    /// Adds an Msol_sol feed priced from bsol's Jupiter quote and stake pool, with a copy of
+    /// bsol's Pyth account last published `age` slots before the fixture's slot.
+    fn add_extra_feed(fixture: &mut Fixture, age: u64) {
+        let bsol = fixture.config["feeds"][0].clone();
+        let pyth = Pubkey::new_unique();
+        let source = fixture.accounts.iter().find(|dump| dump.pubkey == bsol["pyth"].as_str().unwrap()).unwrap();
+        let mut data = BASE64.decode(&source.account.data.0).unwrap();
+        // prev_slot and the aggregate's pub_slot, the two slots the staleness check reads
+        let published = (fixture.slot - age).to_le_bytes();
+        data[176..184].copy_from_slice(&published);
+        data[232..240].copy_from_slice(&published);
+        fixture.accounts.push(AccountDump {
+            pubkey: pyth.to_string(),
+            account: UiAccountDump {
+                lamports: source.account.lamports,
+                data: (BASE64.encode(data), "base64".to_string()),
+                owner: source.account.owner.clone(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        });
+        let mut feed = bsol;
+        feed["symbol"] = "Msol_sol".into();
+        feed["redemption_symbol"] = "Msol_redemption".into();
+        feed["pyth"] = pyth.to_string().into();
+        fixture.config["feeds"].as_array_mut().unwrap().push(feed);
+    }
+
+    #[tokio::test]
+    async fn pushes_a_healthy_extra_feed() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        add_extra_feed(&mut fixture, 10);
+        let config = fixture.config().unwrap();
+        let ixns = oracle_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await.unwrap();
+
+        let rows = rows(&ixns[0]);
+        let symbols: Vec<TradingSymbol> = rows.iter().map(|row| row.symbol).collect();
+        assert_eq!(
+            symbols,
+            [
+                TradingSymbol::Bsol_sol,
+                TradingSymbol::Jitosol_sol,
+                TradingSymbol::Wsol_borrow,
+                TradingSymbol::Msol_sol,
+                TradingSymbol::Bsol_redemption,
+                TradingSymbol::Jitosol_redemption,
+                TradingSymbol::Msol_redemption,
+            ]
+        );
+        assert_eq!(rows[3].data.median, rows[0].data.median);
+    }
+
+    #[tokio::test]
+    async fn skips_an_extra_feed_with_a_stale_pyth_price() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        add_extra_feed(&mut fixture, 1_000);
+        let config = fixture.config().unwrap();
+        let ixns = oracle_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await.unwrap();
+
+        // Jupiter and the stake pool alone are short of MIN_SOURCES, so only the market row is
+        // dropped; the redemption row reads the stake pool and still goes out
+        assert_eq!(ixns.len(), 2);
+        let rows = rows(&ixns[0]);
+        let symbols: Vec<TradingSymbol> = rows.iter().map(|row| row.symbol).collect();
+        assert_eq!(
+            symbols,
+            [
+                TradingSymbol::Bsol_sol,
+                TradingSymbol::Jitosol_sol,
+                TradingSymbol::Wsol_borrow,
+                TradingSymbol::Bsol_redemption,
+                TradingSymbol::Jitosol_redemption,
+                TradingSymbol::Msol_redemption,
+            ]
+        );
+        assert_eq!(rows[0].data.median, 1_120_000_000);
+    }
+
+    #[tokio::test]
+    async fn stale_required_feed_is_an_error() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        let pyth = fixture.config["feeds"][1]["pyth"].as_str().unwrap().to_string();
+        let dump = fixture.accounts.iter_mut().find(|dump| dump.pubkey == pyth).unwrap();
+        let mut data = BASE64.decode(&dump.account.data.0).unwrap();
+        let published = (fixture.slot - 1_000).to_le_bytes();
+        data[176..184].copy_from_slice(&published);
+        data[232..240].copy_from_slice(&published);
+        dump.account.data.0 = BASE64.encode(data);
+        let config = fixture.config().unwrap();
+
+        let result = oracle_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await;
+        assert!(matches!(result, Err(SbError::CustomMessage(message)) if message == "no Jitosol_sol price"));
+    }
+
+    #[tokio::test]
+    async fn bad_reserve_is_an_error() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        let config = fixture.config().unwrap();
+        let reserve = config.sol_reserve.to_string();
+        let dump = fixture.accounts.iter_mut().find(|dump| dump.pubkey == reserve).unwrap();
+        dump.account.data.0 = String::new();
+
+        let result = oracle_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await;
+        assert!(matches!(result, Err(SbError::CustomMessage(message)) if message.starts_with("invalid wsol reserve")));
+    }
+}
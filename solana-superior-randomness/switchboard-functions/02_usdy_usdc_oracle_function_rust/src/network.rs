@@ -0,0 +1,114 @@
+// Everything the function reads from outside the enclave. Live runs go to the cluster, Jupiter
+// and the system clock; the simulate binary replays a fixture through the same traits instead
+// (see fixtures.rs).
+use crate::*;
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use async_trait::async_trait;
+use switchboard_solana::solana_sdk::account::Account;
+
+#[async_trait]
+pub trait Rpc: Send + Sync {
+    async fn get_account(&self, key: &Pubkey) -> std::result::Result<Account, SbError>;
+
+    async fn get_slot(&self) -> std::result::Result<u64, SbError>;
+
+    async fn get_epoch(&self) -> std::result::Result<u64, SbError>;
+
+    async fn get_account_data(&self, key: &Pubkey) -> std::result::Result<Vec<u8>, SbError> {
+        Ok(self.get_account(key).await?.data)
+    }
+}
+
+#[async_trait]
+impl Rpc for RpcClient {
+    async fn get_account(&self, key: &Pubkey) -> std::result::Result<Account, SbError> {
+        RpcClient::get_account(self, key)
+            .await
+            .map_err(|e| SbError::CustomMessage(format!("{} fetch failed: {}", key, e)))
+    }
+
+    async fn get_slot(&self) -> std::result::Result<u64, SbError> {
+        RpcClient::get_slot(self)
+            .await
+            .map_err(|e| SbError::CustomMessage(format!("slot fetch failed: {}", e)))
+    }
+
+    async fn get_epoch(&self) -> std::result::Result<u64, SbError> {
+        Ok(RpcClient::get_epoch_info(self)
+            .await
+            .map_err(|e| SbError::CustomMessage(format!("epoch fetch failed: {}", e)))?
+            .epoch)
+    }
+}
+
+#[async_trait]
+pub trait Http: Send + Sync {
+    async fn get_json(&self, url: &str) -> std::result::Result<serde_json::Value, SbError>;
+}
+
+pub struct LiveHttp;
+
+#[async_trait]
+impl Http for LiveHttp {
+    async fn get_json(&self, url: &str) -> std::result::Result<serde_json::Value, SbError> {
+        reqwest::get(url)
+            .await
+            .map_err(|e| SbError::CustomMessage(format!("{} request failed: {}", url, e)))?
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| SbError::CustomMessage(format!("{} response invalid: {}", url, e)))
+    }
+}
+
+/// Stamps the pushed rows.
+pub trait TimeSource: Send + Sync {
+    fn unix_timestamp(&self) -> std::result::Result<i64, SbError>;
+}
+
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn unix_timestamp(&self) -> std::result::Result<i64, SbError> {
+        Ok(std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| SbError::CustomMessage("Invalid oracle_timestamp".to_string()))?
+            .as_secs() as i64)
+    }
+}
+
+#[derive(Clone)]
+pub struct Io {
+    pub rpc: Arc<dyn Rpc>,
+    pub http: Arc<dyn Http>,
+    pub time: Arc<dyn TimeSource>,
+}
+
+impl Io {
+    pub fn live(rpc_url: &str) -> Io {
+        Io {
+            rpc: Arc::new(RpcClient::new_with_commitment(
+                rpc_url.to_string(),
+                CommitmentConfig::processed(),
+            )),
+            http: Arc::new(LiveHttp),
+            time: Arc::new(SystemClock),
+        }
+    }
+}
+
+/// The function account and enclave signer the instructions are built for, taken from the
+/// FunctionRunner on live runs.
+#[derive(Clone, Copy, Debug)]
+pub struct FunctionKeys {
+    pub function: Pubkey,
+    pub signer: Pubkey,
+}
+
+impl From<&FunctionRunner> for FunctionKeys {
+    fn from(runner: &FunctionRunner) -> Self {
+        FunctionKeys {
+            function: runner.function,
+            signer: runner.signer,
+        }
+    }
+}
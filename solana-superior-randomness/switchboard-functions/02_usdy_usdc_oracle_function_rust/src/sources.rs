@@ -0,0 +1,212 @@
+// Price sources for the LST feeds. Every source quotes lamports per whole pool token, scaled by
+// 1e9 like the on-chain feeds, so their results can be aggregated directly.
+use crate::*;
+use async_trait::async_trait;
+use solend_sdk::math::Decimal;
+use spl_stake_pool::solana_program::borsh0_10::try_from_slice_unchecked;
+use spl_stake_pool::state::StakePool;
+use superior_randomness::{marinade_msol_price, pool_price, price_ratio, PoolKind};
+use switchboard_solana::solana_sdk::account_info::IntoAccountInfo;
+
+pub const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+
+/// Sources further than this from the median of all sources are dropped before aggregating.
+pub const MAX_SOURCE_DEVIATION: f64 = 0.02;
+/// Prices needed before aggregating. With two the median is their midpoint, so a bad source
+/// can't be told from a good one.
+pub const MIN_SOURCES: usize = 3;
+/// Prices that have to survive the deviation filter, so one source never sets the feed alone.
+pub const MIN_AGREEING_SOURCES: usize = 2;
+
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> String;
+
+    /// Lamports per pool token, scaled by 1e9.
+    async fn fetch(&self) -> std::result::Result<f64, SbError>;
+}
+
+/// Jupiter's quote for swapping one whole pool token to SOL.
+pub struct JupiterQuote {
+    pub http: Arc<dyn Http>,
+    pub input_mint: Pubkey,
+}
+
+#[async_trait]
+impl PriceSource for JupiterQuote {
+    fn name(&self) -> String {
+        format!("jupiter {}", self.input_mint)
+    }
+
+    async fn fetch(&self) -> std::result::Result<f64, SbError> {
+        let url = format!(
+            "https://quote-api.jup.ag/v6/quote?inputMint={}&outputMint={}&amount=1000000000",
+            self.input_mint, WSOL_MINT
+        );
+        let quote = self.http.get_json(&url).await?;
+        quote["outAmount"]
+            .as_str()
+            .and_then(|out_amount| out_amount.parse().ok())
+            .ok_or_else(|| SbError::CustomMessage("jupiter quote has no outAmount".to_string()))
+    }
+}
+
+/// The stake pool's own exchange rate, `total_lamports / pool_token_supply`, or Marinade's
+/// mSOL price.
+pub struct StakePoolRate {
+    pub rpc: Arc<dyn Rpc>,
+    pub stake_pool: Pubkey,
+    pub kind: PoolKind,
+}
+
+#[async_trait]
+impl PriceSource for StakePoolRate {
+    fn name(&self) -> String {
+        format!("stake pool {}", self.stake_pool)
+    }
+
+    async fn fetch(&self) -> std::result::Result<f64, SbError> {
+        let data = self.rpc.get_account_data(&self.stake_pool).await?;
+        if self.kind == PoolKind::Marinade {
+            return Ok(marinade_msol_price(&data).map_err(math_error)? as f64);
+        }
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&data)
+            .map_err(|_| SbError::CustomMessage("invalid stake pool account".to_string()))?;
+        if stake_pool.pool_token_supply == 0 {
+            return Err(SbError::CustomMessage("stake pool is empty".to_string()));
+        }
+        let price = pool_price(stake_pool.total_lamports, stake_pool.pool_token_supply).map_err(math_error)?;
+        Ok(price as f64)
+    }
+}
+
+/// Pyth publishes LSTs against USD, so this divides the pool token's price account by SOL's.
+/// Staleness and confidence are checked the way Solend checks them.
+pub struct PythPrice {
+    pub rpc: Arc<dyn Rpc>,
+    pub price_account: Pubkey,
+    pub sol_price_account: Pubkey,
+}
+
+impl PythPrice {
+    async fn usd_price(&self, price_account: &Pubkey, clock: &Clock) -> std::result::Result<Decimal, SbError> {
+        let mut account = self.rpc.get_account(price_account).await?;
+        let account_info = (price_account, &mut account).into_account_info();
+        let (price, _) = solend_sdk::oracles::get_pyth_price(&account_info, clock)
+            .map_err(|e| SbError::CustomMessage(format!("pyth price rejected: {}", e)))?;
+        Ok(price)
+    }
+}
+
+#[async_trait]
+impl PriceSource for PythPrice {
+    fn name(&self) -> String {
+        format!("pyth {}", self.price_account)
+    }
+
+    async fn fetch(&self) -> std::result::Result<f64, SbError> {
+        // the staleness check only reads the slot
+        let clock = Clock {
+            slot: self.rpc.get_slot().await?,
+            ..Clock::default()
+        };
+        let price = self.usd_price(&self.price_account, &clock).await?;
+        let sol_price = self.usd_price(&self.sol_price_account, &clock).await?;
+        if sol_price == Decimal::zero() {
+            return Err(SbError::CustomMessage("pyth SOL price is zero".to_string()));
+        }
+        Ok(price_ratio(price, sol_price).map_err(math_error)? as f64)
+    }
+}
+
+/// The fixed-point helpers come from the program crate, so their errors are anchor errors.
+pub fn math_error(e: anchor_lang::error::Error) -> SbError {
+    SbError::CustomMessage(format!("math failed: {}", e))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aggregate {
+    pub mean: f64,
+    pub median: f64,
+    pub std: f64,
+    pub sources: usize,
+}
+
+/// Mean, median and population std of `prices` after dropping every price further than
+/// `max_deviation` (a fraction) from their median. None with fewer than MIN_SOURCES prices or
+/// fewer than MIN_AGREEING_SOURCES left.
+pub fn aggregate(prices: &[f64], max_deviation: f64) -> Option<Aggregate> {
+    if prices.len() < MIN_SOURCES {
+        return None;
+    }
+    let median = statistical::median(prices);
+    let kept: Vec<f64> = prices
+        .iter()
+        .copied()
+        .filter(|price| median > 0.0 && ((price - median) / median).abs() <= max_deviation)
+        .collect();
+    if kept.len() < MIN_AGREEING_SOURCES {
+        return None;
+    }
+    let mean = statistical::mean(&kept);
+    let std = statistical::population_standard_deviation(&kept, Some(mean));
+    Some(Aggregate {
+        mean,
+        median: statistical::median(&kept),
+        std,
+        sources: kept.len(),
+    })
+}
+
+/// Queries every source, logging and skipping the ones that fail, and aggregates the rest.
+pub async fn fetch_aggregate(sources: &[Box<dyn PriceSource>]) -> std::result::Result<Aggregate, SbError> {
+    let mut prices = Vec::with_capacity(sources.len());
+    for source in sources {
+        match source.fetch().await {
+            Ok(price) => {
+                println!("{}: {}", source.name(), price);
+                prices.push(price);
+            }
+            Err(e) => println!("{} skipped: {:?}", source.name(), e),
+        }
+    }
+    let aggregate = aggregate(&prices, MAX_SOURCE_DEVIATION)
+        .ok_or_else(|| SbError::CustomMessage("no price source agreed".to_string()))?;
+    println!("aggregate: {:?}", aggregate);
+    Ok(aggregate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_the_outlier() {
+        let aggregate = aggregate(&[1.10e9, 1.11e9, 1.50e9], MAX_SOURCE_DEVIATION).unwrap();
+        assert_eq!(aggregate.sources, 2);
+        assert_eq!(aggregate.mean, 1.105e9);
+        assert_eq!(aggregate.median, 1.105e9);
+        assert_eq!(aggregate.std, 0.005e9);
+    }
+
+    #[test]
+    fn keeps_agreeing_sources() {
+        let aggregate = aggregate(&[1.1e9, 1.1e9, 1.1e9], MAX_SOURCE_DEVIATION).unwrap();
+        assert_eq!(aggregate.sources, 3);
+        assert_eq!(aggregate.mean, 1.1e9);
+        assert_eq!(aggregate.std, 0.0);
+    }
+
+    #[test]
+    fn needs_three_sources() {
+        assert_eq!(aggregate(&[], MAX_SOURCE_DEVIATION), None);
+        // the midpoint of two is within 2% of both, neither could be rejected
+        assert_eq!(aggregate(&[1.10e9, 1.13e9], MAX_SOURCE_DEVIATION), None);
+    }
+
+    #[test]
+    fn needs_two_agreeing_sources() {
+        assert_eq!(aggregate(&[1.0e9, 1.5e9, 2.0e9], MAX_SOURCE_DEVIATION), None);
+        assert_eq!(aggregate(&[0.0, 0.0, 0.0], MAX_SOURCE_DEVIATION), None);
+    }
+}
@@ -0,0 +1,134 @@
+// Fair value of a pool token read straight from its stake pool (or Marinade State) account,
+// instead of what a swap would fetch. Pushed under the *_redemption symbols so a vault can
+// price its collateral at redemption value by pointing its CollateralPool at them. initialize
+// only registers Bsol_redemption and Jitosol_redemption, the register_symbols binary prints the
+// register_oracle_symbol instructions any other configured feed needs.
+use crate::*;
+use spl_stake_pool::solana_program::borsh0_10::try_from_slice_unchecked;
+use spl_stake_pool::state::StakePool;
+use superior_randomness::{marinade_msol_price, OracleDataBorsh, PoolKind};
+
+/// One whole pool token, every supported LST has 9 decimals.
+pub const ONE_POOL_TOKEN: u64 = 1_000_000_000;
+
+/// Lamports one pool token redeems for at `epoch`.
+///
+/// Withdrawals are rejected until the pool has been updated for the current epoch, and that
+/// update is what activates a scheduled fee change. So when the pool is behind, the pending
+/// withdrawal fees are the ones a redemption would actually pay. The rewards the update will
+/// add are left out, which can only understate the value.
+///
+/// SOL withdrawals are capped by the reserve while stake withdrawals always go through, and
+/// their fees differ, so the lower of the two routes is used.
+pub fn redemption_value(stake_pool: &StakePool, epoch: u64) -> Option<u64> {
+    let mut stake_pool = stake_pool.clone();
+    if stake_pool.last_update_epoch < epoch {
+        if let Some(fee) = stake_pool.next_sol_withdrawal_fee.get() {
+            stake_pool.sol_withdrawal_fee = *fee;
+        }
+        if let Some(fee) = stake_pool.next_stake_withdrawal_fee.get() {
+            stake_pool.stake_withdrawal_fee = *fee;
+        }
+    }
+    let withdraw = |fee: Option<u64>| {
+        stake_pool.calc_lamports_withdraw_amount(ONE_POOL_TOKEN.checked_sub(fee?)?)
+    };
+    let sol = withdraw(stake_pool.calc_pool_tokens_sol_withdrawal_fee(ONE_POOL_TOKEN))?;
+    let stake = withdraw(stake_pool.calc_pool_tokens_stake_withdrawal_fee(ONE_POOL_TOKEN))?;
+    Some(sol.min(stake))
+}
+
+/// Fetches `stake_pool` and the current epoch and returns the redemption value as an oracle
+/// row. There is a single source, so mean and median are equal and std is zero. Marinade
+/// redeems mSOL through a delayed unstake at its mSOL price, which is what its row carries.
+pub async fn fetch_redemption(
+    rpc: &dyn Rpc,
+    stake_pool: &Pubkey,
+    kind: PoolKind,
+    oracle_timestamp: i64,
+) -> std::result::Result<OracleDataBorsh, SbError> {
+    let data = rpc.get_account_data(stake_pool).await?;
+    if kind == PoolKind::Marinade {
+        let value = marinade_msol_price(&data).map_err(math_error)?;
+        println!("{} redemption: {} (marinade)", stake_pool, value);
+        return Ok(OracleDataBorsh {
+            oracle_timestamp,
+            mean: value,
+            median: value,
+            std: 0,
+        });
+    }
+    let pool = try_from_slice_unchecked::<StakePool>(&data)
+        .map_err(|_| SbError::CustomMessage("invalid stake pool account".to_string()))?;
+    let epoch = rpc.get_epoch().await?;
+    let value = redemption_value(&pool, epoch)
+        .filter(|value| *value > 0)
+        .ok_or_else(|| SbError::CustomMessage("stake pool has no redemption value".to_string()))?;
+    println!(
+        "{} redemption: {} (pool epoch {}, current {})",
+        stake_pool, value, pool.last_update_epoch, epoch
+    );
+    Ok(OracleDataBorsh {
+        oracle_timestamp,
+        mean: value,
+        median: value,
+        std: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_stake_pool::state::{Fee, FutureEpoch};
+
+    fn fee(numerator: u64) -> Fee {
+        Fee { numerator, denominator: 1_000 }
+    }
+
+    /// 1.1 SOL per pool token, updated at epoch 500, 0.1% SOL and 0.3% stake withdrawal fees.
+    fn pool() -> StakePool {
+        StakePool {
+            total_lamports: 1_100 * ONE_POOL_TOKEN,
+            pool_token_supply: 1_000 * ONE_POOL_TOKEN,
+            last_update_epoch: 500,
+            sol_withdrawal_fee: fee(1),
+            stake_withdrawal_fee: fee(3),
+            ..StakePool::default()
+        }
+    }
+
+    #[test]
+    fn takes_the_cheaper_route() {
+        assert_eq!(redemption_value(&pool(), 500), Some(1_096_700_000));
+        let pool = StakePool { stake_withdrawal_fee: fee(0), ..pool() };
+        assert_eq!(redemption_value(&pool, 500), Some(1_098_900_000));
+    }
+
+    #[test]
+    fn pending_fees_apply_once_the_pool_is_behind() {
+        let pool = StakePool {
+            next_sol_withdrawal_fee: FutureEpoch::One(fee(5)),
+            next_stake_withdrawal_fee: FutureEpoch::One(fee(10)),
+            ..pool()
+        };
+        // updated for this epoch, the current fees still apply
+        assert_eq!(redemption_value(&pool, 500), Some(1_096_700_000));
+        // behind, the update that unblocks withdrawals rolls the fees over first
+        assert_eq!(redemption_value(&pool, 501), Some(1_089_000_000));
+    }
+
+    #[test]
+    fn fees_scheduled_two_epochs_out_stay_pending() {
+        let pool = StakePool {
+            next_sol_withdrawal_fee: FutureEpoch::Two(fee(5)),
+            ..pool()
+        };
+        assert_eq!(redemption_value(&pool, 501), Some(1_096_700_000));
+    }
+
+    #[test]
+    fn empty_pool_redeems_nothing() {
+        let pool = StakePool { total_lamports: 0, pool_token_supply: 0, ..pool() };
+        assert_eq!(redemption_value(&pool, 500), Some(0));
+    }
+}
@@ -0,0 +1,88 @@
+//! Config loading shared by the Switchboard functions. Everything cluster specific lives in the
+//! config, so one image serves devnet, mainnet and a local validator.
+//!
+//! The config is JSON, taken from the container params or, when those are empty, from the
+//! FUNCTION_CONFIG env var. Params are stored on-chain, so an RPC URL carrying an API key belongs
+//! in RPC_URL instead, which is used whenever `rpc_url` is left out. Each function documents its
+//! own fields in its config.rs.
+use anchor_lang::prelude::Pubkey;
+use serde::de::DeserializeOwned;
+use std::str::FromStr;
+use switchboard_utils::SbError;
+
+pub const CONFIG_ENV: &str = "FUNCTION_CONFIG";
+pub const RPC_URL_ENV: &str = "RPC_URL";
+
+/// Parses the container params, or FUNCTION_CONFIG when there are none.
+pub fn read_config<T: DeserializeOwned>(params: &[u8]) -> Result<T, SbError> {
+    if !params.is_empty() {
+        serde_json::from_slice(params)
+            .map_err(|e| config_error(format!("container params are not a valid config: {}", e)))
+    } else if let Ok(json) = std::env::var(CONFIG_ENV) {
+        serde_json::from_str(&json).map_err(|e| config_error(format!("{} is not a valid config: {}", CONFIG_ENV, e)))
+    } else {
+        Err(config_error(format!("no container params and {} is unset", CONFIG_ENV)))
+    }
+}
+
+/// `rpc_url` from the config, falling back to RPC_URL.
+pub fn rpc_url(value: Option<String>) -> Result<String, SbError> {
+    let rpc_url = value
+        .or_else(|| std::env::var(RPC_URL_ENV).ok())
+        .ok_or_else(|| config_error(format!("rpc_url is unset and so is {}", RPC_URL_ENV)))?;
+    validate_url("rpc_url", &rpc_url, &["http://", "https://"])?;
+    Ok(rpc_url)
+}
+
+pub fn config_error(message: String) -> SbError {
+    SbError::CustomMessage(format!("invalid config: {}", message))
+}
+
+pub fn validate_url(field: &str, url: &str, schemes: &[&str]) -> Result<(), SbError> {
+    if !schemes.iter().any(|scheme| url.starts_with(scheme)) {
+        return Err(config_error(format!("{} must start with one of {:?}", field, schemes)));
+    }
+    Ok(())
+}
+
+pub fn parse_pubkey(field: &str, value: Option<String>) -> Result<Pubkey, SbError> {
+    let value = value.ok_or_else(|| config_error(format!("{} is missing", field)))?;
+    Pubkey::from_str(&value).map_err(|_| config_error(format!("{} is not a pubkey: {}", field, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(error: SbError) -> String {
+        match error {
+            SbError::CustomMessage(message) => message,
+            error => panic!("unexpected {:?}", error),
+        }
+    }
+
+    #[test]
+    fn urls_need_a_listed_scheme() {
+        assert!(validate_url("rpc_url", "https://api.devnet.solana.com", &["http://", "https://"]).is_ok());
+        let error = validate_url("ws_url", "https://api.devnet.solana.com", &["ws://", "wss://"]).unwrap_err();
+        assert_eq!(message(error), "invalid config: ws_url must start with one of [\"ws://\", \"wss://\"]");
+    }
+
+    #[test]
+    fn pubkeys_are_required_and_parsed() {
+        let key = Pubkey::new_unique();
+        assert_eq!(parse_pubkey("vault_seed", Some(key.to_string())).unwrap(), key);
+        assert_eq!(message(parse_pubkey("vault_seed", None).unwrap_err()), "invalid config: vault_seed is missing");
+        assert_eq!(
+            message(parse_pubkey("vault_seed", Some("vault".to_string())).unwrap_err()),
+            "invalid config: vault_seed is not a pubkey: vault"
+        );
+    }
+
+    #[test]
+    fn params_win_over_the_env() {
+        let value: serde_json::Value = read_config(br#"{"rpc_url":"http://localhost:8899"}"#).unwrap();
+        assert_eq!(value["rpc_url"], "http://localhost:8899");
+        assert!(read_config::<serde_json::Value>(b"{").is_err());
+    }
+}
@@ -0,0 +1,34 @@
+//! Runs the function offline against a recorded fixture (see fixtures.rs) and prints the
+//! instructions it would emit as JSON. When the fixture lists `expected` instructions the run
+//! fails unless they match exactly, so fixtures double as regression tests:
+//!
+//!     cargo run --bin simulate -- fixtures/draw.json
+use balancer_oracle_function::*;
+
+#[tokio::main]
+async fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| exit("usage: simulate <fixture.json>", 2));
+    let fixture = Fixture::read(&path).unwrap_or_else(|e| exit(e, 2));
+    let config = fixture.config().unwrap_or_else(|e| exit(e, 2));
+    let keys = fixture.keys().unwrap_or_else(|e| exit(e, 2));
+    let io = fixture.io().unwrap_or_else(|e| exit(e, 2));
+
+    let ixns = draw_ixns(&config, keys, &io).await.unwrap_or_else(|e| exit(e, 1));
+    let actual: Vec<InstructionJson> = ixns.iter().map(InstructionJson::from).collect();
+    println!("{}", serde_json::to_string_pretty(&actual).unwrap());
+
+    if let Some(expected) = &fixture.expected {
+        if *expected != actual {
+            eprintln!("expected:\n{}", serde_json::to_string_pretty(expected).unwrap());
+            exit("instructions differ from the fixture", 1);
+        }
+        eprintln!("instructions match the fixture");
+    }
+}
+
+fn exit(error: impl std::fmt::Debug, code: i32) -> ! {
+    eprintln!("{:?}", error);
+    std::process::exit(code)
+}
@@ -0,0 +1,96 @@
+// The savings game function's config. function_config reads it, from the container params or
+// FUNCTION_CONFIG, with RPC_URL standing in for a left out `rpc_url`:
+//
+// {
+//   "rpc_url": "https://api.devnet.solana.com",
+//   "ws_url": "wss://api.devnet.solana.com",
+//   "program_id": "<superior_randomness program id>",
+//   "vault_seed": "<thewinnerog the vault PDA is derived from>"
+// }
+//
+// The pool token the prize is paid in isn't configured, it is read from the vault's
+// CollateralPool.
+use crate::*;
+use function_config::{parse_pubkey, read_config, validate_url};
+use serde::Deserialize;
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    rpc_url: Option<String>,
+    ws_url: Option<String>,
+    program_id: Option<String>,
+    vault_seed: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub rpc_url: String,
+    /// Defaults to rpc_url
+    pub ws_url: String,
+    pub program_id: Pubkey,
+    pub vault: VaultAddresses,
+}
+
+impl Config {
+    /// Reads and validates the config, failing on the first missing or malformed field.
+    pub fn load(params: &[u8]) -> std::result::Result<Config, SbError> {
+        let raw: RawConfig = read_config(params)?;
+        let rpc_url = function_config::rpc_url(raw.rpc_url)?;
+        let ws_url = raw.ws_url.unwrap_or_else(|| rpc_url.clone());
+        validate_url("ws_url", &ws_url, &["http://", "https://", "ws://", "wss://"])?;
+        let program_id = parse_pubkey("program_id", raw.program_id)?;
+        let vault = VaultAddresses::new(program_id, parse_pubkey("vault_seed", raw.vault_seed)?);
+
+        Ok(Config {
+            rpc_url,
+            ws_url,
+            program_id,
+            vault,
+        })
+    }
+
+    pub fn cluster(&self) -> anchor_client::Cluster {
+        anchor_client::Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load(config: serde_json::Value) -> std::result::Result<Config, String> {
+        Config::load(&serde_json::to_vec(&config).unwrap()).map_err(|e| match e {
+            SbError::CustomMessage(message) => message,
+            e => format!("{:?}", e),
+        })
+    }
+
+    #[test]
+    fn derives_the_vault_under_the_configured_program() {
+        let (program_id, vault_seed) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let config = load(json!({
+            "rpc_url": "https://api.devnet.solana.com",
+            "program_id": program_id.to_string(),
+            "vault_seed": vault_seed.to_string(),
+        }))
+        .unwrap();
+        assert_eq!(config.vault.program_id, program_id);
+        assert_eq!(config.ws_url, "https://api.devnet.solana.com");
+        assert_eq!(config.vault.marginfi_pda, VaultAddresses::new(program_id, vault_seed).marginfi_pda);
+    }
+
+    #[test]
+    fn rejects_bad_fields() {
+        let program_id = Pubkey::new_unique().to_string();
+        let error = load(json!({ "rpc_url": "ws://localhost:8900", "program_id": program_id, "vault_seed": program_id }));
+        assert!(error.unwrap_err().contains("rpc_url must start with"));
+        let error = load(json!({ "rpc_url": "http://localhost:8899", "program_id": program_id }));
+        assert!(error.unwrap_err().contains("vault_seed is missing"));
+        let error = load(json!({ "rpc_url": "http://localhost:8899", "program_id": "program", "vault_seed": program_id }));
+        assert!(error.unwrap_err().contains("program_id is not a pubkey"));
+        let error = load(json!({ "rpc_url": "http://localhost:8899", "sol_reserve": program_id }));
+        assert!(error.unwrap_err().contains("not a valid config"));
+    }
+}
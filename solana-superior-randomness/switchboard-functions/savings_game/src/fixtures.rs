@@ -0,0 +1,215 @@
+// Recorded inputs for the simulate binary. A fixture holds the function config, the accounts
+// the function reads (in `solana account <pubkey> --output json` format), the time to replay at
+// and the enclave sealing key the draw key is derived from, plus optionally the instructions a
+// run has to produce:
+//
+// {
+//   "function": "<function account>",
+//   "signer": "<enclave signer>",
+//   "config": { ...same JSON as the container params, see config.rs... },
+//   "unix_timestamp": 1700000000,
+//   "sealing_key": "<base64>",
+//   "accounts": [ { "pubkey": "...", "account": { "lamports": 1, "data": ["<base64>", "base64"], "owner": "...", "executable": false, "rentEpoch": 0 } } ],
+//   "expected": [ { "program_id": "...", "accounts": [ { "pubkey": "...", "is_signer": false, "is_writable": true } ], "data": "<base64>" } ]
+// }
+//
+// getProgramAccounts is answered from the same dumps: every account owned by the program that
+// passes the filters, in pubkey order.
+use crate::*;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_filter::RpcFilterType;
+use std::collections::BTreeMap;
+use switchboard_solana::solana_sdk::account::Account;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    pub function: String,
+    pub signer: String,
+    pub config: serde_json::Value,
+    pub unix_timestamp: i64,
+    pub sealing_key: Option<String>,
+    #[serde(default)]
+    pub accounts: Vec<AccountDump>,
+    pub expected: Option<Vec<InstructionJson>>,
+}
+
+#[derive(Deserialize)]
+pub struct AccountDump {
+    pub pubkey: String,
+    pub account: UiAccountDump,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiAccountDump {
+    pub lamports: u64,
+    /// `[data, encoding]`, only base64 is accepted
+    pub data: (String, String),
+    pub owner: String,
+    pub executable: bool,
+    #[serde(default)]
+    pub rent_epoch: u64,
+}
+
+impl Fixture {
+    pub fn read(path: &str) -> std::result::Result<Fixture, SbError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| fixture_error(format!("can't read {}: {}", path, e)))?;
+        serde_json::from_str(&json).map_err(|e| fixture_error(format!("{} is not a fixture: {}", path, e)))
+    }
+
+    pub fn config(&self) -> std::result::Result<Config, SbError> {
+        let params = serde_json::to_vec(&self.config).map_err(|e| fixture_error(e.to_string()))?;
+        Config::load(&params)
+    }
+
+    pub fn keys(&self) -> std::result::Result<FunctionKeys, SbError> {
+        Ok(FunctionKeys {
+            function: parse_pubkey(&self.function)?,
+            signer: parse_pubkey(&self.signer)?,
+        })
+    }
+
+    /// Io answering every read from the fixture.
+    pub fn io(&self) -> std::result::Result<Io, SbError> {
+        let mut accounts = BTreeMap::new();
+        for dump in &self.accounts {
+            let (data, encoding) = &dump.account.data;
+            if encoding != "base64" {
+                return Err(fixture_error(format!("{} data is {}, not base64", dump.pubkey, encoding)));
+            }
+            let account = Account {
+                lamports: dump.account.lamports,
+                data: BASE64
+                    .decode(data)
+                    .map_err(|e| fixture_error(format!("{} data: {}", dump.pubkey, e)))?,
+                owner: parse_pubkey(&dump.account.owner)?,
+                executable: dump.account.executable,
+                rent_epoch: dump.account.rent_epoch,
+            };
+            accounts.insert(parse_pubkey(&dump.pubkey)?, account);
+        }
+        let sealing_key = self
+            .sealing_key
+            .as_ref()
+            .map(|sealing_key| BASE64.decode(sealing_key))
+            .transpose()
+            .map_err(|e| fixture_error(format!("sealing_key: {}", e)))?;
+        let replay = Arc::new(Replay {
+            accounts,
+            unix_timestamp: self.unix_timestamp,
+            sealing_key,
+        });
+        Ok(Io {
+            rpc: replay.clone(),
+            time: replay.clone(),
+            sealing_key: replay,
+        })
+    }
+}
+
+pub struct Replay {
+    accounts: BTreeMap<Pubkey, Account>,
+    unix_timestamp: i64,
+    sealing_key: Option<Vec<u8>>,
+}
+
+fn passes(filter: &RpcFilterType, data: &[u8]) -> bool {
+    match filter {
+        RpcFilterType::DataSize(size) => data.len() as u64 == *size,
+        RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(data),
+        _ => true,
+    }
+}
+
+#[async_trait]
+impl Rpc for Replay {
+    async fn get_account(&self, key: &Pubkey) -> std::result::Result<Account, SbError> {
+        self.accounts
+            .get(key)
+            .cloned()
+            .ok_or_else(|| fixture_error(format!("account {} not found", key)))
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> std::result::Result<Vec<(Pubkey, Account)>, SbError> {
+        let filters = config.filters.unwrap_or_default();
+        let data_slice = config.account_config.data_slice;
+        Ok(self
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .filter(|(_, account)| filters.iter().all(|filter| passes(filter, &account.data)))
+            .map(|(key, account)| {
+                let mut account = account.clone();
+                if let Some(slice) = data_slice {
+                    account.data = account.data.into_iter().skip(slice.offset).take(slice.length).collect();
+                }
+                (*key, account)
+            })
+            .collect())
+    }
+}
+
+impl TimeSource for Replay {
+    fn unix_timestamp(&self) -> std::result::Result<i64, SbError> {
+        Ok(self.unix_timestamp)
+    }
+}
+
+impl SealingKey for Replay {
+    fn read(&self) -> std::result::Result<Vec<u8>, SbError> {
+        self.sealing_key
+            .clone()
+            .ok_or_else(|| fixture_error("sealing_key was read but none is recorded".to_string()))
+    }
+}
+
+/// An instruction as printed by, and compared against in, the simulate binary.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InstructionJson {
+    pub program_id: String,
+    pub accounts: Vec<AccountMetaJson>,
+    /// base64
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AccountMetaJson {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl From<&Instruction> for InstructionJson {
+    fn from(ixn: &Instruction) -> Self {
+        InstructionJson {
+            program_id: ixn.program_id.to_string(),
+            accounts: ixn
+                .accounts
+                .iter()
+                .map(|meta| AccountMetaJson {
+                    pubkey: meta.pubkey.to_string(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: BASE64.encode(&ixn.data),
+        }
+    }
+}
+
+fn fixture_error(message: String) -> SbError {
+    SbError::CustomMessage(format!("fixture: {}", message))
+}
+
+fn parse_pubkey(value: &str) -> std::result::Result<Pubkey, SbError> {
+    Pubkey::from_str(value).map_err(|_| fixture_error(format!("{} is not a pubkey", value)))
+}
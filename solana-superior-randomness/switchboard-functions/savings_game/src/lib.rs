@@ -0,0 +1,548 @@
+use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use anchor_client::solana_sdk::program_pack::Pack;
+use spl_token_2022::extension::StateWithExtensions;
+
+use solana_account_decoder::UiDataSliceConfig;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use superior_randomness::client::{self, VaultAddresses};
+use superior_randomness::{
+    derive_randomness_result, draw_from_result, find_slot_hash, gross_jarezi_balance, CollateralPool,
+    HolderEntries, MarginFiPda, RequestAccountData, SetWinnerParams, prize_pool_address,
+};
+use switchboard_solana::Pubkey;
+
+pub use switchboard_solana::prelude::*;
+pub mod config;
+pub mod etherprices;
+pub mod fixtures;
+pub mod network;
+pub use solana_client::*;
+use std::sync::Arc;
+pub use switchboard_solana::get_ixn_discriminator;
+pub use switchboard_solana::prelude::*;
+pub use config::*;
+pub use etherprices::*;
+pub use fixtures::*;
+pub use network::*;
+use std::str::FromStr;
+use switchboard_utils;
+use switchboard_utils::SbError;
+use tokio;
+
+use ethers::types::I256;
+
+pub use superior_randomness::{id, ID};
+
+#[derive(Clone)]
+pub struct StakeProgram;
+
+impl anchor_lang::Id for StakeProgram {
+    fn id() -> Pubkey {
+        Pubkey::from_str("Stake11111111111111111111111111111111111111").unwrap()
+    }
+}
+
+#[derive(Clone)]
+pub struct SolendProgram;
+
+impl anchor_lang::Id for SolendProgram {
+    fn id() -> Pubkey {
+        Pubkey::from_str("So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo").unwrap()
+    }
+}
+
+// jarezi accounts harvested per draw, keeps the transaction under the size limit
+const MAX_FEE_SOURCES: usize = 16;
+// holder snapshot entries synced per transaction, for the same reason
+const MAX_SNAPSHOT_SYNC: usize = 16;
+
+pub struct Holder {
+    pub pubkey: Pubkey,
+    /// Draw weight: the balance plus the fees withheld in the account, see sync_holder_snapshot
+    pub amount: u64,
+    pub owner: Pubkey,
+    pub withheld: u64,
+}
+
+// The commit key is derived instead of drawn, so the run that reveals it, slots after the one
+// that committed it, gets the same key back. The request's seed and open time make it unique per
+// draw, and the sealing key keeps it out of reach of anyone outside the enclave.
+fn draw_key(
+    sealing_key: &dyn SealingKey,
+    request: &Pubkey,
+    request_account: &RequestAccountData,
+) -> std::result::Result<[u8; 32], SbError> {
+    let secret = sealing_key.read()?;
+    Ok(solana_program::hash::hashv(&[
+        b"jarezi-draw-key",
+        &secret,
+        request.as_ref(),
+        &{ request_account.seed }.to_le_bytes(),
+        &{ request_account.request_timestamp }.to_le_bytes(),
+    ])
+    .to_bytes())
+}
+
+// Newest slot in the SlotHashes sysvar data, 0 if it is empty.
+fn newest_slot(slot_hashes: &[u8]) -> u64 {
+    slot_hashes
+        .get(8..16)
+        .map(|slot| u64::from_le_bytes(slot.try_into().unwrap()))
+        .unwrap_or(0)
+}
+
+fn invalid(message: &str) -> SbError {
+    SbError::CustomMessage(message.to_string())
+}
+
+/// Reads the vault and its holders through `io` and builds the instructions for the next step
+/// of the draw: commit the snapshot and randomness, or, from a later slot, reveal and pick and
+/// pay the winner.
+pub async fn draw_ixns(
+    config: &Config,
+    keys: FunctionKeys,
+    io: &Io,
+) -> std::result::Result<Vec<Instruction>, SbError> {
+    // Define the accounts that will be passed to the function
+    let vault = config.vault;
+    let marginfi_pda = vault.marginfi_pda;
+    let marginfi_pda_data = io.rpc.get_account_data(&marginfi_pda).await?;
+    let marginfi_pda_account = MarginFiPda::try_deserialize(&mut &marginfi_pda_data[..])
+        .map_err(|_| invalid("invalid vault account"))?;
+
+    // Nothing to draw until the authority opens a randomness request
+    let request_data = match io.rpc.get_account_data(&vault.request).await {
+        Ok(data) => data,
+        Err(_) => {
+            println!("no open randomness request");
+            return Ok(vec![]);
+        }
+    };
+    let request_account = client::decode_request(&request_data)
+        .map_err(|_| invalid("invalid randomness request"))?;
+    let winner_winner_chickum_dinner = marginfi_pda_account.winner_winner_chickum_dinner;
+
+    let jarezis = io.rpc.get_program_accounts(
+        &anchor_spl::token_interface::Token2022::id(),
+        solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(vec![
+                solana_client::rpc_filter::RpcFilterType::Memcmp(solana_client::rpc_filter::Memcmp {
+                offset: 4,
+                bytes: solana_client::rpc_filter::MemcmpEncodedBytes::Binary(marginfi_pda.to_string()),
+                encoding: None
+            })]),account_config: RpcAccountInfoConfig {
+                min_context_slot: None,
+                encoding: Some(solana_account_decoder::UiAccountEncoding::Base64Zstd),
+                commitment: Some(CommitmentConfig::processed()),
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: spl_token_2022::state::Mint::LEN as usize
+                }),
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    ).await?;
+    let jarezi_mint = jarezis.first().ok_or_else(|| invalid("vault has no jarezi mint"))?.0;
+    println!("jarezi_mint: {:?}", jarezi_mint);
+    let jarezi_mint_data = io.rpc.get_account_data(&jarezi_mint).await?;
+    let token_supply = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&jarezi_mint_data)
+        .map_err(|_| invalid("invalid jarezi mint"))?
+        .base
+        .supply;
+
+    // the prize is backed by whichever stake pool this vault is bound to
+    let collateral_pool_data = io.rpc.get_account_data(&vault.collateral_pool).await?;
+    let collateral_pool = CollateralPool::try_deserialize(&mut &collateral_pool_data[..])
+        .map_err(|_| invalid("invalid collateral pool"))?;
+    println!("pool_mint: {:?}", collateral_pool.pool_mint);
+
+    println!("token_supply.amount {:?}", token_supply);
+
+    // the prize is paid per whole period of the on-chain schedule, nothing to draw before that
+    let now = io.time.unix_timestamp()?;
+    let periods = marginfi_pda_account.prize_periods(now);
+    println!("prize periods: {:?}", periods);
+    if periods == 0 {
+        return Ok(vec![]);
+    }
+
+    
+        
+    let holders = io.rpc.get_program_accounts(
+        &anchor_spl::token_interface::Token2022::id(),
+        solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(vec![
+                solana_client::rpc_filter::RpcFilterType::Memcmp(solana_client::rpc_filter::Memcmp {
+                offset: 0,
+                bytes: solana_client::rpc_filter::MemcmpEncodedBytes::Binary(jarezi_mint.to_string()),
+                encoding: None
+            })]),account_config: RpcAccountInfoConfig {
+                min_context_slot: None,
+                encoding: Some(solana_account_decoder::UiAccountEncoding::Base64Zstd),
+                commitment: Some(CommitmentConfig::processed()),
+                // whole accounts, the withheld fees sit in an extension past the base account
+                data_slice: None,
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    ).await?;
+    println!("holders: {:?}", holders.len());
+    println!("jarezimint: {:?}", jarezi_mint);
+    let mut parsed_holders: Vec<Holder> = Vec::with_capacity(holders.len());
+    for (pubkey, account) in holders {
+        let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .map_err(|_| invalid("invalid jarezi account"))?;
+        // the prize pool and anything else the vault holds never draws
+        if state.base.owner == marginfi_pda {
+            continue;
+        }
+        let amount = gross_jarezi_balance(&account.data).map_err(|_| invalid("invalid jarezi account"))?;
+        parsed_holders.push(Holder {
+            pubkey,
+            amount,
+            owner: state.base.owner,
+            withheld: amount - state.base.amount,
+        });
+    }
+    let holders = parsed_holders;
+
+    // The snapshot the draw runs over is kept on-chain, written only from the token accounts
+    // themselves. Holders whose live weight differs from their entry get synced before the
+    // commit, including entries whose account has since been closed or emptied.
+    let snapshot_data = io.rpc.get_account_data(&vault.holder_snapshot).await.ok();
+    let entries = snapshot_data
+        .as_deref()
+        .map(HolderEntries::new)
+        .transpose()
+        .map_err(|_| invalid("invalid holder snapshot"))?;
+    let mut stale: Vec<Pubkey> = holders
+        .iter()
+        .filter(|holder| entries.map_or(0, |entries| entries.weight_of(&holder.pubkey)) != holder.amount)
+        .map(|holder| holder.pubkey)
+        .collect();
+    if let Some(entries) = entries {
+        stale.extend(
+            entries
+                .iter()
+                .map(|entry| entry.token_account)
+                .filter(|token_account| holders.iter().all(|holder| holder.pubkey != *token_account)),
+        );
+    }
+
+    let key = draw_key(&*io.sealing_key, &vault.request, &request_account)?;
+    // Commit the holder snapshot and the randomness before anyone can know the draw. The result
+    // mixes in the hash of the slot the seed lands in, so the key is revealed by a later run.
+    if !request_account.is_seeded() {
+        if stale.len() > MAX_SNAPSHOT_SYNC {
+            println!("syncing {} of {} stale snapshot entries, committing next run", MAX_SNAPSHOT_SYNC, stale.len());
+            return Ok(vec![vault.sync_holder_snapshot(
+                jarezi_mint,
+                keys.function,
+                keys.signer,
+                &stale[..MAX_SNAPSHOT_SYNC],
+            )]);
+        }
+        let mut ixns = vec![];
+        // the first sync creates the account, even with nobody to write into it
+        if !stale.is_empty() || entries.is_none() {
+            ixns.push(vault.sync_holder_snapshot(jarezi_mint, keys.function, keys.signer, &stale));
+        }
+        let pubkey_hash = solana_program::hash::hashv(&[key.as_ref()]).to_bytes();
+        ixns.extend([
+            vault.post_holder_snapshot(jarezi_mint, keys.function, keys.signer),
+            vault.seed_randomness(keys.function, keys.signer, pubkey_hash),
+        ]);
+        return Ok(ixns);
+    }
+    // Seeded, so the snapshot is frozen until the draw closes the request. Later transfers only
+    // change who the winning entry pays out to.
+    let entries = entries.ok_or_else(|| invalid("request was seeded without a holder snapshot"))?;
+    let cancel_ixn = vault.cancel_randomness(marginfi_pda_account.authority, keys.signer, Some(keys.function));
+
+    let mut ixns: Vec<Instruction> = vec![];
+    let result = if request_account.is_revealed() {
+        request_account.result
+    } else {
+        let seed_slot = request_account.seed_slot;
+        let slot_hashes = io
+            .rpc
+            .get_account_data(&solana_program::sysvar::slot_hashes::ID)
+            .await?;
+        let blockhash = match find_slot_hash(&slot_hashes, seed_slot) {
+            Some(blockhash) => blockhash,
+            None if newest_slot(&slot_hashes) <= seed_slot => {
+                println!("seed slot {} has no hash yet, revealing next run", seed_slot);
+                return Ok(vec![]);
+            }
+            None => {
+                println!("seed slot {} left SlotHashes before the reveal, cancelling", seed_slot);
+                return Ok(vec![cancel_ixn]);
+            }
+        };
+        if solana_program::hash::hashv(&[key.as_ref()]).to_bytes() != request_account.pubkey_hash {
+            // seeded by this image on another CPU, whose sealing key derives a different key
+            println!("request was committed by another enclave, leaving the reveal to it");
+            return Ok(vec![]);
+        }
+        ixns.push(vault.reveal_randomness(keys.function, keys.signer, key));
+        derive_randomness_result(&key, &blockhash, request_account.seed)
+    };
+
+    // Same draw the program derives from the revealed request
+    let draw = draw_from_result(&result, marginfi_pda_account.snapshot_total_weight)
+        .map_err(|_| invalid("randomness request can't be drawn from"))?;
+    println!("draw: {:?}", draw);
+    let winner_index = entries.find(draw).ok_or_else(|| invalid("draw falls outside the snapshot"))?;
+    let actual_destination = entries.get(winner_index).unwrap().token_account;
+    let winner_account_data = io.rpc.get_account_data(&actual_destination).await?;
+    let new_winner_winner_chickum_dinner =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&winner_account_data)
+            .map_err(|_| invalid("invalid winning jarezi account"))?
+            .base
+            .owner;
+    let set_winner_params = SetWinnerParams {
+        index: winner_index as u32,
+    };
+    
+    println!("actual_destination: {:?}", actual_destination);
+    println!("new_winner_winner_chickum_dinner: {:?}", new_winner_winner_chickum_dinner);
+    println!("winner_winner_chickum_dinner: {:?}", winner_winner_chickum_dinner);
+
+    // the vault's Solend obligation, found by owner since its seed isn't kept on-chain
+    let obligations = io.rpc.get_program_accounts(
+        &SolendProgram::id(),
+        solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(vec![
+                solana_client::rpc_filter::RpcFilterType::DataSize(solend_sdk::state::Obligation::LEN as u64),
+                solana_client::rpc_filter::RpcFilterType::Memcmp(solana_client::rpc_filter::Memcmp {
+                offset: 42,
+                bytes: solana_client::rpc_filter::MemcmpEncodedBytes::Binary(marginfi_pda.to_string()),
+                encoding: None
+            })]),account_config: RpcAccountInfoConfig {
+                min_context_slot: None,
+                encoding: Some(solana_account_decoder::UiAccountEncoding::Base64Zstd),
+                commitment: Some(CommitmentConfig::processed()),
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: 0
+                }),
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    ).await?;
+    let obligation = obligations.first().ok_or_else(|| invalid("vault has no Solend obligation"))?.0;
+    println!("obligation: {:?}", obligation);
+
+    let pre_ixn = vault.set_winner_winner_chickum_dinner(
+        winner_winner_chickum_dinner,
+        new_winner_winner_chickum_dinner,
+        actual_destination,
+        jarezi_mint,
+        keys.function,
+        keys.signer,
+        set_winner_params,
+    );
+    // set_winner runs first in the same transaction, so the new winner is already in place
+    let ixn = vault.winner_winner_chickum_dinner_distribute(
+        new_winner_winner_chickum_dinner,
+        actual_destination,
+        Some(marginfi_pda_account.kickback_destination).filter(|key| *key != Pubkey::default()),
+        // harvest_withheld_fees below creates it ahead of the payout if it doesn't exist yet
+        Some(prize_pool_address(&marginfi_pda, &jarezi_mint)),
+        jarezi_mint,
+        collateral_pool.pool_mint,
+        obligation,
+        collateral_pool.reserve,
+        keys.function,
+        keys.signer,
+    );
+    // sweep the biggest withheld transfer fees into the prize pool first
+    let mut fee_sources: Vec<&Holder> = holders.iter().filter(|holder| holder.withheld > 0).collect();
+    fee_sources.sort_by(|a, b| b.withheld.cmp(&a.withheld));
+    let fee_sources: Vec<Pubkey> = fee_sources
+        .iter()
+        .take(MAX_FEE_SOURCES)
+        .map(|holder| holder.pubkey)
+        .collect();
+    let harvest_ixn = vault.harvest_withheld_fees(keys.signer, jarezi_mint, &fee_sources);
+    ixns.extend([harvest_ixn, pre_ixn, ixn]);
+    Ok(ixns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Discriminator;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use superior_randomness::HolderSnapshotAccount;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/draw.json");
+    const ALICE: &str = "GiKtrYFWABgFzgQsRL9qbFExdzcH5F7oz9hBva55g9d2";
+    const BOB: &str = "AZ2tw6qMKkPbp8Y5dfY71cramtRpn81h5vhnJgPfext6";
+    const CAROL: &str = "CkQ7bchKJwPGrwFPf3ydDFRtXgXSqkWAHdZe5CtPw4Sr";
+    // accounts sync_holder_snapshot takes ahead of the holders
+    const SYNC_ACCOUNTS: usize = 7;
+
+    fn pubkey(value: &str) -> Pubkey {
+        Pubkey::from_str(value).unwrap()
+    }
+
+    fn put_account(fixture: &mut Fixture, address: Pubkey, data: Vec<u8>, owner: Pubkey) {
+        let address = address.to_string();
+        fixture.accounts.retain(|dump| dump.pubkey != address);
+        fixture.accounts.push(AccountDump {
+            pubkey: address,
+            account: UiAccountDump {
+                lamports: 1_000_000,
+                data: (BASE64.encode(data), "base64".to_string()),
+                owner: owner.to_string(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        });
+    }
+
+    fn account_data(fixture: &Fixture, address: &Pubkey) -> Vec<u8> {
+        let address = address.to_string();
+        let dump = fixture.accounts.iter().find(|dump| dump.pubkey == address).unwrap();
+        BASE64.decode(&dump.account.data.0).unwrap()
+    }
+
+    fn put_snapshot(fixture: &mut Fixture, config: &Config, entries: &[(Pubkey, u64)]) {
+        let mut data = HolderSnapshotAccount::discriminator().to_vec();
+        data.push(255);
+        data.extend((entries.len() as u32).to_le_bytes());
+        let mut entries = entries.to_vec();
+        entries.sort();
+        for (token_account, weight) in entries {
+            data.extend(token_account.as_ref());
+            data.extend(weight.to_le_bytes());
+        }
+        put_account(fixture, config.vault.holder_snapshot, data, config.program_id);
+    }
+
+    fn synced(ixn: &Instruction) -> Vec<Pubkey> {
+        let mut holders: Vec<Pubkey> = ixn.accounts[SYNC_ACCOUNTS..].iter().map(|meta| meta.pubkey).collect();
+        holders.sort();
+        holders
+    }
+
+    fn sorted(mut keys: Vec<Pubkey>) -> Vec<Pubkey> {
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn commits_the_snapshot_and_randomness() {
+        let fixture = Fixture::read(FIXTURE).unwrap();
+        let config = fixture.config().unwrap();
+        let io = fixture.io().unwrap();
+        let ixns = draw_ixns(&config, fixture.keys().unwrap(), &io).await.unwrap();
+        assert_eq!(ixns.len(), 3);
+        assert!(ixns.iter().all(|ixn| ixn.program_id == config.program_id));
+
+        // the first draw creates the snapshot, dave holds nothing and the prize pool belongs to
+        // the vault, neither gets an entry
+        assert_eq!(ixns[0].accounts[2].pubkey, config.vault.holder_snapshot);
+        assert_eq!(synced(&ixns[0]), sorted(vec![pubkey(ALICE), pubkey(BOB), pubkey(CAROL)]));
+        assert_eq!(&ixns[1].data[..], &superior_randomness::instruction::PostHolderSnapshot::DISCRIMINATOR[..]);
+
+        let request_data = io.rpc.get_account_data(&config.vault.request).await.unwrap();
+        let request = client::decode_request(&request_data).unwrap();
+        let key = draw_key(&*io.sealing_key, &config.vault.request, &request).unwrap();
+        assert_eq!(&ixns[2].data[8..], &solana_program::hash::hashv(&[key.as_ref()]).to_bytes()[..]);
+    }
+
+    #[tokio::test]
+    async fn syncs_only_the_holders_that_changed() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        let config = fixture.config().unwrap();
+        // bob's balance moved since the last draw and the account the old entry points at is gone
+        let closed = Pubkey::new_unique();
+        put_snapshot(
+            &mut fixture,
+            &config,
+            &[(pubkey(ALICE), 100), (pubkey(BOB), 250), (pubkey(CAROL), 300), (closed, 40)],
+        );
+
+        let ixns = draw_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await.unwrap();
+        assert_eq!(ixns.len(), 3);
+        assert_eq!(synced(&ixns[0]), sorted(vec![pubkey(BOB), closed]));
+    }
+
+    #[tokio::test]
+    async fn commits_an_up_to_date_snapshot_without_syncing() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        let config = fixture.config().unwrap();
+        put_snapshot(&mut fixture, &config, &[(pubkey(ALICE), 100), (pubkey(BOB), 200), (pubkey(CAROL), 300)]);
+
+        let ixns = draw_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await.unwrap();
+        assert_eq!(ixns.len(), 2);
+        assert_eq!(&ixns[0].data[..], &superior_randomness::instruction::PostHolderSnapshot::DISCRIMINATOR[..]);
+    }
+
+    #[tokio::test]
+    async fn draws_from_the_committed_entries_after_transfers() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        let config = fixture.config().unwrap();
+        let vault = config.vault;
+
+        // committed before carol sold down to 300 and alice to 100
+        let entries = [(pubkey(ALICE), 1_000), (pubkey(BOB), 200), (pubkey(CAROL), 4_000)];
+        put_snapshot(&mut fixture, &config, &entries);
+        let mut marginfi_pda =
+            MarginFiPda::try_deserialize(&mut &account_data(&fixture, &vault.marginfi_pda)[..]).unwrap();
+        marginfi_pda.snapshot_total_weight = 5_200;
+        let mut data = vec![];
+        marginfi_pda.try_serialize(&mut data).unwrap();
+        put_account(&mut fixture, vault.marginfi_pda, data, config.program_id);
+
+        // seeded and revealed: seed_timestamp, reveal_timestamp and the result
+        let mut request = account_data(&fixture, &vault.request);
+        let result = solana_program::hash::hashv(&[b"revealed"]).to_bytes();
+        request[109..141].copy_from_slice(&result);
+        request[149..157].copy_from_slice(&1i64.to_le_bytes());
+        request[157..165].copy_from_slice(&1i64.to_le_bytes());
+        put_account(&mut fixture, vault.request, request, config.program_id);
+
+        let mut obligation = vec![0; solend_sdk::state::Obligation::LEN];
+        obligation[42..74].copy_from_slice(vault.marginfi_pda.as_ref());
+        put_account(&mut fixture, Pubkey::new_unique(), obligation, SolendProgram::id());
+
+        let ixns = draw_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await.unwrap();
+        assert_eq!(ixns.len(), 3);
+        let set_winner = &ixns[1];
+        assert_eq!(
+            &set_winner.data[..8],
+            &superior_randomness::instruction::SetWinnerWinnerChickumDinner::DISCRIMINATOR[..]
+        );
+        let params = SetWinnerParams::try_from_slice(&set_winner.data[8..]).unwrap();
+
+        // the entries sit in token account order, each over [weight before it, + its weight)
+        let mut ordered = entries.to_vec();
+        ordered.sort();
+        let draw = draw_from_result(&result, 5_200).unwrap();
+        let mut range_start = 0;
+        let winner = ordered
+            .iter()
+            .position(|(_, weight)| {
+                range_start += weight;
+                draw < range_start
+            })
+            .unwrap();
+        assert_eq!(params.index as usize, winner);
+        assert!(set_winner.accounts.iter().any(|meta| meta.pubkey == ordered[winner].0));
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_randomness_request() {
+        let mut fixture = Fixture::read(FIXTURE).unwrap();
+        let config = fixture.config().unwrap();
+        let request = config.vault.request.to_string();
+        fixture.accounts.retain(|dump| dump.pubkey != request);
+
+        let ixns = draw_ixns(&config, fixture.keys().unwrap(), &fixture.io().unwrap()).await.unwrap();
+        assert!(ixns.is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+// Everything the function reads from outside the enclave. Live runs go to the cluster, the
+// system clock and Gramine's sealing key; the simulate binary replays a fixture through the same
+// traits instead (see fixtures.rs).
+use crate::*;
+use async_trait::async_trait;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use switchboard_solana::solana_sdk::account::Account;
+
+#[async_trait]
+pub trait Rpc: Send + Sync {
+    async fn get_account(&self, key: &Pubkey) -> std::result::Result<Account, SbError>;
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> std::result::Result<Vec<(Pubkey, Account)>, SbError>;
+
+    async fn get_account_data(&self, key: &Pubkey) -> std::result::Result<Vec<u8>, SbError> {
+        Ok(self.get_account(key).await?.data)
+    }
+}
+
+#[async_trait]
+impl Rpc for RpcClient {
+    async fn get_account(&self, key: &Pubkey) -> std::result::Result<Account, SbError> {
+        RpcClient::get_account(self, key)
+            .await
+            .map_err(|e| SbError::CustomMessage(format!("{} fetch failed: {}", key, e)))
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> std::result::Result<Vec<(Pubkey, Account)>, SbError> {
+        RpcClient::get_program_accounts_with_config(self, program_id, config)
+            .await
+            .map_err(|e| SbError::CustomMessage(format!("{} accounts fetch failed: {}", program_id, e)))
+    }
+}
+
+/// Secret the draw's commit key is derived from. The key is revealed by a later run than the one
+/// that committed it, so it has to come back the same across runs and only inside the enclave.
+pub trait SealingKey: Send + Sync {
+    fn read(&self) -> std::result::Result<Vec<u8>, SbError>;
+}
+
+/// Gramine's MRENCLAVE sealing key: the same for every run of this image on one CPU, and
+/// unreadable outside the enclave.
+pub struct GramineSealingKey;
+
+const MRENCLAVE_SEALING_KEY: &str = "/dev/attestation/keys/_sgx_mrenclave";
+
+impl SealingKey for GramineSealingKey {
+    fn read(&self) -> std::result::Result<Vec<u8>, SbError> {
+        std::fs::read(MRENCLAVE_SEALING_KEY)
+            .map_err(|e| SbError::CustomMessage(format!("gramine sealing key unavailable: {}", e)))
+    }
+}
+
+/// Decides how many prize periods have elapsed.
+pub trait TimeSource: Send + Sync {
+    fn unix_timestamp(&self) -> std::result::Result<i64, SbError>;
+}
+
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn unix_timestamp(&self) -> std::result::Result<i64, SbError> {
+        Ok(std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| SbError::CustomMessage("system clock is before the epoch".to_string()))?
+            .as_secs() as i64)
+    }
+}
+
+#[derive(Clone)]
+pub struct Io {
+    pub rpc: Arc<dyn Rpc>,
+    pub time: Arc<dyn TimeSource>,
+    pub sealing_key: Arc<dyn SealingKey>,
+}
+
+impl Io {
+    pub fn live(rpc_url: &str) -> Io {
+        Io {
+            rpc: Arc::new(RpcClient::new_with_commitment(
+                rpc_url.to_string(),
+                CommitmentConfig::processed(),
+            )),
+            time: Arc::new(SystemClock),
+            sealing_key: Arc::new(GramineSealingKey),
+        }
+    }
+}
+
+/// The function account and enclave signer the instructions are built for, taken from the
+/// FunctionRunner on live runs.
+#[derive(Clone, Copy, Debug)]
+pub struct FunctionKeys {
+    pub function: Pubkey,
+    pub signer: Pubkey,
+}
+
+impl From<&FunctionRunner> for FunctionKeys {
+    fn from(runner: &FunctionRunner) -> Self {
+        FunctionKeys {
+            function: runner.function,
+            signer: runner.signer,
+        }
+    }
+}